@@ -1,7 +1,48 @@
-use cgmath;
-use bytemuck;
+use crate::input::{Action, Axis, Input};
+use crate::scene::SceneCamera;
 
-use crate::input::Input;
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    Orthographic,
+}
+
+impl Projection {
+    // name used in .arsc camera blocks
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "perspective" => Some(Projection::Perspective),
+            "orthographic" => Some(Projection::Orthographic),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Projection::Perspective => "perspective",
+            Projection::Orthographic => "orthographic",
+        }
+    }
+}
+
+// axis-aligned views for technical and layout shots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewPreset {
+    Top,    // looking down -Y, -Z up on screen
+    Front,  // looking down -Z
+    Side,   // looking down -X
+}
+
+impl ViewPreset {
+    // (yaw, pitch) in radians; top stops just short of straight down so look_at_rh keeps a valid up vector
+    fn orientation(self) -> (f32, f32) {
+        match self {
+            ViewPreset::Top => (-std::f32::consts::FRAC_PI_2, -89.9f32.to_radians()),
+            ViewPreset::Front => (-std::f32::consts::FRAC_PI_2, 0.0),
+            ViewPreset::Side => (std::f32::consts::PI, 0.0),
+        }
+    }
+}
 
 pub struct Camera {
     pub eye: cgmath::Point3<f32>,
@@ -13,13 +54,50 @@ pub struct Camera {
     pub zfar: f32,
     pub yaw: f32,   // horizontal rotation
     pub pitch: f32, // vertical rotation
+    pub projection: Projection,
+    // near = 1, far = 0 depth with an infinite far plane for perspective; needs a matching depth setup
+    pub reversed_z: bool,
+    pub ortho_height: f32, // visible height in world units for orthographic views; width follows aspect
+    // 0 = perspective, 1 = orthographic; eases towards `projection` for smooth switching
+    projection_blend: f32,
+}
+
+// seconds for a perspective <-> orthographic transition
+const PROJECTION_TRANSITION_TIME: f32 = 0.3;
+const MIN_ORTHO_HEIGHT: f32 = 0.01;
+const MAX_ORTHO_HEIGHT: f32 = 1000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    Fly,    // WASD + mouse look
+    Orbit,  // rotate around a target point, scroll to zoom, middle mouse to pan
 }
 
 pub struct CameraController {
-    pub speed: f32,
+    pub mode: CameraMode,
+    pub speed: f32,              // units per second
     pub sensitivity: f32,
+    pub acceleration: f32,       // how fast velocity catches up with input (1/s)
+    pub damping: f32,            // how fast velocity decays without input (1/s)
+    pub sprint_multiplier: f32,
+    velocity: cgmath::Vector3<f32>,
+    pub orbit_target: cgmath::Point3<f32>,
+    pub orbit_distance: f32,
 }
 
+const MIN_SPEED: f32 = 0.1;
+const MAX_SPEED: f32 = 100.0;
+// speed factor per scroll wheel line
+const SCROLL_SPEED_STEP: f32 = 1.1;
+// orbit distance factor per scroll wheel line
+const ZOOM_STEP: f32 = 1.1;
+const MIN_ORBIT_DISTANCE: f32 = 0.05;
+const MAX_ORBIT_DISTANCE: f32 = 500.0;
+// pan distance per mouse count, relative to the orbit distance
+const PAN_SENSITIVITY: f32 = 0.001;
+// extra room around a framed object
+const FRAME_MARGIN: f32 = 1.2;
+
 // camera matrix data sent to GPU
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -36,11 +114,184 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::from_co
     cgmath::Vector4::new(0.0, 0.0, 0.5, 1.0),
 );
 
+// like OPENGL_TO_WGPU_MATRIX but maps the near plane to depth 1 and the far plane to 0
+#[rustfmt::skip]
+pub const OPENGL_TO_WGPU_REVERSED_Z_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::from_cols(
+    cgmath::Vector4::new(1.0, 0.0, 0.0, 0.0),
+    cgmath::Vector4::new(0.0, 1.0, 0.0, 0.0),
+    cgmath::Vector4::new(0.0, 0.0, -0.5, 0.0),
+    cgmath::Vector4::new(0.0, 0.0, 0.5, 1.0),
+);
+
+// reversed-Z perspective with the far plane at infinity, directly in WGPU clip space:
+// depth = znear / distance, so 1 at the near plane and approaching 0 far away
+fn infinite_reversed_z_perspective(fovy: cgmath::Deg<f32>, aspect: f32, znear: f32) -> cgmath::Matrix4<f32> {
+    let f = 1.0 / (cgmath::Rad::from(fovy).0 * 0.5).tan();
+    #[rustfmt::skip]
+    let matrix = cgmath::Matrix4::new(
+        f / aspect, 0.0, 0.0,   0.0,
+        0.0,        f,   0.0,   0.0,
+        0.0,        0.0, 0.0,  -1.0,
+        0.0,        0.0, znear, 0.0,
+    );
+    matrix
+}
+
 impl Camera {
+    pub fn new(aspect: f32) -> Self {
+        let mut camera = Camera {
+            eye: (0.0, 0.0, 0.0).into(),
+            target: (0.0, 0.0, -1.0).into(),
+            up: cgmath::Vector3::unit_y(),
+            aspect,
+            fovy: 45.0,
+            znear: 0.1,
+            zfar: 100.0,
+            yaw: 0.0,
+            pitch: 0.0,
+            projection: Projection::Perspective,
+            reversed_z: false,
+            ortho_height: 5.0,
+            projection_blend: 0.0,
+        };
+        camera.apply(&SceneCamera::default());
+        camera
+    }
+
+    // jump to a camera defined in the scene
+    pub fn apply(&mut self, scene_camera: &SceneCamera) {
+        self.eye = scene_camera.position.into();
+        self.yaw = scene_camera.yaw.to_radians();
+        self.pitch = scene_camera.pitch.to_radians();
+        self.fovy = scene_camera.fovy;
+        self.znear = scene_camera.znear;
+        self.zfar = scene_camera.zfar;
+        self.projection = scene_camera.projection;
+        self.ortho_height = scene_camera.ortho_height;
+        self.projection_blend = self.projection_target();
+        self.target = self.eye + self.forward();
+    }
+
+    // place the eye and aim at a point; pitch stops short of straight up/down like the presets
+    pub fn look_at(&mut self, eye: cgmath::Point3<f32>, target: cgmath::Point3<f32>) {
+        use cgmath::InnerSpace;
+
+        let direction = target - eye;
+        self.eye = eye;
+        if direction.magnitude2() > 0.0 {
+            let direction = direction.normalize();
+            let max_pitch = 89.9f32.to_radians();
+            self.yaw = direction.z.atan2(direction.x);
+            self.pitch = direction.y.asin().clamp(-max_pitch, max_pitch);
+        }
+        self.target = self.eye + self.forward();
+    }
+
+    fn projection_target(&self) -> f32 {
+        match self.projection {
+            Projection::Perspective => 0.0,
+            Projection::Orthographic => 1.0,
+        }
+    }
+
+    // switch projection with a short transition; the plane at focus_distance keeps its size
+    pub fn set_projection(&mut self, projection: Projection, focus_distance: f32) {
+        if projection == Projection::Orthographic && self.projection != Projection::Orthographic {
+            self.ortho_height = self.perspective_height_at(focus_distance).clamp(MIN_ORTHO_HEIGHT, MAX_ORTHO_HEIGHT);
+        }
+        self.projection = projection;
+    }
+
+    pub fn toggle_projection(&mut self, focus_distance: f32) {
+        let projection = match self.projection {
+            Projection::Perspective => Projection::Orthographic,
+            Projection::Orthographic => Projection::Perspective,
+        };
+        self.set_projection(projection, focus_distance);
+    }
+
+    // orthographic axis view around the point focus_distance in front of the camera
+    pub fn apply_preset(&mut self, preset: ViewPreset, focus_distance: f32) {
+        let focus = self.eye + self.forward() * focus_distance;
+        (self.yaw, self.pitch) = preset.orientation();
+        self.eye = focus - self.forward() * focus_distance;
+        self.target = focus;
+        self.set_projection(Projection::Orthographic, focus_distance);
+    }
+
+    // visible height of the perspective frustum at a distance from the eye
+    pub fn perspective_height_at(&self, distance: f32) -> f32 {
+        2.0 * distance * cgmath::Rad::from(cgmath::Deg(self.fovy * 0.5)).0.tan()
+    }
+
+    // advance the projection transition, dt in seconds
+    pub fn update(&mut self, dt: f32) {
+        let target = self.projection_target();
+        let step = dt / PROJECTION_TRANSITION_TIME;
+        self.projection_blend = if self.projection_blend < target {
+            (self.projection_blend + step).min(target)
+        } else {
+            (self.projection_blend - step).max(target)
+        };
+    }
+
+    // projection into WGPU clip space (depth 0..1, reversed if reversed_z is set)
+    pub fn build_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        let depth_remap = if self.reversed_z { OPENGL_TO_WGPU_REVERSED_Z_MATRIX } else { OPENGL_TO_WGPU_MATRIX };
+        let perspective = || if self.reversed_z {
+            infinite_reversed_z_perspective(cgmath::Deg(self.fovy), self.aspect, self.znear)
+        } else {
+            depth_remap * cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar)
+        };
+        let orthographic = || {
+            let half_height = self.ortho_height * 0.5;
+            let half_width = half_height * self.aspect;
+            depth_remap * cgmath::ortho(-half_width, half_width, -half_height, half_height, self.znear, self.zfar)
+        };
+
+        match self.projection_blend {
+            blend if blend <= 0.0 => perspective(),
+            blend if blend >= 1.0 => orthographic(),
+            blend => {
+                // smoothstep-eased matrix blend; good enough for a short transition
+                let t = blend * blend * (3.0 - 2.0 * blend);
+                perspective() * (1.0 - t) + orthographic() * t
+            }
+        }
+    }
+
+    // the current view as a scene camera, e.g. to save it as a bookmark
+    pub fn to_scene_camera(&self, name: String) -> SceneCamera {
+        SceneCamera {
+            name,
+            position: self.eye.into(),
+            yaw: self.yaw.to_degrees(),
+            pitch: self.pitch.to_degrees(),
+            fovy: self.fovy,
+            znear: self.znear,
+            zfar: self.zfar,
+            projection: self.projection,
+            ortho_height: self.ortho_height,
+            is_default: false,
+        }
+    }
+
+    // view direction from yaw/pitch
+    pub fn forward(&self) -> cgmath::Vector3<f32> {
+        use cgmath::InnerSpace;
+        let (yaw_sin, yaw_cos) = self.yaw.sin_cos();
+        let (pitch_sin, pitch_cos) = self.pitch.sin_cos();
+        cgmath::Vector3::new(
+            yaw_cos * pitch_cos,
+            pitch_sin,
+            yaw_sin * pitch_cos,
+        ).normalize()
+    }
+
     pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
         let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
-        let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
-        return OPENGL_TO_WGPU_MATRIX * proj * view;
+        let proj = self.build_projection_matrix();
+        proj * view
     }
 }
 
@@ -60,16 +311,64 @@ impl CameraUniform {
 impl CameraController {
     pub fn new(speed: f32) -> Self {
         Self {
+            mode: CameraMode::Fly,
             speed,
             sensitivity: 0.002,
+            acceleration: 12.0,
+            damping: 8.0,
+            sprint_multiplier: 3.0,
+            velocity: cgmath::Vector3::new(0.0, 0.0, 0.0),
+            orbit_target: cgmath::Point3::new(0.0, 0.0, 0.0),
+            orbit_distance: 5.0,
         }
     }
 
-    pub fn update_camera(&mut self, camera: &mut Camera, input: &mut Input) {
-        use cgmath::InnerSpace;
+    // switch between fly and orbit while keeping the current eye and view direction
+    pub fn toggle_mode(&mut self, camera: &Camera) {
+        self.mode = match self.mode {
+            CameraMode::Fly => {
+                self.orbit_target = camera.eye + camera.forward() * self.orbit_distance;
+                CameraMode::Orbit
+            }
+            CameraMode::Orbit => {
+                self.velocity = cgmath::Vector3::new(0.0, 0.0, 0.0);
+                CameraMode::Fly
+            }
+        };
+    }
+
+    // orbit around a bounding sphere, far enough back for it to fill the view
+    pub fn frame(&mut self, camera: &mut Camera, center: cgmath::Point3<f32>, radius: f32) {
+        let half_fov = cgmath::Rad::from(cgmath::Deg(camera.fovy * 0.5)).0;
+        self.orbit_target = center;
+        self.orbit_distance = (radius / half_fov.sin() * FRAME_MARGIN).clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
+        // orthographic size doesn't depend on distance, fit the sphere (and the narrower axis) directly
+        let diameter = 2.0 * radius * FRAME_MARGIN;
+        camera.ortho_height = (diameter / camera.aspect.min(1.0)).clamp(MIN_ORTHO_HEIGHT, MAX_ORTHO_HEIGHT);
+        self.velocity = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        self.mode = CameraMode::Orbit;
+    }
 
-        // process mouse movement
-        let (raw_mouse_x, raw_mouse_y) = input.take_mouse_delta();
+    // drop leftover motion after the camera was moved externally (e.g. jumping to a bookmark)
+    pub fn sync_to_camera(&mut self, camera: &Camera) {
+        self.velocity = cgmath::Vector3::new(0.0, 0.0, 0.0);
+        if self.mode == CameraMode::Orbit {
+            self.orbit_target = camera.eye + camera.forward() * self.orbit_distance;
+        }
+    }
+
+    // dt is the frame time in seconds
+    pub fn update_camera(&mut self, camera: &mut Camera, input: &mut Input, dt: f32) {
+        let scroll = input.take_axis(Axis::Zoom, dt);
+        let mouse_delta = (input.take_axis(Axis::LookX, dt), input.take_axis(Axis::LookY, dt));
+
+        match self.mode {
+            CameraMode::Fly => self.update_fly(camera, input, scroll, mouse_delta, dt),
+            CameraMode::Orbit => self.update_orbit(camera, input, scroll, mouse_delta),
+        }
+    }
+
+    fn rotate(&self, camera: &mut Camera, (raw_mouse_x, raw_mouse_y): (f32, f32)) {
         let mouse_yaw = raw_mouse_x * self.sensitivity;
         let mouse_pitch = raw_mouse_y * self.sensitivity;
         
@@ -78,46 +377,67 @@ impl CameraController {
             camera.pitch -= mouse_pitch;
             camera.pitch = camera.pitch.clamp(-1.5, 1.5); // prevent camera flip
         }
+    }
 
-        // calculate direction vectors from yaw/pitch
-        let (yaw_sin, yaw_cos) = camera.yaw.sin_cos();
-        let (pitch_sin, pitch_cos) = camera.pitch.sin_cos();
-        
-        let forward = cgmath::Vector3::new(
-            yaw_cos * pitch_cos,
-            pitch_sin,
-            yaw_sin * pitch_cos,
-        ).normalize();
-        
+    fn update_fly(&mut self, camera: &mut Camera, input: &Input, scroll: f32, mouse_delta: (f32, f32), dt: f32) {
+        use cgmath::{InnerSpace, Zero};
+
+        // scroll wheel scales movement speed
+        if scroll != 0.0 {
+            self.speed = (self.speed * SCROLL_SPEED_STEP.powf(scroll)).clamp(MIN_SPEED, MAX_SPEED);
+        }
+
+        self.rotate(camera, mouse_delta);
+
+        let forward = camera.forward();
         let right = forward.cross(camera.up).normalize();
         let up = camera.up.normalize();
 
+        // gather movement inputs into a direction; analog sticks and triggers give partial lengths
+        let axis = |positive: Action, negative: Action| input.action_value(positive) - input.action_value(negative);
+        let mut direction = forward * axis(Action::MoveForward, Action::MoveBackward)
+            + right * axis(Action::MoveRight, Action::MoveLeft)
+            + up * axis(Action::MoveUp, Action::MoveDown);
+        // keep diagonals at full speed without going faster than a single key
+        if direction.magnitude2() > 1.0 {
+            direction = direction.normalize();
+        }
+
+        // ease velocity towards the target, frame-rate independent
+        let (target_velocity, rate) = if direction.magnitude2() > 0.0 {
+            let speed = if input.is_action_pressed(Action::Sprint) { self.speed * self.sprint_multiplier } else { self.speed };
+            (direction * speed, self.acceleration)
+        } else {
+            (cgmath::Vector3::zero(), self.damping)
+        };
+        self.velocity += (target_velocity - self.velocity) * (1.0 - (-rate * dt).exp());
+
+        camera.eye += self.velocity * dt;
         camera.target = camera.eye + forward;
+    }
 
-        // apply movement inputs
-        if input.is_forward_pressed {
-            camera.eye += forward * self.speed;
-            camera.target += forward * self.speed;
-        }
-        if input.is_backward_pressed {
-            camera.eye -= forward * self.speed;
-            camera.target -= forward * self.speed;
-        }
-        if input.is_right_pressed {
-            camera.eye += right * self.speed;
-            camera.target += right * self.speed;
-        }
-        if input.is_left_pressed {
-            camera.eye -= right * self.speed;
-            camera.target -= right * self.speed;
-        }
-        if input.is_space_pressed {
-            camera.eye += up * self.speed;
-            camera.target += up * self.speed;
+    fn update_orbit(&mut self, camera: &mut Camera, input: &Input, scroll: f32, mouse_delta: (f32, f32)) {
+        use cgmath::InnerSpace;
+
+        if input.is_action_pressed(Action::Pan) {
+            // drag the target in the view plane, scaled so the scene follows the cursor at any zoom
+            let forward = camera.forward();
+            let right = forward.cross(camera.up).normalize();
+            let up = right.cross(forward);
+            let pan_scale = self.orbit_distance * PAN_SENSITIVITY;
+            self.orbit_target += (up * mouse_delta.1 - right * mouse_delta.0) * pan_scale;
+        } else {
+            self.rotate(camera, mouse_delta);
         }
-        if input.is_shift_pressed {
-            camera.eye -= up * self.speed;
-            camera.target -= up * self.speed;
+
+        // scroll wheel zooms towards the target; orthographic extents shrink along with the distance
+        if scroll != 0.0 {
+            let zoom = ZOOM_STEP.powf(-scroll);
+            self.orbit_distance = (self.orbit_distance * zoom).clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
+            camera.ortho_height = (camera.ortho_height * zoom).clamp(MIN_ORTHO_HEIGHT, MAX_ORTHO_HEIGHT);
         }
+
+        camera.eye = self.orbit_target - camera.forward() * self.orbit_distance;
+        camera.target = self.orbit_target;
     }
 }
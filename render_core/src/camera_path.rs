@@ -0,0 +1,401 @@
+// keyframe length when packed for interpolation: position, target, fov
+const PACKED_LEN: usize = 7;
+// tolerance when deciding whether playback reached the end of a path
+const END_EPSILON: f32 = 1e-4;
+const DEFAULT_FOV: f32 = 45.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interpolation {
+    Linear,
+    // smooth curve through every key
+    CatmullRom,
+    // keys are control points of one curve; it only passes through the first and last
+    Bezier,
+}
+
+impl Interpolation {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Interpolation::Linear),
+            "catmull_rom" => Some(Interpolation::CatmullRom),
+            "bezier" => Some(Interpolation::Bezier),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Easing {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl Easing {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Easing::Linear),
+            "ease_in" => Some(Easing::EaseIn),
+            "ease_out" => Some(Easing::EaseOut),
+            "ease_in_out" => Some(Easing::EaseInOut),
+            _ => None,
+        }
+    }
+
+    // remap 0..1 progress; cubic curves so speed starts/ends at zero
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1.0 - (1.0 - t).powi(3),
+            Easing::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// One keyframe; yaw/pitch keys are stored as a target point one unit ahead
+#[derive(Debug, Clone, Copy)]
+pub struct PathKey {
+    pub time: f32,
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub fovy: f32,
+}
+
+impl PathKey {
+    // `<time> position x y z [look_at x y z | yaw deg pitch deg] [fov deg]`;
+    // view direction and fov default to the previous key's
+    pub fn parse(fields: &[&str], previous: Option<&PathKey>) -> Result<PathKey, String> {
+        let number = |index: usize| -> Result<f32, String> {
+            let value = fields.get(index).ok_or_else(|| format!("key is missing a value after '{}'", fields[index - 1]))?;
+            value.parse().map_err(|_| format!("invalid number '{}' in key", value))
+        };
+        let vec3 = |index: usize| -> Result<[f32; 3], String> { Ok([number(index)?, number(index + 1)?, number(index + 2)?]) };
+
+        let time = fields.first()
+            .and_then(|time| time.parse().ok())
+            .ok_or("key needs a time in seconds")?;
+        let mut position = None;
+        let mut look_at = None;
+        let (mut yaw, mut pitch) = (None, None);
+        let mut fovy = previous.map_or(DEFAULT_FOV, |key| key.fovy);
+
+        let mut index = 1;
+        while index < fields.len() {
+            match fields[index] {
+                "position" => { position = Some(vec3(index + 1)?); index += 4; }
+                "look_at" => { look_at = Some(vec3(index + 1)?); index += 4; }
+                "yaw" => { yaw = Some(number(index + 1)?); index += 2; }
+                "pitch" => { pitch = Some(number(index + 1)?); index += 2; }
+                "fov" => { fovy = number(index + 1)?; index += 2; }
+                field => return Err(format!("unknown key field '{}'", field)),
+            }
+        }
+
+        let position = position.ok_or("key needs a position")?;
+        let target = match (look_at, yaw, pitch, previous) {
+            (Some(look_at), _, _, _) => look_at,
+            (None, None, None, Some(previous)) => {
+                // keep looking the same way as the previous key
+                std::array::from_fn(|i| position[i] + previous.target[i] - previous.position[i])
+            }
+            (None, None, None, None) => return Err("the first key needs look_at or yaw/pitch".to_string()),
+            (None, yaw, pitch, _) => {
+                let (yaw, pitch) = (yaw.unwrap_or(-90.0).to_radians(), pitch.unwrap_or(0.0).to_radians());
+                [
+                    position[0] + pitch.cos() * yaw.cos(),
+                    position[1] + pitch.sin(),
+                    position[2] + pitch.cos() * yaw.sin(),
+                ]
+            }
+        };
+
+        Ok(PathKey { time, position, target, fovy })
+    }
+
+    fn pack(&self) -> [f32; PACKED_LEN] {
+        let [px, py, pz] = self.position;
+        let [tx, ty, tz] = self.target;
+        [px, py, pz, tx, ty, tz, self.fovy]
+    }
+}
+
+/// Where the camera is at one point of a path
+#[derive(Debug, Clone, Copy)]
+pub struct PathSample {
+    pub position: [f32; 3],
+    pub target: [f32; 3],
+    pub fovy: f32,
+}
+
+/// Keyframed camera move from a `camera_path` block
+#[derive(Debug, Clone)]
+pub struct CameraPath {
+    pub name: String,
+    pub keys: Vec<PathKey>,
+    pub interpolation: Interpolation,
+    pub easing: Easing,
+    pub looping: bool,
+}
+
+impl CameraPath {
+    pub fn new(name: String) -> Self {
+        CameraPath {
+            name,
+            keys: Vec::new(),
+            interpolation: Interpolation::CatmullRom,
+            easing: Easing::Linear,
+            looping: false,
+        }
+    }
+
+    // called when the block ends
+    pub fn validate(&self) -> Result<(), String> {
+        if self.keys.len() < 2 {
+            return Err(format!("camera path '{}' needs at least two keys", self.name));
+        }
+        if self.keys.windows(2).any(|pair| pair[1].time <= pair[0].time) {
+            return Err(format!("camera path '{}' has key times that don't increase", self.name));
+        }
+        Ok(())
+    }
+
+    pub fn duration(&self) -> f32 {
+        match (self.keys.first(), self.keys.last()) {
+            (Some(first), Some(last)) => last.time - first.time,
+            _ => 0.0,
+        }
+    }
+
+    pub fn is_past_end(&self, elapsed: f32) -> bool {
+        elapsed > self.duration() + END_EPSILON
+    }
+
+    // true once non-looping playback has shown the last key
+    pub fn is_finished(&self, elapsed: f32) -> bool {
+        !self.looping && self.is_past_end(elapsed)
+    }
+
+    // elapsed is seconds since playback started
+    pub fn sample(&self, elapsed: f32) -> PathSample {
+        let duration = self.duration();
+        let elapsed = if self.looping && duration > 0.0 { elapsed.rem_euclid(duration) } else { elapsed };
+        let progress = if duration > 0.0 { (elapsed / duration).clamp(0.0, 1.0) } else { 0.0 };
+        let progress = self.easing.apply(progress);
+
+        let packed = match self.interpolation {
+            Interpolation::Bezier => {
+                let points: Vec<[f32; PACKED_LEN]> = self.keys.iter().map(PathKey::pack).collect();
+                de_casteljau(points, progress)
+            }
+            Interpolation::Linear | Interpolation::CatmullRom => {
+                let time = self.keys[0].time + progress * duration;
+                // last segment whose start is at or before the time
+                let segment = self.keys.windows(2)
+                    .position(|pair| time <= pair[1].time)
+                    .unwrap_or(self.keys.len() - 2);
+                let (start, end) = (&self.keys[segment], &self.keys[segment + 1]);
+                let t = ((time - start.time) / (end.time - start.time)).clamp(0.0, 1.0);
+
+                if self.interpolation == Interpolation::Linear {
+                    lerp(start.pack(), end.pack(), t)
+                } else {
+                    // end keys are repeated as their own neighbours
+                    let before = &self.keys[segment.saturating_sub(1)];
+                    let after = &self.keys[(segment + 2).min(self.keys.len() - 1)];
+                    catmull_rom(before.pack(), start.pack(), end.pack(), after.pack(), t)
+                }
+            }
+        };
+
+        PathSample {
+            position: [packed[0], packed[1], packed[2]],
+            target: [packed[3], packed[4], packed[5]],
+            fovy: packed[6],
+        }
+    }
+}
+
+fn lerp(a: [f32; PACKED_LEN], b: [f32; PACKED_LEN], t: f32) -> [f32; PACKED_LEN] {
+    std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+// uniform Catmull-Rom between p1 and p2
+fn catmull_rom(p0: [f32; PACKED_LEN], p1: [f32; PACKED_LEN], p2: [f32; PACKED_LEN], p3: [f32; PACKED_LEN], t: f32) -> [f32; PACKED_LEN] {
+    let (t2, t3) = (t * t, t * t * t);
+    std::array::from_fn(|i| 0.5 * (
+        2.0 * p1[i]
+            + (p2[i] - p0[i]) * t
+            + (2.0 * p0[i] - 5.0 * p1[i] + 4.0 * p2[i] - p3[i]) * t2
+            + (3.0 * p1[i] - p0[i] - 3.0 * p2[i] + p3[i]) * t3
+    ))
+}
+
+fn de_casteljau(mut points: Vec<[f32; PACKED_LEN]>, t: f32) -> [f32; PACKED_LEN] {
+    while points.len() > 1 {
+        points = points.windows(2).map(|pair| lerp(pair[0], pair[1], t)).collect();
+    }
+    points[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: [f32; 3], expected: [f32; 3]) {
+        assert!(actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-4), "{:?} != {:?}", actual, expected);
+    }
+
+    fn key(line: &str, previous: Option<&PathKey>) -> Result<PathKey, String> {
+        PathKey::parse(&line.split_whitespace().collect::<Vec<_>>(), previous)
+    }
+
+    // keys at x = 0, 10, 20 and 30, one every 1, 1 and 2 seconds
+    fn path(interpolation: Interpolation) -> CameraPath {
+        let mut path = CameraPath::new("test".to_string());
+        path.interpolation = interpolation;
+        for line in ["0 position 0 0 0 look_at 0 0 -1", "1 position 10 0 0 fov 60", "2 position 20 0 0", "4 position 30 0 0"] {
+            let key = key(line, path.keys.last()).unwrap();
+            path.keys.push(key);
+        }
+        path.validate().unwrap();
+        path
+    }
+
+    #[test]
+    fn keys_parse_their_view_from_look_at_yaw_pitch_or_the_previous_key() {
+        let first = key("0.5 position 1 2 3 look_at 1 2 0", None).unwrap();
+        assert_eq!(first.time, 0.5);
+        assert_eq!(first.position, [1.0, 2.0, 3.0]);
+        assert_eq!(first.target, [1.0, 2.0, 0.0]);
+        assert_eq!(first.fovy, DEFAULT_FOV);
+
+        let second = key("2 fov 30 position 0 0 0", Some(&first)).unwrap();
+        assert_near(second.target, [0.0, 0.0, -3.0]);
+        assert_eq!(second.fovy, 30.0);
+        let third = key("3 position 0 0 0", Some(&second)).unwrap();
+        assert_eq!(third.fovy, 30.0);
+
+        // yaw 0 looks along +x, pitch 90 straight up; a missing one of the two defaults to -90 / 0
+        assert_near(key("0 position 0 0 0 yaw 0 pitch 0", None).unwrap().target, [1.0, 0.0, 0.0]);
+        assert_near(key("0 position 0 0 0 pitch 90", None).unwrap().target, [0.0, 1.0, 0.0]);
+        assert_near(key("0 position 0 0 0 yaw 90", None).unwrap().target, [0.0, 0.0, 1.0]);
+
+        for (line, error) in [
+            ("position 0 0 0 look_at 0 0 1", "key needs a time in seconds"),
+            ("0 look_at 0 0 1", "key needs a position"),
+            ("0 position 0 0 0", "the first key needs look_at or yaw/pitch"),
+            ("0 position 0 0", "key is missing a value after '0'"),
+            ("0 position 0 0 x yaw 0", "invalid number 'x' in key"),
+            ("0 position 0 0 0 yaw 0 roll 5", "unknown key field 'roll'"),
+        ] {
+            assert_eq!(key(line, None).unwrap_err(), error, "{}", line);
+        }
+    }
+
+    #[test]
+    fn paths_need_two_keys_in_time_order() {
+        let mut path = path(Interpolation::Linear);
+        assert_eq!(path.duration(), 4.0);
+        path.keys.truncate(1);
+        assert!(path.validate().is_err());
+
+        let mut path = self::path(Interpolation::Linear);
+        path.keys[2].time = 1.0;
+        assert_eq!(path.validate().unwrap_err(), "camera path 'test' has key times that don't increase");
+    }
+
+    #[test]
+    fn every_interpolation_starts_and_ends_on_the_end_keys() {
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom, Interpolation::Bezier] {
+            let path = path(interpolation);
+            assert_near(path.sample(0.0).position, [0.0, 0.0, 0.0]);
+            assert_near(path.sample(4.0).position, [30.0, 0.0, 0.0]);
+            // outside the path the samples stay on the end keys
+            assert_near(path.sample(-1.0).position, [0.0, 0.0, 0.0]);
+            assert_near(path.sample(9.0).position, [30.0, 0.0, 0.0]);
+            assert_eq!(path.sample(9.0).fovy, 60.0);
+            assert!(!path.is_finished(4.0) && path.is_finished(4.1));
+        }
+    }
+
+    #[test]
+    fn linear_and_catmull_rom_pass_through_every_key() {
+        for interpolation in [Interpolation::Linear, Interpolation::CatmullRom] {
+            let path = path(interpolation);
+            for (time, x) in [(1.0, 10.0), (2.0, 20.0)] {
+                assert_near(path.sample(time).position, [x, 0.0, 0.0]);
+            }
+        }
+
+        // linear moves at a steady speed within each segment, by key time rather than key count
+        let linear = path(Interpolation::Linear);
+        assert_near(linear.sample(0.5).position, [5.0, 0.0, 0.0]);
+        assert_near(linear.sample(3.0).position, [25.0, 0.0, 0.0]);
+        assert!((linear.sample(0.5).fovy - (DEFAULT_FOV + 60.0) / 2.0).abs() < 1e-4);
+        assert_near(linear.sample(0.5).target, [5.0, 0.0, -1.0]);
+
+        // Bezier keys are control points: the middle ones pull the curve without being reached
+        let bezier = path(Interpolation::Bezier);
+        assert!((bezier.sample(1.0).position[0] - 10.0).abs() > 0.1);
+    }
+
+    #[test]
+    fn looping_paths_wrap_and_easing_reshapes_progress() {
+        let mut path = path(Interpolation::Linear);
+        path.looping = true;
+        assert_near(path.sample(4.5).position, path.sample(0.5).position);
+        assert_near(path.sample(10.0).position, [20.0, 0.0, 0.0]);
+        assert!(!path.is_finished(100.0));
+
+        path.looping = false;
+        let uneased = path.clone();
+        for (easing, expected) in [(Easing::Linear, 0.25), (Easing::EaseIn, 0.015625), (Easing::EaseOut, 0.578125), (Easing::EaseInOut, 0.15625)] {
+            assert!((easing.apply(0.25) - expected).abs() < 1e-6, "{:?}", easing);
+            assert_eq!((easing.apply(0.0), easing.apply(1.0)), (0.0, 1.0), "{:?}", easing);
+            path.easing = easing;
+            // a quarter of the way in, the camera is where the uneased path is after 4 * expected seconds
+            assert_near(path.sample(1.0).position, uneased.sample(4.0 * expected).position);
+        }
+    }
+
+    #[test]
+    fn camera_path_blocks_parse_from_scene_files() {
+        let root = std::env::temp_dir().join(format!("arbitra_camera_path_{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("paths.arsc"), "\
+camera_path
+name: Flyby
+interpolation: bezier
+easing: ease_in_out
+loop: true
+key: 0 position 0 1 5 look_at 0 0 0
+key: 2 position 5 1 0 fov 60
+camera_path
+key: 0 position 0 0 0 yaw 0 pitch 0
+key: 1 position 1 0 0
+").unwrap();
+        let scene = crate::scene::Scene::load_from_arsc(root.join("paths.arsc"), root.to_str().unwrap()).unwrap();
+        assert_eq!(scene.camera_paths.len(), 2);
+        let flyby = &scene.camera_paths[0];
+        assert_eq!((flyby.name.as_str(), flyby.interpolation, flyby.easing, flyby.looping), ("Flyby", Interpolation::Bezier, Easing::EaseInOut, true));
+        assert_eq!(flyby.keys.len(), 2);
+        assert_eq!(flyby.keys[1].fovy, 60.0);
+        let second = &scene.camera_paths[1];
+        assert_eq!((second.name.as_str(), second.interpolation, second.looping), ("Path2", Interpolation::CatmullRom, false));
+        assert_near(second.keys[1].target, [2.0, 0.0, 0.0]);
+
+        for (block, error) in [
+            ("camera_path\nname: One\nkey: 0 position 0 0 0 yaw 0\n", "camera path 'One' needs at least two keys"),
+            ("camera_path\nname: Bad\ninterpolation: cubic\n", "Unknown interpolation 'cubic' in camera path 'Bad'"),
+            ("camera_path\nname: Bad\neasing: bounce\n", "Unknown easing 'bounce' in camera path 'Bad'"),
+            ("camera_path\nname: Bad\nkey: 0 position 0 0 0\n", "In camera path 'Bad': the first key needs look_at or yaw/pitch"),
+        ] {
+            std::fs::write(root.join("bad.arsc"), block).unwrap();
+            let result = crate::scene::Scene::load_from_arsc(root.join("bad.arsc"), root.to_str().unwrap());
+            assert_eq!(result.unwrap_err().to_string(), error);
+        }
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,47 @@
+use gilrs::{EventType, Gilrs, GilrsBuilder};
+
+use crate::input::{GamepadEvent, Input, InputEvent};
+
+/// Polls connected gamepads and feeds their events into `Input`
+pub struct Gamepads {
+    gilrs: Gilrs,
+}
+
+impl Gamepads {
+    pub fn new() -> Result<Self, String> {
+        // Input applies its own (configurable) deadzone, so gilrs' filters are left off
+        let gilrs = GilrsBuilder::new()
+            .with_default_filters(false)
+            .build()
+            .map_err(|e| format!("Failed to initialize gamepad support: {}", e))?;
+        for (_, gamepad) in gilrs.gamepads() {
+            println!("Gamepad connected: {}", gamepad.name());
+        }
+        Ok(Gamepads { gilrs })
+    }
+
+    // drain pending events; call once per frame before reading input
+    pub fn poll(&mut self, input: &mut Input) {
+        while let Some(event) = self.gilrs.next_event() {
+            match event.event {
+                EventType::Connected => println!("Gamepad connected: {}", self.gilrs.gamepad(event.id).name()),
+                EventType::Disconnected => println!("Gamepad disconnected: {}", self.gilrs.gamepad(event.id).name()),
+                _ => {}
+            }
+            if let Some(gamepad_event) = translate(event.event) {
+                input.handle_event(InputEvent::Gamepad(usize::from(event.id), gamepad_event));
+            }
+        }
+    }
+}
+
+fn translate(event: EventType) -> Option<GamepadEvent> {
+    match event {
+        EventType::ButtonPressed(button, _) => Some(GamepadEvent::ButtonPressed(button)),
+        EventType::ButtonReleased(button, _) => Some(GamepadEvent::ButtonReleased(button)),
+        EventType::ButtonChanged(button, value, _) => Some(GamepadEvent::ButtonChanged(button, value)),
+        EventType::AxisChanged(axis, value, _) => Some(GamepadEvent::AxisChanged(axis, value)),
+        EventType::Disconnected => Some(GamepadEvent::Disconnected),
+        _ => None,
+    }
+}
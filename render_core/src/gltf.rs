@@ -0,0 +1,656 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cgmath::{InnerSpace, Matrix, Matrix3, Matrix4, Quaternion, SquareMatrix, Vector3, Vector4};
+
+use crate::json::Json;
+use crate::material::{Material, VertexColorMode};
+use crate::normals::NormalMode;
+use crate::object::MeshData;
+use crate::vertex::Vertex;
+
+pub const GLB_MAGIC: &[u8; 4] = b"glTF";
+pub const GLB_JSON_CHUNK: u32 = 0x4e4f_534a;
+pub const GLB_BIN_CHUNK: u32 = 0x004e_4942;
+// required extensions the loader handles; files requiring any other are rejected
+const SUPPORTED_EXTENSIONS: &[&str] = &["KHR_mesh_quantization"];
+// an accessor without a bufferView has no data to bound its count, so it gets a fixed limit (64 MiB of floats)
+const MAX_ZERO_FILLED_VALUES: usize = 1 << 24;
+
+/// A .gltf or .glb file with its buffers loaded
+pub struct Gltf {
+    pub json: Json,
+    buffers: Vec<Vec<u8>>,
+    // folder relative URIs are resolved against
+    dir: PathBuf,
+    // buffer files read besides the .gltf itself, for hot reload
+    pub external_files: Vec<PathBuf>,
+}
+
+impl Gltf {
+    pub fn open(path: &Path) -> Result<Gltf, String> {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let (text, mut bin_chunk) = if bytes.starts_with(GLB_MAGIC) {
+            split_glb(&bytes)?
+        } else {
+            (String::from_utf8(bytes).map_err(|_| "the file is neither GLB nor UTF-8 JSON".to_string())?, None)
+        };
+        let json = Json::parse(&text).map_err(|e| format!("invalid glTF JSON: {}", e))?;
+
+        let version = json.get("asset").and_then(|asset| asset.get("version")).and_then(Json::as_str).unwrap_or("");
+        if !version.starts_with("2.") {
+            return Err(format!("glTF version '{}' is not supported, only 2.x", version));
+        }
+        for extension in json.get("extensionsRequired").map_or(&[][..], Json::items) {
+            let name = extension.as_str().unwrap_or("?");
+            if !SUPPORTED_EXTENSIONS.contains(&name) {
+                return Err(format!("the file requires the extension {}, which isn't supported", name));
+            }
+        }
+
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        let mut buffers = Vec::new();
+        let mut external_files = Vec::new();
+        for (index, buffer) in json.get("buffers").map_or(&[][..], Json::items).iter().enumerate() {
+            let data = match buffer.get("uri").and_then(Json::as_str) {
+                Some(uri) if uri.starts_with("data:") => decode_data_uri(uri)?,
+                Some(uri) => {
+                    let file = dir.join(percent_decode(uri));
+                    let data = fs::read(&file).map_err(|e| format!("buffer {} ({}): {}", index, file.display(), e))?;
+                    external_files.push(file);
+                    data
+                }
+                // only the first buffer of a GLB may leave out the URI
+                None if index == 0 => bin_chunk.take().ok_or("buffer 0 has no URI and there is no GLB binary chunk")?,
+                None => return Err(format!("buffer {} has no URI", index)),
+            };
+            let length = buffer.get("byteLength").and_then(Json::as_usize).unwrap_or(0);
+            if data.len() < length {
+                return Err(format!("buffer {} is {} bytes, but byteLength says {}", index, data.len(), length));
+            }
+            buffers.push(data);
+        }
+
+        Ok(Gltf { json, buffers, dir, external_files })
+    }
+
+    // a top-level array such as "meshes"; empty when the file has none
+    pub fn list(&self, name: &str) -> &[Json] {
+        self.json.get(name).map_or(&[], Json::items)
+    }
+
+    pub fn item(&self, list: &str, index: usize) -> Result<&Json, String> {
+        self.list(list).get(index).ok_or_else(|| format!("{} {} doesn't exist", list, index))
+    }
+
+    // nodes of the default scene (or the first); without scenes, every node nobody lists as a child
+    pub fn scene_roots(&self) -> Vec<usize> {
+        let scene = self.json.get("scene").and_then(Json::as_usize).unwrap_or(0);
+        if let Some(scene) = self.list("scenes").get(scene) {
+            return scene.get("nodes").map_or(&[][..], Json::items).iter().filter_map(Json::as_usize).collect();
+        }
+        let children: Vec<usize> = self.list("nodes").iter()
+            .flat_map(|node| node.get("children").map_or(&[][..], Json::items))
+            .filter_map(Json::as_usize)
+            .collect();
+        (0..self.list("nodes").len()).filter(|node| !children.contains(node)).collect()
+    }
+
+    /// Values of an accessor as floats (normalized integers mapped to 0..1 or -1..1),
+    /// with the number of components per element
+    pub fn read_accessor(&self, index: usize) -> Result<(Vec<f32>, usize), String> {
+        let accessor = self.item("accessors", index)?;
+        let count = accessor.get("count").and_then(Json::as_usize)
+            .ok_or_else(|| format!("accessor {} has no count", index))?;
+        let components = match accessor.get("type").and_then(Json::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            other => return Err(format!("accessor {} has unknown type {:?}", index, other)),
+        };
+        let component_type = accessor.get("componentType").and_then(Json::as_usize)
+            .ok_or_else(|| format!("accessor {} has no componentType", index))?;
+        let normalized = accessor.get("normalized").and_then(Json::as_bool).unwrap_or(false);
+        let offset = byte_count(accessor, "byteOffset", 0)
+            .ok_or_else(|| format!("accessor {} has an invalid byteOffset", index))?;
+
+        let mut values = match accessor.get("bufferView").and_then(Json::as_usize) {
+            Some(view) => self.read_view(view, offset, component_type, components, normalized, count)?,
+            // without a buffer view the values start as zeros, usually to be filled in by sparse data
+            None => match count.checked_mul(components) {
+                Some(length) if length <= MAX_ZERO_FILLED_VALUES => vec![0.0; length],
+                _ => return Err(format!("accessor {} has no bufferView and a count of {}", index, count)),
+            },
+        };
+
+        if let Some(sparse) = accessor.get("sparse") {
+            let sparse_count = sparse.get("count").and_then(Json::as_usize).unwrap_or(0);
+            let view_of = |part: &str| -> Result<(usize, usize, &Json), String> {
+                let part = sparse.get(part).ok_or_else(|| format!("accessor {} sparse data has no {}", index, part))?;
+                let view = part.get("bufferView").and_then(Json::as_usize)
+                    .ok_or_else(|| format!("accessor {} sparse data has no bufferView", index))?;
+                let offset = byte_count(part, "byteOffset", 0)
+                    .ok_or_else(|| format!("accessor {} sparse data has an invalid byteOffset", index))?;
+                Ok((view, offset, part))
+            };
+            let (indices_view, indices_offset, indices) = view_of("indices")?;
+            let indices_type = indices.get("componentType").and_then(Json::as_usize).unwrap_or(0);
+            let targets = self.read_view(indices_view, indices_offset, indices_type, 1, false, sparse_count)?;
+            let (values_view, values_offset, _) = view_of("values")?;
+            let replacements = self.read_view(values_view, values_offset, component_type, components, normalized, sparse_count)?;
+
+            for (replacement, &target) in replacements.chunks_exact(components).zip(&targets) {
+                let target = target as usize;
+                let element = (target < count).then(|| &mut values[target * components..(target + 1) * components])
+                    .ok_or_else(|| format!("accessor {} sparse index {} is out of range", index, target))?;
+                element.copy_from_slice(replacement);
+            }
+        }
+        Ok((values, components))
+    }
+
+    // `count` elements of `components` values each from a buffer view; the range is checked before allocating
+    fn read_view(&self, index: usize, offset: usize, component_type: usize, components: usize, normalized: bool, count: usize) -> Result<Vec<f32>, String> {
+        let view = self.item("bufferViews", index)?;
+        let buffer = view.get("buffer").and_then(Json::as_usize).and_then(|buffer| self.buffers.get(buffer))
+            .ok_or_else(|| format!("bufferView {} refers to a missing buffer", index))?;
+        let data = view_range(view).and_then(|range| buffer.get(range))
+            .ok_or_else(|| format!("bufferView {} is outside its buffer", index))?;
+
+        let size = component_size(component_type)?;
+        let element_size = size * components;
+        // the spec allows strides of 4 to 252 bytes in steps of 4; a smaller stride than the element would
+        // let any count pass the end check below
+        let stride = byte_count(view, "byteStride", element_size)
+            .filter(|&stride| view.get("byteStride").is_none()
+                || (stride >= element_size && (4..=252).contains(&stride) && stride % 4 == 0))
+            .ok_or_else(|| format!("bufferView {} has an invalid byteStride", index))?;
+        // end of the last element, None when it overflows
+        let end = match count.checked_sub(1) {
+            Some(last) => last.checked_mul(stride)
+                .and_then(|bytes| bytes.checked_add(offset))
+                .and_then(|bytes| bytes.checked_add(element_size)),
+            None => Some(0),
+        };
+        let Some(length) = count.checked_mul(components).filter(|_| end.is_some_and(|end| end <= data.len())) else {
+            return Err(format!("an accessor reads past the end of bufferView {}", index));
+        };
+
+        let mut values = Vec::with_capacity(length);
+        for element in 0..count {
+            for component in 0..components {
+                let at = offset + element * stride + component * size;
+                values.push(read_component(&data[at..at + size], component_type, normalized));
+            }
+        }
+        Ok(values)
+    }
+
+    /// Encoded bytes (PNG, JPEG) of an image, whether external, a data URI or inside a buffer
+    pub fn image_bytes(&self, index: usize) -> Result<Vec<u8>, String> {
+        let image = self.item("images", index)?;
+        if let Some(uri) = image.get("uri").and_then(Json::as_str) {
+            if uri.starts_with("data:") {
+                return decode_data_uri(uri);
+            }
+            let file = self.dir.join(percent_decode(uri));
+            return fs::read(&file).map_err(|e| format!("image {} ({}): {}", index, file.display(), e));
+        }
+        let view_index = image.get("bufferView").and_then(Json::as_usize)
+            .ok_or_else(|| format!("image {} has neither a URI nor a bufferView", index))?;
+        let view = self.item("bufferViews", view_index)?;
+        view.get("buffer").and_then(Json::as_usize).and_then(|buffer| self.buffers.get(buffer))
+            .and_then(|buffer| buffer.get(view_range(view)?))
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| format!("image {} points outside its buffer", index))
+    }
+
+    // texture path for Material::albedo_texture, relative to the assets folder; images stored in
+    // the file itself are addressed as `<file>#image<index>`
+    fn texture_path(&self, texture: usize, asset_path: &str) -> Result<String, String> {
+        let image = self.item("textures", texture)?.get("source").and_then(Json::as_usize)
+            .ok_or_else(|| format!("texture {} has no image in a supported format", texture))?;
+        match self.item("images", image)?.get("uri").and_then(Json::as_str) {
+            Some(uri) if !uri.starts_with("data:") => Ok(match asset_path.rsplit_once('/') {
+                Some((dir, _)) => format!("{}/{}", dir, percent_decode(uri)),
+                None => percent_decode(uri),
+            }),
+            _ => Ok(format!("{}#image{}", asset_path, image)),
+        }
+    }
+
+    /// Material from the glTF material at `index`; anything it can't express is added to `notes`
+    pub fn material(&self, index: usize, asset_path: &str, notes: &mut Vec<String>) -> Material {
+        let mut material = Material::default();
+        let Ok(json) = self.item("materials", index) else {
+            notes.push(format!("material {} doesn't exist, the default material is used", index));
+            return material;
+        };
+        material.name = json.get("name").and_then(Json::as_str).map_or_else(|| format!("material {}", index), str::to_string);
+        let mut dropped = |what: &str| notes.push(format!("material '{}': {} is not supported and was dropped", material.name, what));
+
+        let pbr = json.get("pbrMetallicRoughness");
+        let pbr_value = |key: &str| pbr.and_then(|pbr| pbr.get(key));
+        material.base_color = pbr_value("baseColorFactor").and_then(Json::as_f32_array).unwrap_or([1.0; 4]);
+        material.metallic = pbr_value("metallicFactor").and_then(Json::as_f32).unwrap_or(1.0);
+        material.roughness = pbr_value("roughnessFactor").and_then(Json::as_f32).unwrap_or(1.0);
+        if let Some(texture) = pbr_value("baseColorTexture") {
+            if texture.get("texCoord").and_then(Json::as_usize).unwrap_or(0) != 0 {
+                dropped("a base color texture on a second UV set");
+            } else if let Some(texture) = texture.get("index").and_then(Json::as_usize) {
+                match self.texture_path(texture, asset_path) {
+                    Ok(path) => material.albedo_texture = path,
+                    Err(e) => dropped(&format!("the base color texture ({})", e)),
+                }
+            }
+        }
+        if pbr_value("metallicRoughnessTexture").is_some() {
+            dropped("the metallic-roughness texture");
+        }
+        for (key, what) in [("normalTexture", "the normal map"), ("occlusionTexture", "the occlusion map"), ("emissiveTexture", "the emissive texture")] {
+            if json.get(key).is_some() {
+                dropped(what);
+            }
+        }
+        if json.get("emissiveFactor").and_then(Json::as_f32_array::<3>).is_some_and(|factor| factor != [0.0; 3]) {
+            dropped("emissiveFactor (set emissive: on the object instead)");
+        }
+        match json.get("alphaMode").and_then(Json::as_str) {
+            Some("BLEND") => dropped("alpha blending (drawn opaque)"),
+            Some("MASK") => dropped("alphaMode MASK (enable the ALPHA_MASK shader define instead)"),
+            _ => {}
+        }
+        for (extension, _) in json.get("extensions").map_or(&[][..], Json::members) {
+            dropped(&format!("the extension {}", extension));
+        }
+        material
+    }
+
+    /// Triangles of one mesh primitive, in the mesh's own space; None (with a note) when it isn't made of triangles
+    pub fn primitive_mesh(&self, mesh: usize, primitive: &Json, notes: &mut Vec<String>) -> Result<Option<MeshData>, String> {
+        let mode = primitive.get("mode").and_then(Json::as_usize).unwrap_or(4);
+        if !(4..=6).contains(&mode) {
+            notes.push(format!("mesh {}: skipped a primitive of points or lines (mode {}), only triangles are drawn", mesh, mode));
+            return Ok(None);
+        }
+        let attributes = primitive.get("attributes");
+        let attribute = |name: &str| attributes.and_then(|attributes| attributes.get(name)).and_then(Json::as_usize);
+        let Some(position_accessor) = attribute("POSITION") else {
+            notes.push(format!("mesh {}: skipped a primitive without positions", mesh));
+            return Ok(None);
+        };
+        for (name, _) in attributes.map_or(&[][..], Json::members) {
+            if !["POSITION", "NORMAL", "TEXCOORD_0", "COLOR_0"].contains(&name.as_str()) {
+                notes.push(format!("mesh {}: ignored the {} attribute", mesh, name));
+            }
+        }
+        if primitive.get("targets").is_some() {
+            notes.push(format!("mesh {}: ignored morph targets", mesh));
+        }
+
+        let (positions, _) = self.read_accessor_as(position_accessor, &[3])?;
+        let count = positions.len() / 3;
+        let optional = |name: &str, components: &[usize]| -> Result<Option<(Vec<f32>, usize)>, String> {
+            let Some(accessor) = attribute(name) else {
+                return Ok(None);
+            };
+            let (values, width) = self.read_accessor_as(accessor, components)?;
+            if values.len() / width != count {
+                return Err(format!("mesh {}: {} has a different count than POSITION", mesh, name));
+            }
+            Ok(Some((values, width)))
+        };
+        let normals = optional("NORMAL", &[3])?;
+        let uvs = optional("TEXCOORD_0", &[2])?;
+        let colors = optional("COLOR_0", &[3, 4])?;
+
+        let vertices = (0..count)
+            .map(|i| Vertex {
+                position: [positions[i * 3], positions[i * 3 + 1], positions[i * 3 + 2]],
+                // alpha in COLOR_0 is dropped
+                color: colors.as_ref().map_or([1.0; 3], |(values, width)| [values[i * width], values[i * width + 1], values[i * width + 2]]),
+                normal: normals.as_ref().map_or([0.0, 1.0, 0.0], |(values, _)| [values[i * 3], values[i * 3 + 1], values[i * 3 + 2]]),
+                uv: uvs.as_ref().map_or([0.0; 2], |(values, _)| [values[i * 2], values[i * 2 + 1]]),
+            })
+            .collect();
+
+        let order: Vec<u32> = match primitive.get("indices").and_then(Json::as_usize) {
+            Some(accessor) => self.read_accessor_as(accessor, &[1])?.0.into_iter().map(|index| index as u32).collect(),
+            None => (0..count as u32).collect(),
+        };
+        if order.iter().any(|&index| index as usize >= count) {
+            return Err(format!("mesh {}: an index is out of range", mesh));
+        }
+        let indices = match mode {
+            // strips flip every other triangle to keep the winding
+            5 => (0..order.len().saturating_sub(2))
+                .flat_map(|i| if i % 2 == 0 { [order[i], order[i + 1], order[i + 2]] } else { [order[i + 1], order[i], order[i + 2]] })
+                .collect(),
+            6 => (1..order.len().saturating_sub(1)).flat_map(|i| [order[0], order[i], order[i + 1]]).collect(),
+            _ => order,
+        };
+
+        let mut mesh = MeshData {
+            vertices,
+            indices,
+            has_normals: normals.is_some(),
+            ..MeshData::new()
+        };
+        // the glTF spec asks for flat normals when a primitive has none
+        if !mesh.has_normals {
+            mesh.generate_normals(NormalMode::Flat);
+        }
+        Ok(Some(mesh))
+    }
+
+    // read_accessor that also checks the element width
+    fn read_accessor_as(&self, index: usize, widths: &[usize]) -> Result<(Vec<f32>, usize), String> {
+        let (values, width) = self.read_accessor(index)?;
+        if !widths.contains(&width) {
+            return Err(format!("accessor {} has {} components, expected {:?}", index, width, widths));
+        }
+        Ok((values, width))
+    }
+}
+
+/// Every triangle mesh in the file's scene as one mesh, with node transforms applied,
+/// and the material of the first primitive that has one
+pub fn load_mesh(path: &Path, asset_path: &str) -> Result<MeshData, String> {
+    let in_file = |e: String| format!("{}: {}", path.display(), e);
+    let gltf = Gltf::open(path).map_err(in_file)?;
+    let mut notes = Vec::new();
+    let mut combined = MeshData::new();
+    let mut materials: Vec<usize> = Vec::new();
+    let mut has_colors = false;
+
+    let mut stack: Vec<(usize, Matrix4<f32>)> = gltf.scene_roots().into_iter().map(|node| (node, Matrix4::identity())).collect();
+    let mut visited = 0;
+    while let Some((node_index, parent)) = stack.pop() {
+        visited += 1;
+        if visited > gltf.list("nodes").len() {
+            return Err(in_file("the node hierarchy has a cycle or a node with two parents".to_string()));
+        }
+        let node = gltf.item("nodes", node_index).map_err(in_file)?;
+        let world = parent * local_matrix(node);
+        if let Some(mesh_index) = node.get("mesh").and_then(Json::as_usize) {
+            let mesh = gltf.item("meshes", mesh_index).map_err(in_file)?;
+            for primitive in mesh.get("primitives").map_or(&[][..], Json::items) {
+                if let Some(part) = gltf.primitive_mesh(mesh_index, primitive, &mut notes).map_err(in_file)? {
+                    combined.append(transform_mesh(part, world));
+                    has_colors |= has_vertex_colors(primitive);
+                    if let Some(material) = primitive.get("material").and_then(Json::as_usize)
+                        && !materials.contains(&material)
+                    {
+                        materials.push(material);
+                    }
+                }
+            }
+        }
+        for child in node.get("children").map_or(&[][..], Json::items).iter().filter_map(Json::as_usize) {
+            stack.push((child, world));
+        }
+    }
+    if combined.vertices.is_empty() {
+        return Err(in_file("the scene has no triangle meshes".to_string()));
+    }
+
+    if materials.len() > 1 {
+        notes.push(format!("uses {} materials; one geometry has one material, so only the first is used", materials.len()));
+    }
+    combined.material = materials.first().map(|&material| gltf.material(material, asset_path, &mut notes));
+    if has_colors {
+        // glTF multiplies the base color by COLOR_0
+        combined.material.get_or_insert_with(Material::default).vertex_color = VertexColorMode::Multiply;
+    }
+    for list in ["animations", "skins", "cameras"] {
+        if !gltf.list(list).is_empty() {
+            notes.push(format!("ignored {} {}", gltf.list(list).len(), list));
+        }
+    }
+    for note in notes {
+        eprintln!("{}: {}", path.display(), note);
+    }
+    combined.extra_files = gltf.external_files;
+    Ok(combined)
+}
+
+fn has_vertex_colors(primitive: &Json) -> bool {
+    primitive.get("attributes").and_then(|attributes| attributes.get("COLOR_0")).is_some()
+}
+
+/// A node's own transform: its matrix, or translation * rotation * scale
+pub fn local_matrix(node: &Json) -> Matrix4<f32> {
+    if let Some(matrix) = node.get("matrix").and_then(Json::as_f32_array::<16>) {
+        // column-major, like cgmath
+        return Matrix4::from_cols(
+            Vector4::new(matrix[0], matrix[1], matrix[2], matrix[3]),
+            Vector4::new(matrix[4], matrix[5], matrix[6], matrix[7]),
+            Vector4::new(matrix[8], matrix[9], matrix[10], matrix[11]),
+            Vector4::new(matrix[12], matrix[13], matrix[14], matrix[15]),
+        );
+    }
+    let [tx, ty, tz] = node.get("translation").and_then(Json::as_f32_array).unwrap_or([0.0; 3]);
+    let [x, y, z, w] = node.get("rotation").and_then(Json::as_f32_array).unwrap_or([0.0, 0.0, 0.0, 1.0]);
+    let [sx, sy, sz] = node.get("scale").and_then(Json::as_f32_array).unwrap_or([1.0; 3]);
+    Matrix4::from_translation(Vector3::new(tx, ty, tz))
+        * Matrix4::from(Quaternion::new(w, x, y, z))
+        * Matrix4::from_nonuniform_scale(sx, sy, sz)
+}
+
+// bakes `matrix` into the vertices; mirroring matrices also flip the winding so faces stay front-facing
+fn transform_mesh(mut mesh: MeshData, matrix: Matrix4<f32>) -> MeshData {
+    let linear = Matrix3::from_cols(matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate());
+    let normal_matrix = linear.invert().map_or(linear, |inverse| inverse.transpose());
+    for vertex in &mut mesh.vertices {
+        let position = matrix * Vector3::from(vertex.position).extend(1.0);
+        vertex.position = position.truncate().into();
+        let normal = normal_matrix * Vector3::from(vertex.normal);
+        if normal.magnitude2() > 0.0 {
+            vertex.normal = normal.normalize().into();
+        }
+    }
+    if linear.determinant() < 0.0 {
+        for triangle in mesh.indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+    mesh
+}
+
+/// `to` relative to the folder `from`, with forward slashes, e.g. `../textures/a.png`.
+/// None when either doesn't exist or they share no root (other drives on Windows)
+pub fn relative_path(from: &Path, to: &Path) -> Option<String> {
+    let from = fs::canonicalize(from).ok()?;
+    let to = fs::canonicalize(to).ok()?;
+    let common = from.components().zip(to.components()).take_while(|(a, b)| a == b).count();
+    if common == 0 {
+        return None;
+    }
+    let parts: Vec<String> = std::iter::repeat_n("..".to_string(), from.components().count() - common)
+        .chain(to.components().skip(common).map(|part| part.as_os_str().to_string_lossy().into_owned()))
+        .collect();
+    Some(parts.join("/"))
+}
+
+// the JSON chunk as text and the binary chunk, if any
+fn split_glb(bytes: &[u8]) -> Result<(String, Option<Vec<u8>>), String> {
+    let word = |at: usize| -> Result<u32, String> {
+        bytes.get(at..at + 4).map(|word| u32::from_le_bytes(word.try_into().unwrap())).ok_or_else(|| "the GLB file is truncated".to_string())
+    };
+    if word(4)? != 2 {
+        return Err(format!("GLB version {} is not supported, only 2", word(4)?));
+    }
+    let mut json = None;
+    let mut bin = None;
+    let mut at = 12;
+    while at < bytes.len().min(word(8)? as usize) {
+        let (length, kind) = (word(at)? as usize, word(at + 4)?);
+        let data = bytes.get(at + 8..at + 8 + length).ok_or("a GLB chunk runs past the end of the file")?;
+        match kind {
+            GLB_JSON_CHUNK if json.is_none() => json = Some(String::from_utf8(data.to_vec()).map_err(|_| "the GLB JSON chunk is not UTF-8")?),
+            GLB_BIN_CHUNK if bin.is_none() => bin = Some(data.to_vec()),
+            // unknown chunk types are skipped, as the spec asks
+            _ => {}
+        }
+        at += 8 + length;
+    }
+    Ok((json.ok_or("the GLB file has no JSON chunk")?, bin))
+}
+
+// byte range of a buffer view within its buffer, None when it overflows
+fn view_range(view: &Json) -> Option<std::ops::Range<usize>> {
+    let start = byte_count(view, "byteOffset", 0)?;
+    let length = byte_count(view, "byteLength", 0)?;
+    Some(start..start.checked_add(length)?)
+}
+
+// `default` when the property is left out, None when it is there but not a usable size
+fn byte_count(json: &Json, key: &str, default: usize) -> Option<usize> {
+    json.get(key).map_or(Some(default), Json::as_usize)
+}
+
+fn component_size(component_type: usize) -> Result<usize, String> {
+    match component_type {
+        5120 | 5121 => Ok(1),
+        5122 | 5123 => Ok(2),
+        5125 | 5126 => Ok(4),
+        _ => Err(format!("unknown componentType {}", component_type)),
+    }
+}
+
+fn read_component(bytes: &[u8], component_type: usize, normalized: bool) -> f32 {
+    // normalized signed values map to -1..1, with the smallest value clamped to -1
+    let scaled = |value: f32, max: f32| if normalized { (value / max).max(-1.0) } else { value };
+    match component_type {
+        5120 => scaled(bytes[0] as i8 as f32, 127.0),
+        5121 => scaled(bytes[0] as f32, 255.0),
+        5122 => scaled(i16::from_le_bytes([bytes[0], bytes[1]]) as f32, 32767.0),
+        5123 => scaled(u16::from_le_bytes([bytes[0], bytes[1]]) as f32, 65535.0),
+        5125 => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+        _ => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    }
+}
+
+// `data:<mime>;base64,<data>`
+fn decode_data_uri(uri: &str) -> Result<Vec<u8>, String> {
+    let (header, data) = uri.split_once(',').ok_or("data URI without a comma")?;
+    if !header.ends_with(";base64") {
+        return Err("only base64 data URIs are supported".to_string());
+    }
+    decode_base64(data)
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut bit_count) = (0u32, 0);
+    for character in text.bytes().filter(|character| !character.is_ascii_whitespace() && *character != b'=') {
+        let value = match character {
+            b'A'..=b'Z' => character - b'A',
+            b'a'..=b'z' => character - b'a' + 26,
+            b'0'..=b'9' => character - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(format!("invalid base64 character '{}'", character as char)),
+        };
+        bits = (bits << 6) | value as u32;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((bits >> bit_count) as u8);
+        }
+    }
+    // one character past a full group holds only 6 bits, less than a byte
+    if bit_count == 6 {
+        return Err("base64 data ends with an incomplete byte".to_string());
+    }
+    Ok(bytes)
+}
+
+// URIs in glTF are percent-encoded, e.g. spaces as %20
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok()).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gltf_with(accessors: &str, buffer_views: &str) -> Gltf {
+        let json = format!(r#"{{"accessors": [{}], "bufferViews": [{}]}}"#, accessors, buffer_views);
+        Gltf { json: Json::parse(&json).unwrap(), buffers: vec![vec![0; 48]], dir: PathBuf::new(), external_files: Vec::new() }
+    }
+
+    #[test]
+    fn accessors_outside_their_data_are_rejected() {
+        let view = r#"{"buffer": 0, "byteLength": 48}"#;
+        let ok = gltf_with(r#"{"bufferView": 0, "count": 4, "type": "VEC3", "componentType": 5126}"#, view);
+        assert_eq!(ok.read_accessor(0).unwrap().0.len(), 12);
+
+        for accessor in [
+            r#"{"bufferView": 0, "count": 5, "type": "VEC3", "componentType": 5126}"#,
+            r#"{"bufferView": 0, "count": 1e15, "type": "MAT4", "componentType": 5126}"#,
+            r#"{"bufferView": 0, "count": 1e30, "type": "SCALAR", "componentType": 5126}"#,
+            r#"{"bufferView": 0, "byteOffset": 18446744073709551615, "count": 1, "type": "SCALAR", "componentType": 5126}"#,
+            r#"{"count": 1e15, "type": "VEC3", "componentType": 5126}"#,
+        ] {
+            assert!(gltf_with(accessor, view).read_accessor(0).is_err(), "{}", accessor);
+        }
+
+        for stride in ["0", "2", "6", "8", "256"] {
+            let view = format!(r#"{{"buffer": 0, "byteLength": 48, "byteStride": {}}}"#, stride);
+            let accessor = r#"{"bufferView": 0, "count": 1e15, "type": "VEC3", "componentType": 5126}"#;
+            assert!(gltf_with(accessor, &view).read_accessor(0).is_err(), "byteStride {}", stride);
+        }
+        let strided = gltf_with(r#"{"bufferView": 0, "count": 3, "type": "VEC3", "componentType": 5126}"#,
+            r#"{"buffer": 0, "byteLength": 48, "byteStride": 16}"#);
+        assert_eq!(strided.read_accessor(0).unwrap().0.len(), 9);
+
+        let huge_view = r#"{"buffer": 0, "byteOffset": 18446744073709551000, "byteLength": 18446744073709551000}"#;
+        let accessor = r#"{"bufferView": 0, "count": 1, "type": "SCALAR", "componentType": 5126}"#;
+        assert!(gltf_with(accessor, huge_view).read_accessor(0).is_err());
+    }
+
+    #[test]
+    fn data_uris_decode_base64_with_and_without_padding() {
+        assert_eq!(decode_data_uri("data:application/octet-stream;base64,TWFu").unwrap(), b"Man");
+        assert_eq!(decode_data_uri("data:application/octet-stream;base64,TWE=").unwrap(), b"Ma");
+        assert_eq!(decode_data_uri("data:application/octet-stream;base64,TQ==").unwrap(), b"M");
+        assert_eq!(decode_data_uri("data:application/octet-stream;base64,TQ").unwrap(), b"M");
+        assert_eq!(decode_data_uri("data:application/octet-stream;base64,T W\nFu").unwrap(), b"Man");
+        assert_eq!(decode_data_uri("data:application/octet-stream;base64,").unwrap(), b"");
+        assert_eq!(decode_base64("+/+/").unwrap(), [0xfb, 0xff, 0xbf]);
+
+        assert!(decode_data_uri("data:application/octet-stream;base64,TWFuT").is_err());
+        assert!(decode_data_uri("data:application/octet-stream;base64,TW-u").is_err());
+        assert!(decode_data_uri("data:text/plain,Man").is_err());
+        assert!(decode_data_uri("data:application/octet-stream;base64").is_err());
+    }
+
+    #[test]
+    fn percent_escapes_are_decoded_and_stray_percents_kept() {
+        assert_eq!(percent_decode("my%20model.bin"), "my model.bin");
+        assert_eq!(percent_decode("caf%C3%A9%2fa.png"), "café/a.png");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("50%zz%4"), "50%zz%4");
+        assert_eq!(percent_decode("plain/path.bin"), "plain/path.bin");
+    }
+}
@@ -0,0 +1,823 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cgmath::{InnerSpace, Matrix3, Quaternion, Vector3};
+
+use crate::camera::Projection;
+use crate::gltf::{self, GLB_BIN_CHUNK, GLB_JSON_CHUNK, GLB_MAGIC};
+use crate::json::Json;
+use crate::material::{Material, VertexColorMode};
+use crate::object::ObjectGeometry;
+use crate::scene::{Scene, SceneCamera, EMISSIVE_LIGHT_INTENSITY};
+
+const GENERATOR: &str = "ArbitraRenderer";
+// glTF enums: bufferView targets and accessor component types
+const ARRAY_BUFFER: usize = 34962;
+const ELEMENT_ARRAY_BUFFER: usize = 34963;
+const UNSIGNED_SHORT: usize = 5123;
+const UNSIGNED_INT: usize = 5125;
+const FLOAT: usize = 5126;
+// round trip comparisons allow this much difference in positions, directions and factors
+const ROUND_TRIP_TOLERANCE: f32 = 1e-4;
+
+// accessors of a geometry's vertex data, shared by every mesh that uses the geometry
+#[derive(Clone, Copy)]
+struct GeometryAccessors {
+    position: usize,
+    normal: usize,
+    uv: usize,
+    // None when every vertex is white
+    color: Option<usize>,
+    indices: usize,
+}
+
+// the glTF document being built
+struct Export<'a> {
+    scene: &'a Scene,
+    assets_root: &'a str,
+    // folder of the .gltf, which texture URIs are relative to; None for .glb, which embeds its images
+    uri_dir: Option<PathBuf>,
+    buffer: Vec<u8>,
+    buffer_views: Vec<Json>,
+    accessors: Vec<Json>,
+    meshes: Vec<Json>,
+    materials: Vec<Json>,
+    textures: Vec<Json>,
+    images: Vec<Json>,
+    geometries: HashMap<String, Option<GeometryAccessors>>,
+    // a mesh per geometry and material pair, since glTF keeps the material with the mesh
+    mesh_index: HashMap<(String, usize), usize>,
+    exported_materials: Vec<Material>,
+    image_index: HashMap<String, usize>,
+    notes: Vec<String>,
+}
+
+impl Scene {
+    /// Write the scene as glTF 2.0. A `.glb` path gives one binary file with the images inside; any other
+    /// gives a `.gltf` with its data in a `.bin` of the same name, referring to the textures where they are.
+    /// Instances become nodes (children keep their parent), emissive instances also get a point light,
+    /// and the directional light and cameras get nodes of their own. What glTF can't hold is listed on the console
+    pub fn export_gltf<P: AsRef<Path>>(&self, path: P, assets_root: &str) -> Result<(), String> {
+        let path = path.as_ref();
+        let is_glb = path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("glb"));
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+
+        let mut export = Export {
+            scene: self,
+            assets_root,
+            uri_dir: (!is_glb).then(|| dir.to_path_buf()),
+            buffer: Vec::new(),
+            buffer_views: Vec::new(),
+            accessors: Vec::new(),
+            meshes: Vec::new(),
+            materials: Vec::new(),
+            textures: Vec::new(),
+            images: Vec::new(),
+            geometries: HashMap::new(),
+            mesh_index: HashMap::new(),
+            exported_materials: Vec::new(),
+            image_index: HashMap::new(),
+            notes: Vec::new(),
+        };
+        let mut document = export.document()?;
+
+        if is_glb {
+            if !export.buffer.is_empty() {
+                document.set("buffers", Json::array([Json::object([("byteLength", export.buffer.len().into())])]));
+            }
+            fs::write(path, glb(&document.to_string(), &export.buffer)).map_err(|e| format!("{}: {}", path.display(), e))?;
+        } else {
+            if !export.buffer.is_empty() {
+                let bin_path = path.with_extension("bin");
+                let bin_name = bin_path.file_name().map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+                document.set("buffers", Json::array([Json::object([
+                    ("uri", percent_encode(&bin_name).into()),
+                    ("byteLength", export.buffer.len().into()),
+                ])]));
+                fs::write(&bin_path, &export.buffer).map_err(|e| format!("{}: {}", bin_path.display(), e))?;
+            }
+            fs::write(path, document.to_string()).map_err(|e| format!("{}: {}", path.display(), e))?;
+        }
+
+        let count = |key| document.get(key).map_or(0, |list| list.items().len());
+        println!("Exported scene '{}' to '{}': {} nodes, {} meshes, {} materials",
+            self.name, path.display(), count("nodes"), count("meshes"), count("materials"));
+        if !export.notes.is_empty() {
+            eprintln!("{}: some of the scene could not be exported:", path.display());
+            for note in &export.notes {
+                eprintln!("  - {}", note);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Export<'_> {
+    fn document(&mut self) -> Result<Json, String> {
+        let scene = self.scene;
+        let mut nodes = Vec::new();
+        let mut lights = Vec::new();
+
+        // instance i is node i, so parent indices carry over
+        for instance in &scene.instances {
+            let mut node = Json::object([
+                ("name", instance.name.as_str().into()),
+                ("translation", instance.transform.position.into()),
+                ("rotation", quaternion_array(instance.transform.rotation.normalize()).into()),
+                ("scale", instance.transform.scale.into()),
+            ]);
+            let children: Vec<usize> = (0..scene.instances.len()).filter(|&child| scene.instances[child].parent == Some(nodes.len())).collect();
+            if !children.is_empty() {
+                node.set("children", Json::array(children));
+            }
+            if let Some(mesh) = self.mesh(&instance.geometry_name, &instance.material)? {
+                node.set("mesh", mesh);
+            }
+            if instance.emissive > 0.0 {
+                node.set("extensions", light_extension(lights.len()));
+                lights.push(Json::object([
+                    ("name", format!("{} light", instance.name).into()),
+                    ("type", "point".into()),
+                    ("color", instance.emissive_color.map(|channel| channel.clamp(0.0, 1.0)).into()),
+                    ("intensity", (instance.emissive * EMISSIVE_LIGHT_INTENSITY).into()),
+                ]));
+            }
+            nodes.push(node);
+        }
+        let mut roots: Vec<usize> = (0..scene.instances.len()).filter(|&index| scene.instances[index].parent.is_none()).collect();
+        if scene.instances.iter().any(|instance| instance.emissive > 0.0) {
+            self.notes.push("emissive objects only keep their point light, not their glow".to_string());
+        }
+
+        // the directional light shines down the node's -Z axis
+        let direction = Vector3::from(scene.light.direction);
+        if direction.magnitude2() > 0.0 {
+            roots.push(nodes.len());
+            nodes.push(Json::object([
+                ("name", "Sun".into()),
+                ("rotation", quaternion_array(look_rotation(direction)).into()),
+                ("extensions", light_extension(lights.len())),
+            ]));
+            lights.push(Json::object([
+                ("name", "Sun".into()),
+                ("type", "directional".into()),
+                ("color", scene.light.color.map(|channel| channel.clamp(0.0, 1.0)).into()),
+                ("intensity", scene.light.intensity.max(0.0).into()),
+            ]));
+        }
+        if scene.light.ambient_strength > 0.0 {
+            self.notes.push(format!("glTF has no ambient light, ambient_strength {} was dropped", scene.light.ambient_strength));
+        }
+
+        // the default camera goes first, which is the one viewers (and the import) start with
+        let mut cameras: Vec<&SceneCamera> = scene.cameras.iter().collect();
+        if let Some(default) = scene.default_camera() {
+            cameras.retain(|camera| !std::ptr::eq(*camera, default));
+            cameras.insert(0, default);
+        }
+        let mut camera_list = Vec::new();
+        for camera in cameras {
+            roots.push(nodes.len());
+            nodes.push(Json::object([
+                ("name", camera.name.as_str().into()),
+                ("translation", camera.position.into()),
+                ("rotation", quaternion_array(look_rotation(camera_forward(camera))).into()),
+                ("camera", camera_list.len().into()),
+            ]));
+            camera_list.push(camera_json(camera));
+        }
+        if !scene.camera_paths.is_empty() {
+            self.notes.push(format!("{} camera paths were dropped", scene.camera_paths.len()));
+        }
+
+        let mut root_scene = Json::object([("name", scene.name.as_str().into())]);
+        if !roots.is_empty() {
+            root_scene.set("nodes", Json::array(roots));
+        }
+        let mut document = Json::object([
+            ("asset", Json::object([("version", "2.0".into()), ("generator", GENERATOR.into())])),
+            ("scene", 0.into()),
+            ("scenes", Json::array([root_scene])),
+        ]);
+        if !lights.is_empty() {
+            document.set("extensionsUsed", Json::array(["KHR_lights_punctual"]));
+            document.set("extensions", Json::object([("KHR_lights_punctual", Json::object([("lights", Json::Array(lights))]))]));
+        }
+        // glTF doesn't allow empty arrays
+        for (key, list) in [
+            ("nodes", nodes),
+            ("cameras", camera_list),
+            ("meshes", std::mem::take(&mut self.meshes)),
+            ("materials", std::mem::take(&mut self.materials)),
+            ("textures", std::mem::take(&mut self.textures)),
+            ("images", std::mem::take(&mut self.images)),
+            ("accessors", std::mem::take(&mut self.accessors)),
+            ("bufferViews", std::mem::take(&mut self.buffer_views)),
+        ] {
+            if !list.is_empty() {
+                document.set(key, Json::Array(list));
+            }
+        }
+        Ok(document)
+    }
+
+    // mesh for the geometry drawn with the material; None for geometries without triangles
+    fn mesh(&mut self, geometry_name: &str, material: &Material) -> Result<Option<usize>, String> {
+        let Some(accessors) = self.geometry(geometry_name)? else {
+            return Ok(None);
+        };
+        let material_index = self.material(material)?;
+        if let Some(&mesh) = self.mesh_index.get(&(geometry_name.to_string(), material_index)) {
+            return Ok(Some(mesh));
+        }
+
+        let mut attributes = Json::object([
+            ("POSITION", accessors.position.into()),
+            ("NORMAL", accessors.normal.into()),
+            ("TEXCOORD_0", accessors.uv.into()),
+        ]);
+        // glTF always multiplies by COLOR_0, so it's left out for materials that ignore vertex colors
+        if let Some(color) = accessors.color
+            && material.vertex_color != VertexColorMode::Ignore
+        {
+            attributes.set("COLOR_0", color);
+        }
+        self.meshes.push(Json::object([
+            ("name", geometry_name.into()),
+            ("primitives", Json::array([Json::object([
+                ("attributes", attributes),
+                ("indices", accessors.indices.into()),
+                ("material", material_index.into()),
+            ])])),
+        ]));
+        let mesh = self.meshes.len() - 1;
+        self.mesh_index.insert((geometry_name.to_string(), material_index), mesh);
+        Ok(Some(mesh))
+    }
+
+    // writes the geometry's vertices and indices to the buffer the first time it's used
+    fn geometry(&mut self, name: &str) -> Result<Option<GeometryAccessors>, String> {
+        if let Some(accessors) = self.geometries.get(name) {
+            return Ok(*accessors);
+        }
+        let geometry = self.scene.get_geometry(name).ok_or_else(|| format!("geometry '{}' is not in the scene", name))?;
+        let accessors = if geometry.vertices.is_empty() || geometry.indices.is_empty() {
+            self.notes.push(format!("geometry '{}' has no triangles and was left out", name));
+            None
+        } else {
+            Some(self.write_geometry(geometry))
+        };
+        self.geometries.insert(name.to_string(), accessors);
+        Ok(accessors)
+    }
+
+    fn write_geometry(&mut self, geometry: &ObjectGeometry) -> GeometryAccessors {
+        let vertices = &geometry.vertices;
+        let (min, max) = geometry.bounds();
+        let position = self.float_accessor(vertices.iter().flat_map(|vertex| vertex.position), 3, Some((min, max)));
+        // glTF requires unit normals
+        let normal = self.float_accessor(vertices.iter().flat_map(|vertex| {
+            let normal = Vector3::from(vertex.normal);
+            let normal = if normal.magnitude2() > 0.0 { normal.normalize() } else { Vector3::unit_y() };
+            [normal.x, normal.y, normal.z]
+        }), 3, None);
+        let uv = self.float_accessor(vertices.iter().flat_map(|vertex| vertex.uv), 2, None);
+        let color = vertices.iter().any(|vertex| vertex.color != [1.0; 3])
+            .then(|| self.float_accessor(vertices.iter().flat_map(|vertex| vertex.color), 3, None));
+
+        // glTF viewers cull faces wound clockwise; the engine draws both sides, so some meshes
+        // (models/cube.obj among them) are wound the other way and get turned around to match their normals
+        let mut indices = geometry.indices.clone();
+        for triangle in indices.chunks_exact_mut(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| &vertices[triangle[corner] as usize]);
+            let [pa, pb, pc] = [a, b, c].map(|vertex| Vector3::from(vertex.position));
+            let normals = Vector3::from(a.normal) + Vector3::from(b.normal) + Vector3::from(c.normal);
+            if (pb - pa).cross(pc - pa).dot(normals) < 0.0 {
+                triangle.swap(1, 2);
+            }
+        }
+        // 65535 is the primitive restart value of 16-bit indices, which glTF doesn't allow as an index
+        let (bytes, component_type): (Vec<u8>, _) = if indices.contains(&u16::MAX) {
+            (indices.iter().flat_map(|&index| (index as u32).to_le_bytes()).collect(), UNSIGNED_INT)
+        } else {
+            (indices.iter().flat_map(|index| index.to_le_bytes()).collect(), UNSIGNED_SHORT)
+        };
+        let view = self.buffer_view(bytes, Some(ELEMENT_ARRAY_BUFFER));
+        self.accessors.push(Json::object([
+            ("bufferView", view.into()),
+            ("componentType", component_type.into()),
+            ("count", indices.len().into()),
+            ("type", "SCALAR".into()),
+        ]));
+        let indices = self.accessors.len() - 1;
+
+        GeometryAccessors { position, normal, uv, color, indices }
+    }
+
+    fn float_accessor(&mut self, values: impl Iterator<Item = f32>, components: usize, bounds: Option<([f32; 3], [f32; 3])>) -> usize {
+        let bytes: Vec<u8> = values.flat_map(f32::to_le_bytes).collect();
+        let count = bytes.len() / 4 / components;
+        let view = self.buffer_view(bytes, Some(ARRAY_BUFFER));
+        let mut accessor = Json::object([
+            ("bufferView", view.into()),
+            ("componentType", FLOAT.into()),
+            ("count", count.into()),
+            ("type", ["SCALAR", "VEC2", "VEC3", "VEC4"][components - 1].into()),
+        ]);
+        // required for POSITION
+        if let Some((min, max)) = bounds {
+            accessor.set("min", min);
+            accessor.set("max", max);
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    // appends the bytes to the buffer, 4-byte aligned as accessors require
+    fn buffer_view(&mut self, bytes: Vec<u8>, target: Option<usize>) -> usize {
+        let offset = self.buffer.len();
+        let mut view = Json::object([
+            ("buffer", 0.into()),
+            ("byteOffset", offset.into()),
+            ("byteLength", bytes.len().into()),
+        ]);
+        if let Some(target) = target {
+            view.set("target", target);
+        }
+        self.buffer.extend(bytes);
+        self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+        self.buffer_views.push(view);
+        self.buffer_views.len() - 1
+    }
+
+    // materials that export the same are written once
+    fn material(&mut self, material: &Material) -> Result<usize, String> {
+        let same = |other: &Material| other.name == material.name
+            && other.albedo_texture == material.albedo_texture
+            && other.base_color == material.base_color
+            && other.roughness == material.roughness
+            && other.metallic == material.metallic
+            && other.vertex_color == material.vertex_color;
+        if let Some(index) = self.exported_materials.iter().position(same) {
+            return Ok(index);
+        }
+
+        let mut pbr = Json::object([
+            ("baseColorFactor", material.base_color.map(|channel| channel.clamp(0.0, 1.0)).into()),
+            ("metallicFactor", material.metallic.clamp(0.0, 1.0).into()),
+            ("roughnessFactor", material.roughness.clamp(0.0, 1.0).into()),
+        ]);
+        // the default white texture changes nothing, so it's left out
+        if material.albedo_texture != Material::default().albedo_texture {
+            let texture = self.texture(&material.albedo_texture)?;
+            pbr.set("baseColorTexture", Json::object([("index", texture.into())]));
+        }
+        if material.vertex_color == VertexColorMode::Replace {
+            self.notes.push(format!("material '{}': vertex_color replace became multiply", material.name));
+        }
+        self.materials.push(Json::object([
+            ("name", material.name.as_str().into()),
+            ("pbrMetallicRoughness", pbr),
+        ]));
+        self.exported_materials.push(material.clone());
+        Ok(self.materials.len() - 1)
+    }
+
+    fn texture(&mut self, albedo_texture: &str) -> Result<usize, String> {
+        if let Some(&texture) = self.image_index.get(albedo_texture) {
+            return Ok(texture);
+        }
+        let file = Path::new(self.assets_root).join(albedo_texture.split_once('#').map_or(albedo_texture, |(file, _)| file));
+        let is_web_image = file.extension().and_then(|extension| extension.to_str())
+            .is_some_and(|extension| ["png", "jpg", "jpeg"].contains(&extension.to_ascii_lowercase().as_str()));
+
+        let uri = match &self.uri_dir {
+            Some(dir) if is_web_image && !albedo_texture.contains('#') => gltf::relative_path(dir, &file),
+            _ => None,
+        };
+        let image = match uri {
+            Some(uri) => Json::object([("uri", percent_encode(&uri).into())]),
+            // embedded: .glb files, images inside other glTF files, and formats glTF doesn't allow
+            None => {
+                let (bytes, mime_type) = self.image_bytes(albedo_texture)?;
+                let view = self.buffer_view(bytes, None);
+                Json::object([("bufferView", view.into()), ("mimeType", mime_type.into())])
+            }
+        };
+        self.images.push(image);
+        self.textures.push(Json::object([("source", (self.images.len() - 1).into())]));
+        let texture = self.textures.len() - 1;
+        self.image_index.insert(albedo_texture.to_string(), texture);
+        Ok(texture)
+    }
+
+    // PNG or JPEG bytes of a texture; other formats are converted to PNG
+    fn image_bytes(&self, albedo_texture: &str) -> Result<(Vec<u8>, &'static str), String> {
+        let bytes = texture_bytes(self.assets_root, albedo_texture)?;
+        if bytes.starts_with(b"\x89PNG") {
+            return Ok((bytes, "image/png"));
+        }
+        if bytes.starts_with(&[0xff, 0xd8, 0xff]) {
+            return Ok((bytes, "image/jpeg"));
+        }
+        let image = image::load_from_memory(&bytes).map_err(|e| format!("texture {}: {}", albedo_texture, e))?;
+        let mut png = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut png, image::ImageFormat::Png).map_err(|e| format!("texture {}: {}", albedo_texture, e))?;
+        Ok((png.into_inner(), "image/png"))
+    }
+}
+
+/// Differences between a scene and the same scene exported and loaded back with `load_from_gltf`;
+/// empty when the export kept everything glTF can hold. Objects are matched by name (in order for
+/// repeated names), cameras by name
+pub fn compare_round_trip(source: &Scene, imported: &Scene, assets_root: &str) -> Vec<String> {
+    let mut differences = Vec::new();
+    let close = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(a, b)| (a - b).abs() <= ROUND_TRIP_TOLERANCE * a.abs().max(1.0));
+    let source_worlds = source.world_matrices();
+    let imported_worlds = imported.world_matrices();
+    let matrix_values = |matrix: cgmath::Matrix4<f32>| -> [f32; 16] { *matrix.as_ref() };
+
+    let mut unmatched: Vec<usize> = (0..imported.instances.len()).collect();
+    for (index, instance) in source.instances.iter().enumerate() {
+        let Some(position) = unmatched.iter().position(|&other| imported.instances[other].name == instance.name) else {
+            differences.push(format!("object '{}' is missing", instance.name));
+            continue;
+        };
+        let other_index = unmatched.remove(position);
+        let other = &imported.instances[other_index];
+        if !close(&matrix_values(source_worlds[index]), &matrix_values(imported_worlds[other_index])) {
+            differences.push(format!("object '{}' moved", instance.name));
+        }
+        match (source.get_geometry(&instance.geometry_name), imported.get_geometry(&other.geometry_name)) {
+            (Some(a), Some(b)) if a.vertices.is_empty() || a.indices.is_empty() => {
+                differences.push(format!("object '{}' has empty geometry '{}' but got '{}'", instance.name, a.name, b.name));
+            }
+            (Some(a), Some(b)) => {
+                if a.vertices.len() != b.vertices.len() || a.indices.len() != b.indices.len() {
+                    differences.push(format!("object '{}': geometry '{}' has {} vertices and {} indices, the import {} and {}",
+                        instance.name, a.name, a.vertices.len(), a.indices.len(), b.vertices.len(), b.indices.len()));
+                } else if a.vertices.iter().zip(&b.vertices).any(|(a, b)| !close(&a.position, &b.position) || !close(&a.uv, &b.uv)) {
+                    differences.push(format!("object '{}': the vertices of geometry '{}' changed", instance.name, a.name));
+                }
+            }
+            (Some(a), None) if !a.vertices.is_empty() && !a.indices.is_empty() => {
+                differences.push(format!("object '{}' lost geometry '{}'", instance.name, a.name));
+            }
+            _ => {}
+        }
+
+        let (a, b) = (&instance.material, &other.material);
+        // textures come back as a relative path or as an image inside the file, so the pixels are compared
+        let pixels = |texture: &str| texture_bytes(assets_root, texture).ok()
+            .and_then(|bytes| image::load_from_memory(&bytes).ok())
+            .map(|image| image.to_rgba8());
+        let same_texture = a.albedo_texture == b.albedo_texture
+            || pixels(&a.albedo_texture).is_some_and(|image| pixels(&b.albedo_texture) == Some(image));
+        let expected_colors = match a.vertex_color {
+            VertexColorMode::Replace => VertexColorMode::Multiply,
+            mode => mode,
+        };
+        // vertex color modes only matter for meshes that have colors
+        let has_colors = source.get_geometry(&instance.geometry_name)
+            .is_some_and(|geometry| geometry.vertices.iter().any(|vertex| vertex.color != [1.0; 3]));
+        if a.name != b.name || !same_texture || !close(&a.base_color.map(|c| c.clamp(0.0, 1.0)), &b.base_color)
+            || !close(&[a.metallic.clamp(0.0, 1.0), a.roughness.clamp(0.0, 1.0)], &[b.metallic, b.roughness])
+            || (has_colors && expected_colors != b.vertex_color)
+        {
+            differences.push(format!("object '{}': material '{}' changed", instance.name, a.name));
+        }
+
+        // point lights come back as a marker at the object's origin
+        if instance.emissive > 0.0 {
+            let marker = unmatched.iter().position(|&marker| {
+                let light = &imported.instances[marker];
+                light.parent == Some(other_index) && light.emissive > 0.0
+            });
+            match marker.map(|position| unmatched.remove(position)) {
+                Some(marker) if close(&[instance.emissive], &[imported.instances[marker].emissive])
+                    && close(&instance.emissive_color.map(|c| c.clamp(0.0, 1.0)), &imported.instances[marker].emissive_color) => {}
+                _ => differences.push(format!("object '{}': its point light changed", instance.name)),
+            }
+        }
+    }
+    for other in unmatched {
+        differences.push(format!("the import has an extra object '{}'", imported.instances[other].name));
+    }
+
+    let direction = |light: &crate::scene::Light| -> [f32; 3] { Vector3::from(light.direction).normalize().into() };
+    if !close(&direction(&source.light), &direction(&imported.light))
+        || !close(&source.light.color.map(|c| c.clamp(0.0, 1.0)), &imported.light.color)
+        || !close(&[source.light.intensity.max(0.0)], &[imported.light.intensity])
+    {
+        differences.push("the directional light changed".to_string());
+    }
+
+    if source.cameras.len() != imported.cameras.len() {
+        differences.push(format!("{} cameras became {}", source.cameras.len(), imported.cameras.len()));
+    }
+    for camera in &source.cameras {
+        let Some(other) = imported.cameras.iter().find(|other| other.name == camera.name) else {
+            differences.push(format!("camera '{}' is missing", camera.name));
+            continue;
+        };
+        let forward: [f32; 3] = camera_forward(camera).into();
+        let other_forward: [f32; 3] = camera_forward(other).into();
+        let projection = match camera.projection {
+            Projection::Perspective => close(&[camera.fovy], &[other.fovy]),
+            Projection::Orthographic => close(&[camera.ortho_height], &[other.ortho_height]),
+        };
+        if !close(&camera.position, &other.position) || !close(&forward, &other_forward)
+            || camera.projection != other.projection || !projection
+            || !close(&[camera.znear, camera.zfar], &[other.znear, other.zfar])
+        {
+            differences.push(format!("camera '{}' changed", camera.name));
+        }
+    }
+    if let (Some(a), Some(b)) = (source.default_camera(), imported.default_camera())
+        && a.name != b.name
+    {
+        differences.push(format!("the default camera '{}' became '{}'", a.name, b.name));
+    }
+    differences
+}
+
+// file contents of a texture, which may be an image inside a glTF file (`file#imageN`)
+fn texture_bytes(assets_root: &str, albedo_texture: &str) -> Result<Vec<u8>, String> {
+    match albedo_texture.split_once('#') {
+        Some((file, fragment)) => {
+            let index = fragment.strip_prefix("image").and_then(|index| index.parse().ok())
+                .ok_or_else(|| format!("invalid embedded image '{}'", albedo_texture))?;
+            gltf::Gltf::open(&Path::new(assets_root).join(file))?.image_bytes(index)
+        }
+        None => {
+            let file = Path::new(assets_root).join(albedo_texture);
+            fs::read(&file).map_err(|e| format!("texture {}: {}", file.display(), e))
+        }
+    }
+}
+
+fn light_extension(light: usize) -> Json {
+    Json::object([("KHR_lights_punctual", Json::object([("light", light.into())]))])
+}
+
+fn camera_json(camera: &SceneCamera) -> Json {
+    match camera.projection {
+        Projection::Perspective => Json::object([
+            ("name", camera.name.as_str().into()),
+            ("type", "perspective".into()),
+            ("perspective", Json::object([
+                ("yfov", camera.fovy.to_radians().into()),
+                ("znear", camera.znear.into()),
+                ("zfar", camera.zfar.into()),
+            ])),
+        ]),
+        // glTF wants a width too; the window decides the real one, so it's left square
+        Projection::Orthographic => Json::object([
+            ("name", camera.name.as_str().into()),
+            ("type", "orthographic".into()),
+            ("orthographic", Json::object([
+                ("xmag", (camera.ortho_height / 2.0).into()),
+                ("ymag", (camera.ortho_height / 2.0).into()),
+                ("znear", camera.znear.into()),
+                ("zfar", camera.zfar.into()),
+            ])),
+        ]),
+    }
+}
+
+// view direction of a scene camera, as Camera::forward computes it
+fn camera_forward(camera: &SceneCamera) -> Vector3<f32> {
+    let (yaw_sin, yaw_cos) = camera.yaw.to_radians().sin_cos();
+    let (pitch_sin, pitch_cos) = camera.pitch.to_radians().sin_cos();
+    Vector3::new(yaw_cos * pitch_cos, pitch_sin, yaw_sin * pitch_cos).normalize()
+}
+
+// rotation that turns -Z towards `forward` while keeping +Y up, as glTF cameras and lights expect
+fn look_rotation(forward: Vector3<f32>) -> Quaternion<f32> {
+    let back = -forward.normalize();
+    let right = Vector3::unit_y().cross(back);
+    // looking straight up or down, any horizontal axis will do
+    let right = if right.magnitude2() > 1e-12 { right.normalize() } else { Vector3::unit_x() };
+    let up = back.cross(right);
+    Quaternion::from(Matrix3::from_cols(right, up, back)).normalize()
+}
+
+fn quaternion_array(rotation: Quaternion<f32>) -> [f32; 4] {
+    [rotation.v.x, rotation.v.y, rotation.v.z, rotation.s]
+}
+
+// a GLB file: header, JSON chunk padded with spaces, binary chunk padded with zeros
+fn glb(json: &str, buffer: &[u8]) -> Vec<u8> {
+    let mut json = json.as_bytes().to_vec();
+    json.resize(json.len().next_multiple_of(4), b' ');
+    let mut bin = buffer.to_vec();
+    bin.resize(bin.len().next_multiple_of(4), 0);
+    let chunks = [(GLB_JSON_CHUNK, json), (GLB_BIN_CHUNK, bin)];
+    let total = 12 + chunks.iter().filter(|(_, data)| !data.is_empty()).map(|(_, data)| 8 + data.len()).sum::<usize>();
+
+    let mut bytes = Vec::with_capacity(total);
+    bytes.extend_from_slice(GLB_MAGIC);
+    bytes.extend_from_slice(&2u32.to_le_bytes());
+    bytes.extend_from_slice(&(total as u32).to_le_bytes());
+    for (kind, data) in chunks.iter().filter(|(_, data)| !data.is_empty()) {
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&kind.to_le_bytes());
+        bytes.extend_from_slice(data);
+    }
+    bytes
+}
+
+// URIs in glTF are percent-encoded; letters, digits and `-._~/` stay as they are
+fn percent_encode(path: &str) -> String {
+    path.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::{Light, ObjectInstance};
+    use crate::transform::Transform;
+
+    const ASSETS_ROOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets");
+
+    fn instance(name: &str, geometry: &ObjectGeometry, transform: Transform, material: Material) -> ObjectInstance {
+        ObjectInstance {
+            name: name.to_string(),
+            geometry_name: geometry.name.clone(),
+            transform,
+            parent: None,
+            emissive: 0.0,
+            emissive_color: [1.0; 3],
+            material,
+        }
+    }
+
+    // a parent with a textured child, an emissive object, the sun and a camera
+    fn test_scene() -> Scene {
+        let mut scene = Scene::new("Export Test".to_string());
+        let sphere = ObjectGeometry::from_primitive("sphere segments=8 rings=4").unwrap();
+        let plane = ObjectGeometry::from_primitive("plane subdivisions=2").unwrap();
+        let checker = Material {
+            name: "Checker".to_string(),
+            albedo_texture: "textures/checkerboard.png".to_string(),
+            base_color: [0.8, 0.6, 0.4, 1.0],
+            roughness: 0.3,
+            metallic: 0.1,
+            ..Material::default()
+        };
+
+        scene.instances.push(instance("Base", &sphere, Transform::new().with_position(1.0, 0.5, -2.0).with_rotation(0.0, 30.0, 0.0).with_scale(2.0, 2.0, 2.0), Material::default()));
+        let mut child = instance("Floor", &plane, Transform::new().with_position(0.0, -0.5, 0.0).with_rotation(10.0, 0.0, 5.0).with_scale(3.0, 1.0, 2.0), checker);
+        child.parent = Some(0);
+        scene.instances.push(child);
+        let mut glow = instance("Glow", &sphere, Transform::new().with_position(-1.5, 1.0, 0.0), Material::default());
+        glow.emissive = 1.5;
+        glow.emissive_color = [1.0, 0.5, 0.2];
+        scene.instances.push(glow);
+        scene.geometries.insert(sphere.name.clone(), sphere);
+        scene.geometries.insert(plane.name.clone(), plane);
+
+        scene.light = Light { direction: [0.2, -1.0, 0.3], color: [1.0, 0.9, 0.8], intensity: 2.0, ..Light::default() };
+        scene.has_light = true;
+        scene.cameras.push(SceneCamera {
+            name: "Front".to_string(),
+            position: [0.0, 2.0, 6.0],
+            yaw: -90.0,
+            pitch: -15.0,
+            fovy: 50.0,
+            is_default: true,
+            ..SceneCamera::default()
+        });
+        scene
+    }
+
+    fn assert_no_empty_arrays(json: &Json, at: &str) {
+        match json {
+            Json::Array(items) => {
+                assert!(!items.is_empty(), "empty array at {}", at);
+                for (index, item) in items.iter().enumerate() {
+                    assert_no_empty_arrays(item, &format!("{}[{}]", at, index));
+                }
+            }
+            Json::Object(members) => {
+                for (key, value) in members {
+                    assert_no_empty_arrays(value, &format!("{}.{}", at, key));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // validates the file with the gltf crate (schema, references, buffers and images), reads every mesh back
+    // through it, and checks what the crate leaves to viewers: no empty arrays, aligned offsets, POSITION bounds
+    fn assert_valid_gltf(path: &Path, scene: &Scene) {
+        let (document, buffers, images) = ::gltf::import(path).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+        assert_eq!(document.as_json().asset.version, "2.0");
+        assert_eq!(images.len(), document.images().len());
+        for instance in &scene.instances {
+            assert!(document.nodes().any(|node| node.name() == Some(instance.name.as_str())), "no node '{}'", instance.name);
+        }
+
+        let mut primitives = 0;
+        for primitive in document.meshes().flat_map(|mesh| mesh.primitives().collect::<Vec<_>>()) {
+            let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+            let positions: Vec<[f32; 3]> = reader.read_positions().expect("a primitive without POSITION").collect();
+            let indices: Vec<u32> = reader.read_indices().expect("a primitive without indices").into_u32().collect();
+            assert!(!indices.is_empty() && indices.iter().all(|&index| (index as usize) < positions.len()));
+            if primitive.indices().unwrap().data_type() == ::gltf::accessor::DataType::U16 {
+                assert!(!indices.contains(&(u16::MAX as u32)), "16-bit index 65535 is the primitive restart value");
+            }
+            let min: [f32; 3] = std::array::from_fn(|axis| positions.iter().map(|position| position[axis]).fold(f32::MAX, f32::min));
+            let max: [f32; 3] = std::array::from_fn(|axis| positions.iter().map(|position| position[axis]).fold(f32::MIN, f32::max));
+            let bounds = primitive.bounding_box();
+            assert_eq!((bounds.min, bounds.max), (min, max), "POSITION min/max");
+            primitives += 1;
+        }
+        assert!(primitives > 0);
+
+        let gltf = gltf::Gltf::open(path).unwrap();
+        assert_no_empty_arrays(&gltf.json, "root");
+        for list in ["bufferViews", "accessors"] {
+            for (index, item) in gltf.list(list).iter().enumerate() {
+                let offset = item.get("byteOffset").and_then(Json::as_usize).unwrap_or(0);
+                assert_eq!(offset % 4, 0, "{} {} has byteOffset {}", list, index, offset);
+            }
+        }
+    }
+
+    // header, chunk headers and padding as the GLB container spec lays them out
+    fn assert_valid_glb(bytes: &[u8]) {
+        let word = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+        assert_eq!(&bytes[..4], GLB_MAGIC);
+        assert_eq!(word(4), 2, "GLB version");
+        assert_eq!(word(8), bytes.len(), "GLB length header");
+
+        let json_length = word(12);
+        assert_eq!(word(16), GLB_JSON_CHUNK as usize, "the first chunk is JSON");
+        assert_eq!(json_length % 4, 0, "JSON chunk length");
+        let json = std::str::from_utf8(&bytes[20..20 + json_length]).unwrap();
+        assert!(Json::parse(json).is_ok());
+        let json_padding = json.len() - json.trim_end_matches(' ').len();
+        assert!(json.trim_end_matches(' ').ends_with('}') && json_padding < 4, "JSON is padded with up to 3 spaces");
+
+        let bin_start = 20 + json_length;
+        let bin_length = word(bin_start);
+        assert_eq!(word(bin_start + 4), GLB_BIN_CHUNK as usize, "the second chunk is BIN");
+        assert_eq!(bin_length % 4, 0, "BIN chunk length");
+        assert_eq!(bin_start + 8 + bin_length, bytes.len(), "nothing follows the BIN chunk");
+        let byte_length = Json::parse(json).unwrap().get("buffers").unwrap().items()[0].get("byteLength").and_then(Json::as_usize).unwrap();
+        assert!(byte_length <= bin_length && bin_length - byte_length < 4, "BIN is padded with up to 3 bytes");
+        assert!(bytes[bin_start + 8 + byte_length..].iter().all(|&byte| byte == 0), "BIN padding is zeros");
+
+        let glb = ::gltf::Glb::from_slice(bytes).unwrap();
+        assert_eq!(glb.header.length as usize, bytes.len());
+    }
+
+    #[test]
+    fn meshes_using_index_65535_get_32_bit_indices() {
+        let dir = std::env::temp_dir().join(format!("arbitra_export_large_{}", std::process::id()));
+        let mut scene = Scene::new("Large".to_string());
+        // 256 x 256 vertices, the most a geometry can have
+        let plane = ObjectGeometry::from_primitive("plane subdivisions=255").unwrap();
+        assert_eq!(plane.vertices.len(), crate::object::MAX_VERTICES);
+        scene.instances.push(instance("Plane", &plane, Transform::new(), Material::default()));
+        scene.geometries.insert(plane.name.clone(), plane);
+
+        let path = dir.join("large.glb");
+        scene.export_gltf(&path, ASSETS_ROOT).unwrap();
+        assert_valid_gltf(&path, &scene);
+        assert_valid_glb(&fs::read(&path).unwrap());
+        let gltf = gltf::Gltf::open(&path).unwrap();
+        let indices = gltf.list("meshes")[0].get("primitives").unwrap().items()[0].get("indices").and_then(Json::as_usize).unwrap();
+        assert_eq!(gltf.item("accessors", indices).unwrap().get("componentType").and_then(Json::as_usize), Some(UNSIGNED_INT));
+        let imported = Scene::load_from_gltf(&path, ASSETS_ROOT).unwrap();
+        assert_eq!(compare_round_trip(&scene, &imported, ASSETS_ROOT), Vec::<String>::new());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn export_round_trips_as_gltf_and_glb() {
+        let dir = std::env::temp_dir().join(format!("arbitra_export_{}", std::process::id()));
+        let scene = test_scene();
+
+        for file in ["scene.gltf", "scene.glb"] {
+            let path = dir.join(file);
+            scene.export_gltf(&path, ASSETS_ROOT).unwrap();
+            assert_valid_gltf(&path, &scene);
+            let imported = Scene::load_from_gltf(&path, ASSETS_ROOT).unwrap();
+            assert_eq!(compare_round_trip(&scene, &imported, ASSETS_ROOT), Vec::<String>::new(), "{}", file);
+        }
+
+        assert_valid_glb(&fs::read(dir.join("scene.glb")).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
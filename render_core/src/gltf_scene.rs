@@ -0,0 +1,423 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use cgmath::{InnerSpace, Matrix4, SquareMatrix, Vector3, Vector4};
+
+use crate::camera::Projection;
+use crate::gltf::{self, Gltf};
+use crate::json::Json;
+use crate::material::{Material, VertexColorMode};
+use crate::object::{ObjectGeometry, MAX_VERTICES};
+use crate::scene::{ObjectInstance, Scene, SceneCamera, EMISSIVE_LIGHT_INTENSITY, MAX_POINT_LIGHTS};
+use crate::transform::Transform;
+
+// point and spot lights are drawn as a small glowing sphere of this size
+const LIGHT_MARKER: &str = "icosphere subdivisions=1";
+const LIGHT_MARKER_SCALE: f32 = 0.1;
+// extensions the import understands; other used extensions are reported
+const IMPORTED_EXTENSIONS: &[&str] = &["KHR_lights_punctual", "KHR_mesh_quantization"];
+// a model matrix this far from its position/rotation/scale split has shear
+const SHEAR_TOLERANCE: f32 = 1e-4;
+
+// state while the nodes are walked; meshes and materials are converted once and shared by the nodes using them
+struct Import<'a> {
+    gltf: &'a Gltf,
+    // the file relative to the assets folder, for texture paths
+    asset_path: String,
+    scene: Scene,
+    // geometry name and material per part of each mesh
+    meshes: HashMap<usize, Vec<(String, Material)>>,
+    materials: HashMap<usize, Material>,
+    has_sun: bool,
+    notes: Vec<String>,
+}
+
+// where a node's instances go: the instance they attach to, the transforms of nodes without an instance
+// since then, and the world matrix of the node above
+#[derive(Clone, Copy)]
+struct Placement {
+    parent: Option<usize>,
+    pending: Matrix4<f32>,
+    parent_world: Matrix4<f32>,
+}
+
+impl Scene {
+    /// Scene from the default scene of a .gltf or .glb file. Nodes become instances and keep their
+    /// hierarchy through `parent`; nodes without a mesh are folded into their children. Meshes become one
+    /// geometry per material they use, materials keep their PBR factors and base color texture,
+    /// KHR_lights_punctual lights become the directional light or glowing point light markers, and
+    /// cameras become scene cameras. Everything that is dropped is listed on the console
+    pub fn load_from_gltf<P: AsRef<Path>>(path: P, assets_root: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let (scene, notes) = Self::import_gltf(path, assets_root)?;
+        println!("Loaded glTF scene '{}': {} instances, {} geometries, {} cameras",
+            scene.name, scene.instances.len(), scene.geometries.len(), scene.cameras.len());
+        if !notes.is_empty() {
+            eprintln!("{}: some of the file could not be imported:", path.display());
+            for note in notes {
+                eprintln!("  - {}", note);
+            }
+        }
+        Ok(scene)
+    }
+
+    // the scene and what had to be dropped from it
+    fn import_gltf(path: &Path, assets_root: &str) -> Result<(Self, Vec<String>), Box<dyn std::error::Error>> {
+        let in_file = |e: String| format!("{}: {}", path.display(), e);
+        let gltf = Gltf::open(path).map_err(in_file)?;
+        // textures are named relative to the assets folder, which may mean going up out of it
+        let asset_path = gltf::relative_path(Path::new(assets_root), path)
+            .ok_or_else(|| in_file(format!("can't find the file relative to the assets folder '{}'", assets_root)))?;
+
+        let scene_index = gltf.json.get("scene").and_then(Json::as_usize).unwrap_or(0);
+        let name = gltf.list("scenes").get(scene_index)
+            .and_then(|scene| scene.get("name"))
+            .and_then(Json::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned()));
+        let mut import = Import {
+            gltf: &gltf,
+            asset_path,
+            scene: Scene::new(name),
+            meshes: HashMap::new(),
+            materials: HashMap::new(),
+            has_sun: false,
+            notes: Vec::new(),
+        };
+        import.note_unsupported();
+
+        let root = Placement { parent: None, pending: Matrix4::identity(), parent_world: Matrix4::identity() };
+        let mut stack: Vec<(usize, Placement)> = gltf.scene_roots().into_iter().rev().map(|node| (node, root)).collect();
+        let mut visited = 0;
+        while let Some((node_index, placement)) = stack.pop() {
+            visited += 1;
+            if visited > gltf.list("nodes").len() {
+                return Err(in_file("the node hierarchy has a cycle or a node with two parents".to_string()).into());
+            }
+            let children_placement = import.node(node_index, placement).map_err(in_file)?;
+            let node = gltf.item("nodes", node_index).map_err(in_file)?;
+            // reversed so children come out of the stack in file order
+            for &child in node.get("children").map_or(&[][..], Json::items).iter().filter_map(Json::as_usize).collect::<Vec<_>>().iter().rev() {
+                stack.push((child, children_placement));
+            }
+        }
+
+        let beyond_limit: Vec<&str> = import.scene.instances.iter()
+            .filter(|instance| instance.emissive > 0.0)
+            .skip(MAX_POINT_LIGHTS)
+            .map(|instance| instance.name.as_str())
+            .collect();
+        if !beyond_limit.is_empty() {
+            import.notes.push(format!("only {} point lights are supported; these are drawn but give no light: {}",
+                MAX_POINT_LIGHTS, beyond_limit.join(", ")));
+        }
+
+        let mut scene = import.scene;
+        scene.has_light = import.has_sun;
+        scene.included_files = gltf.external_files.clone();
+        Ok((scene, import.notes))
+    }
+}
+
+impl Import<'_> {
+    fn note_unsupported(&mut self) {
+        let gltf = self.gltf;
+        let scenes = gltf.list("scenes").len();
+        if scenes > 1 {
+            self.notes.push(format!("only the default scene was loaded, {} others were ignored", scenes - 1));
+        }
+        for (list, what) in [("animations", "animations"), ("skins", "skins (skinned meshes are drawn in their bind pose)")] {
+            if !gltf.list(list).is_empty() {
+                self.notes.push(format!("ignored {} {}", gltf.list(list).len(), what));
+            }
+        }
+        for extension in gltf.json.get("extensionsUsed").map_or(&[][..], Json::items).iter().filter_map(Json::as_str) {
+            // material extensions are reported with the material
+            if !IMPORTED_EXTENSIONS.contains(&extension) && !extension.starts_with("KHR_materials_") {
+                self.notes.push(format!("ignored the extension {}", extension));
+            }
+        }
+    }
+
+    // adds the node's instances, light and camera; returns where its children go
+    fn node(&mut self, index: usize, placement: Placement) -> Result<Placement, String> {
+        let node = self.gltf.item("nodes", index)?;
+        let name = node.get("name").and_then(Json::as_str).map_or_else(|| format!("node {}", index), str::to_string);
+        let own_matrix = gltf::local_matrix(node);
+        let parent = placement.parent;
+        let local = placement.pending * own_matrix;
+        let world = placement.parent_world * own_matrix;
+        let mut mesh_instance = None;
+
+        if let Some(mesh) = node.get("mesh").and_then(Json::as_usize) {
+            let parts = self.mesh(mesh)?;
+            let first = self.scene.instances.len();
+            for (part, (geometry_name, material)) in parts.into_iter().enumerate() {
+                // further parts ride along with the first at its origin
+                let (part_name, transform, part_parent) = if part == 0 {
+                    (name.clone(), self.transform(local, &name), parent)
+                } else {
+                    (format!("{}_{}", name, part), Transform::new(), Some(first))
+                };
+                self.scene.instances.push(instance(part_name, geometry_name, transform, part_parent, material));
+            }
+            if self.scene.instances.len() > first {
+                mesh_instance = Some(first);
+            }
+        }
+        if node.get("skin").is_some() {
+            self.notes.push(format!("node '{}': the skin was ignored", name));
+        }
+        if node.get("weights").is_some() {
+            self.notes.push(format!("node '{}': morph target weights were ignored", name));
+        }
+
+        let light = node.get("extensions")
+            .and_then(|extensions| extensions.get("KHR_lights_punctual"))
+            .and_then(|light| light.get("light"))
+            .and_then(Json::as_usize);
+        let mut light_marker = None;
+        if let Some(light) = light {
+            // a mesh node carries the marker as a child; otherwise the marker stands in for the node
+            light_marker = match mesh_instance {
+                Some(instance) => {
+                    self.light(light, &name, world, Matrix4::identity(), Some(instance));
+                    None
+                }
+                None => self.light(light, &name, world, local, parent),
+            };
+        }
+        if let Some(camera) = node.get("camera").and_then(Json::as_usize) {
+            self.camera(camera, &name, world)?;
+        }
+
+        let (parent, pending) = match (mesh_instance, light_marker) {
+            (Some(instance), _) => (Some(instance), Matrix4::identity()),
+            // the marker is shrunk, its children shouldn't be
+            (None, Some(marker)) => (Some(marker), Matrix4::from_scale(1.0 / LIGHT_MARKER_SCALE)),
+            (None, None) => (parent, local),
+        };
+        Ok(Placement { parent, pending, parent_world: world })
+    }
+
+    // one geometry per material the mesh uses, converted the first time a node uses the mesh
+    fn mesh(&mut self, index: usize) -> Result<Vec<(String, Material)>, String> {
+        if let Some(parts) = self.meshes.get(&index) {
+            return Ok(parts.clone());
+        }
+        let gltf = self.gltf;
+        let mesh = gltf.item("meshes", index)?;
+
+        // primitives grouped by material in order of first use, and whether any of them has vertex colors
+        let mut groups: Vec<(Option<usize>, crate::object::MeshData, bool)> = Vec::new();
+        for primitive in mesh.get("primitives").map_or(&[][..], Json::items) {
+            let Some(part) = gltf.primitive_mesh(index, primitive, &mut self.notes)? else {
+                continue;
+            };
+            let material = primitive.get("material").and_then(Json::as_usize);
+            let has_colors = primitive.get("attributes").and_then(|attributes| attributes.get("COLOR_0")).is_some();
+            match groups.iter_mut().find(|(group_material, _, _)| *group_material == material) {
+                Some((_, group, group_colors)) => {
+                    group.append(part);
+                    *group_colors |= has_colors;
+                }
+                None => groups.push((material, part, has_colors)),
+            }
+        }
+
+        let mut parts = Vec::new();
+        let group_count = groups.len();
+        for (part, (material_index, data, has_colors)) in groups.into_iter().enumerate() {
+            let geometry_name = match group_count {
+                1 => format!("{}#mesh{}", self.asset_path, index),
+                _ => format!("{}#mesh{}.{}", self.asset_path, index, part),
+            };
+            if data.vertices.len() > MAX_VERTICES {
+                return Err(format!("mesh {} has {} vertices, at most {} are supported", index, data.vertices.len(), MAX_VERTICES));
+            }
+            let mut material = match material_index {
+                Some(material_index) => self.material(material_index),
+                None => Material::default(),
+            };
+            if has_colors {
+                // glTF multiplies the base color by COLOR_0
+                material.vertex_color = VertexColorMode::Multiply;
+            }
+            self.scene.geometries.insert(geometry_name.clone(), ObjectGeometry {
+                name: geometry_name.clone(),
+                vertices: data.vertices,
+                indices: data.indices.into_iter().map(|index| index as u16).collect(),
+                // the scene reloads as a whole when the file changes
+                source_files: Vec::new(),
+                material: Some(material.clone()),
+            });
+            parts.push((geometry_name, material));
+        }
+        self.meshes.insert(index, parts.clone());
+        Ok(parts)
+    }
+
+    fn material(&mut self, index: usize) -> Material {
+        if let Some(material) = self.materials.get(&index) {
+            return material.clone();
+        }
+        let material = self.gltf.material(index, &self.asset_path, &mut self.notes);
+        self.materials.insert(index, material.clone());
+        material
+    }
+
+    fn transform(&mut self, matrix: Matrix4<f32>, name: &str) -> Transform {
+        let transform = Transform::from_matrix(matrix);
+        let rebuilt = transform.to_matrix();
+        let difference = (0..4).flat_map(|col| (0..4).map(move |row| (col, row)))
+            .map(|(col, row)| (rebuilt[col][row] - matrix[col][row]).abs())
+            .fold(0.0, f32::max);
+        if difference > SHEAR_TOLERANCE {
+            self.notes.push(format!("node '{}': its transform has shear (from a non-uniformly scaled parent without a mesh), which was dropped", name));
+        }
+        transform
+    }
+
+    // sets the directional light, or adds a marker instance for point and spot lights and returns its index
+    fn light(&mut self, index: usize, node_name: &str, world: Matrix4<f32>, local: Matrix4<f32>, parent: Option<usize>) -> Option<usize> {
+        let lights = self.gltf.json.get("extensions")
+            .and_then(|extensions| extensions.get("KHR_lights_punctual"))
+            .and_then(|extension| extension.get("lights"))
+            .map_or(&[][..], Json::items);
+        let Some(light) = lights.get(index) else {
+            self.notes.push(format!("node '{}': light {} doesn't exist", node_name, index));
+            return None;
+        };
+        let name = light.get("name").and_then(Json::as_str).unwrap_or(node_name).to_string();
+        let color = light.get("color").and_then(Json::as_f32_array).unwrap_or([1.0; 3]);
+        let intensity = light.get("intensity").and_then(Json::as_f32).unwrap_or(1.0);
+
+        match light.get("type").and_then(Json::as_str) {
+            Some("directional") if self.has_sun => {
+                self.notes.push(format!("light '{}': only one directional light is supported, this one was dropped", name));
+                None
+            }
+            Some("directional") => {
+                // lights shine down their local -Z axis
+                let direction = (world * Vector4::new(0.0, 0.0, -1.0, 0.0)).truncate();
+                if direction.magnitude2() > 0.0 {
+                    self.scene.light.direction = direction.normalize().into();
+                }
+                self.scene.light.color = color;
+                self.scene.light.intensity = intensity;
+                self.has_sun = true;
+                None
+            }
+            Some(kind @ ("point" | "spot")) => {
+                if kind == "spot" {
+                    self.notes.push(format!("light '{}': the spot cone was dropped, it shines in all directions", name));
+                }
+                if light.get("range").is_some() {
+                    self.notes.push(format!("light '{}': the range was dropped", name));
+                }
+                let geometry = ObjectGeometry::from_primitive(LIGHT_MARKER).ok()?;
+                let geometry_name = geometry.name.clone();
+                self.scene.geometries.entry(geometry_name.clone()).or_insert(geometry);
+                let transform = self.transform(local * Matrix4::from_scale(LIGHT_MARKER_SCALE), &name);
+                self.scene.instances.push(ObjectInstance {
+                    emissive: intensity / EMISSIVE_LIGHT_INTENSITY,
+                    emissive_color: color,
+                    ..instance(name, geometry_name, transform, parent, Material::default())
+                });
+                Some(self.scene.instances.len() - 1)
+            }
+            other => {
+                self.notes.push(format!("light '{}': unknown type {:?}, dropped", name, other));
+                None
+            }
+        }
+    }
+
+    fn camera(&mut self, index: usize, node_name: &str, world: Matrix4<f32>) -> Result<(), String> {
+        let camera = self.gltf.item("cameras", index)?;
+        let name = camera.get("name").and_then(Json::as_str).unwrap_or(node_name).to_string();
+        // cameras look down their local -Z axis with +Y up
+        let forward = (world * Vector4::new(0.0, 0.0, -1.0, 0.0)).truncate().normalize();
+        let up = (world * Vector4::new(0.0, 1.0, 0.0, 0.0)).truncate().normalize();
+        let mut scene_camera = SceneCamera {
+            name: name.clone(),
+            position: world.w.truncate().into(),
+            yaw: forward.z.atan2(forward.x).to_degrees(),
+            pitch: forward.y.clamp(-1.0, 1.0).asin().to_degrees(),
+            is_default: self.scene.cameras.is_empty(),
+            ..SceneCamera::default()
+        };
+
+        // scene cameras keep the horizon level
+        let level_up = forward.cross(Vector3::unit_y()).cross(forward);
+        if level_up.magnitude2() > 0.0 && up.dot(level_up.normalize()) < 1f32.to_radians().cos() {
+            self.notes.push(format!("camera '{}': the roll was dropped", name));
+        }
+
+        let number = |settings: Option<&Json>, key: &str| settings.and_then(|settings| settings.get(key)).and_then(Json::as_f32);
+        match camera.get("type").and_then(Json::as_str) {
+            Some("orthographic") => {
+                let settings = camera.get("orthographic");
+                scene_camera.projection = Projection::Orthographic;
+                scene_camera.ortho_height = number(settings, "ymag").map_or(scene_camera.ortho_height, |ymag| ymag * 2.0);
+                scene_camera.znear = number(settings, "znear").unwrap_or(scene_camera.znear);
+                scene_camera.zfar = number(settings, "zfar").unwrap_or(scene_camera.zfar);
+            }
+            _ => {
+                let settings = camera.get("perspective");
+                scene_camera.fovy = number(settings, "yfov").map_or(scene_camera.fovy, f32::to_degrees);
+                scene_camera.znear = number(settings, "znear").unwrap_or(scene_camera.znear);
+                match number(settings, "zfar") {
+                    Some(zfar) => scene_camera.zfar = zfar,
+                    None => self.notes.push(format!("camera '{}': the infinite far plane was replaced by {}", name, scene_camera.zfar)),
+                }
+            }
+        }
+        self.scene.cameras.push(scene_camera);
+        Ok(())
+    }
+}
+
+fn instance(name: String, geometry_name: String, transform: Transform, parent: Option<usize>, material: Material) -> ObjectInstance {
+    ObjectInstance {
+        name,
+        geometry_name,
+        transform,
+        parent,
+        emissive: 0.0,
+        emissive_color: [1.0; 3],
+        material,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_lights_past_the_limit_are_reported() {
+        let root = std::env::temp_dir().join(format!("arbitra_gltf_lights_{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let count = MAX_POINT_LIGHTS + 2;
+        let lights: Vec<String> = (0..count).map(|_| r#"{"type": "point", "intensity": 10.0}"#.to_string()).collect();
+        let nodes: Vec<String> = (0..count)
+            .map(|index| format!(r#"{{"name": "Lamp{}", "extensions": {{"KHR_lights_punctual": {{"light": {}}}}}}}"#, index, index))
+            .collect();
+        let json = format!(
+            r#"{{"asset": {{"version": "2.0"}}, "extensionsUsed": ["KHR_lights_punctual"],
+                "extensions": {{"KHR_lights_punctual": {{"lights": [{}]}}}},
+                "scenes": [{{"nodes": [{}]}}], "nodes": [{}]}}"#,
+            lights.join(","),
+            (0..count).map(|index| index.to_string()).collect::<Vec<_>>().join(","),
+            nodes.join(","),
+        );
+        std::fs::write(root.join("lamps.gltf"), json).unwrap();
+
+        let (scene, notes) = Scene::import_gltf(&root.join("lamps.gltf"), root.to_str().unwrap()).unwrap();
+        assert_eq!(scene.instances.len(), count);
+        assert!(scene.instances.iter().all(|instance| instance.emissive * EMISSIVE_LIGHT_INTENSITY == 10.0));
+        let expected = format!("only {} point lights are supported; these are drawn but give no light: Lamp8, Lamp9", MAX_POINT_LIGHTS);
+        assert_eq!(notes, [expected]);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,74 +1,696 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use anyhow::{bail, Context, Result};
+use gilrs::{Axis as GamepadAxis, Button as GamepadButton};
+use winit::event::MouseButton;
 use winit::keyboard::KeyCode;
 
-pub struct Input {
-    pub is_forward_pressed: bool,
-    pub is_backward_pressed: bool,
-    pub is_left_pressed: bool,
-    pub is_right_pressed: bool,
-    pub is_space_pressed: bool,
-    pub is_shift_pressed: bool,
-    pub is_escape_pressed: bool,
+// rebindable controls, loaded at startup and hot reloaded like the other assets
+pub const BINDINGS_FILE: &str = "assets/config/input.arbind";
+
+// how far a held key or fully deflected stick moves an axis per second, in the axis' own units
+const HELD_LOOK_RATE: f32 = 600.0; // mouse counts
+const HELD_ZOOM_RATE: f32 = 8.0; // wheel lines
+// analog inputs past this count as a held button for digital actions (sprint, pan)
+const PRESS_THRESHOLD: f32 = 0.5;
+const DEFAULT_GAMEPAD_DEADZONE: f32 = 0.15;
+
+/// Named things the user can do, each bound to any number of keys, mouse buttons or wheel steps
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Sprint,
+    Pan,
+    ReleaseCursor,
+    ToggleCameraMode,
+    SelectNext,
+    FrameSelected,
+    SaveBookmark,
+    ToggleProjection,
+    ViewTop,
+    ViewFront,
+    ViewSide,
+    PlayCameraPath,
+    // index into the scene's cameras, bound as camera_1 .. camera_9
+    JumpToCamera(usize),
+}
+
+const MAX_CAMERA_ACTIONS: usize = 9;
+
+const NAMED_ACTIONS: [(&str, Action); 18] = [
+    ("move_forward", Action::MoveForward),
+    ("move_backward", Action::MoveBackward),
+    ("move_left", Action::MoveLeft),
+    ("move_right", Action::MoveRight),
+    ("move_up", Action::MoveUp),
+    ("move_down", Action::MoveDown),
+    ("sprint", Action::Sprint),
+    ("pan", Action::Pan),
+    ("release_cursor", Action::ReleaseCursor),
+    ("toggle_camera_mode", Action::ToggleCameraMode),
+    ("select_next", Action::SelectNext),
+    ("frame_selected", Action::FrameSelected),
+    ("save_bookmark", Action::SaveBookmark),
+    ("toggle_projection", Action::ToggleProjection),
+    ("view_top", Action::ViewTop),
+    ("view_front", Action::ViewFront),
+    ("view_side", Action::ViewSide),
+    ("play_camera_path", Action::PlayCameraPath),
+];
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some(number) = name.strip_prefix("camera_") {
+            return match number.parse::<usize>() {
+                Ok(n) if (1..=MAX_CAMERA_ACTIONS).contains(&n) => Some(Action::JumpToCamera(n - 1)),
+                _ => None,
+            };
+        }
+        NAMED_ACTIONS.iter().find(|(action_name, _)| *action_name == name).map(|&(_, action)| action)
+    }
+}
+
+/// Continuous inputs, read once per frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+    LookX,
+    LookY,
+    // positive = away from the user, like the wheel
+    Zoom,
+}
+
+impl Axis {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "look_x" => Some(Axis::LookX),
+            "look_y" => Some(Axis::LookY),
+            "zoom" => Some(Axis::Zoom),
+            _ => None,
+        }
+    }
+
+    fn held_rate(self) -> f32 {
+        match self {
+            Axis::LookX | Axis::LookY => HELD_LOOK_RATE,
+            Axis::Zoom => HELD_ZOOM_RATE,
+        }
+    }
+}
+
+/// A physical input that can trigger an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    // one wheel notch counts as a press
+    WheelUp,
+    WheelDown,
+    // analog buttons (triggers) report how far they are pressed
+    PadButton(GamepadButton),
+    // one half of a stick axis, true for the positive direction
+    PadAxis(GamepadAxis, bool),
+}
+
+/// Something that feeds an axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AxisSource {
+    MouseX,
+    MouseY,
+    Wheel,
+    // held key or button pushing the axis in the given direction (+1 or -1)
+    Held(Binding, f32),
+    // full range of a stick axis, scaled by the direction
+    PadAxis(GamepadAxis, f32),
+}
+
+/// Gamepad input in engine terms; gilrs events are translated in gamepad.rs,
+/// and tools can feed synthetic ones without any hardware attached
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GamepadEvent {
+    ButtonPressed(GamepadButton),
+    ButtonReleased(GamepadButton),
+    // analog button travel in 0..1
+    ButtonChanged(GamepadButton, f32),
+    // stick position in -1..1, up and right are positive
+    AxisChanged(GamepadAxis, f32),
+    Disconnected,
+}
+
+/// Everything that reaches `Input`, in the order it arrived; recordings store these per frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    Key(KeyCode, bool),
+    MouseButton(MouseButton, bool),
+    MouseMove(f32, f32),
+    Scroll(f32),
+    // index of the pad that sent the event, and the event
+    Gamepad(usize, GamepadEvent),
+    // focus loss or cursor release, held inputs won't see their release events
+    ReleaseAll,
+}
+
+/// Action and axis bindings, parsed from an `.arbind` file
+#[derive(Debug, Clone)]
+pub struct InputBindings {
+    actions: HashMap<Action, Vec<Binding>>,
+    axes: HashMap<Axis, Vec<AxisSource>>,
+    // stick values below this are ignored, the rest is rescaled to start from zero
+    gamepad_deadzone: f32,
+}
+
+impl Default for InputBindings {
+    // the built-in layout, also shipped as assets/config/input.arbind
+    fn default() -> Self {
+        use Binding::{Key, Mouse, PadAxis, PadButton};
+        let mut actions = HashMap::from([
+            (Action::MoveForward, vec![Key(KeyCode::KeyW), Key(KeyCode::ArrowUp), PadAxis(GamepadAxis::LeftStickY, true)]),
+            (Action::MoveBackward, vec![Key(KeyCode::KeyS), Key(KeyCode::ArrowDown), PadAxis(GamepadAxis::LeftStickY, false)]),
+            (Action::MoveLeft, vec![Key(KeyCode::KeyA), Key(KeyCode::ArrowLeft), PadAxis(GamepadAxis::LeftStickX, false)]),
+            (Action::MoveRight, vec![Key(KeyCode::KeyD), Key(KeyCode::ArrowRight), PadAxis(GamepadAxis::LeftStickX, true)]),
+            (Action::MoveUp, vec![Key(KeyCode::Space), PadButton(GamepadButton::RightTrigger2)]),
+            (Action::MoveDown, vec![Key(KeyCode::ShiftLeft), Key(KeyCode::ShiftRight), PadButton(GamepadButton::LeftTrigger2)]),
+            (Action::Sprint, vec![Key(KeyCode::ControlLeft), Key(KeyCode::ControlRight), PadButton(GamepadButton::LeftThumb)]),
+            (Action::Pan, vec![Mouse(MouseButton::Middle)]),
+            (Action::ReleaseCursor, vec![Key(KeyCode::Escape)]),
+            (Action::ToggleCameraMode, vec![Key(KeyCode::KeyO), PadButton(GamepadButton::North)]),
+            (Action::SelectNext, vec![Key(KeyCode::Tab), PadButton(GamepadButton::RightTrigger)]),
+            (Action::FrameSelected, vec![Key(KeyCode::KeyF), PadButton(GamepadButton::West)]),
+            (Action::SaveBookmark, vec![Key(KeyCode::KeyB)]),
+            (Action::ToggleProjection, vec![Key(KeyCode::KeyP), Key(KeyCode::Numpad5), PadButton(GamepadButton::Select)]),
+            (Action::ViewTop, vec![Key(KeyCode::Numpad7)]),
+            (Action::ViewFront, vec![Key(KeyCode::Numpad1)]),
+            (Action::ViewSide, vec![Key(KeyCode::Numpad3)]),
+            (Action::PlayCameraPath, vec![Key(KeyCode::KeyC), PadButton(GamepadButton::Start)]),
+        ]);
+        const DIGITS: [KeyCode; MAX_CAMERA_ACTIONS] = [
+            KeyCode::Digit1, KeyCode::Digit2, KeyCode::Digit3,
+            KeyCode::Digit4, KeyCode::Digit5, KeyCode::Digit6,
+            KeyCode::Digit7, KeyCode::Digit8, KeyCode::Digit9,
+        ];
+        for (index, digit) in DIGITS.into_iter().enumerate() {
+            actions.insert(Action::JumpToCamera(index), vec![Key(digit)]);
+        }
+
+        // stick up is positive but mouse up is negative, so look_y flips the right stick
+        let axes = HashMap::from([
+            (Axis::LookX, vec![AxisSource::MouseX, AxisSource::PadAxis(GamepadAxis::RightStickX, 1.0)]),
+            (Axis::LookY, vec![AxisSource::MouseY, AxisSource::PadAxis(GamepadAxis::RightStickY, -1.0)]),
+            (Axis::Zoom, vec![
+                AxisSource::Wheel,
+                AxisSource::Held(PadButton(GamepadButton::DPadUp), 1.0),
+                AxisSource::Held(PadButton(GamepadButton::DPadDown), -1.0),
+            ]),
+        ]);
+
+        InputBindings { actions, axes, gamepad_deadzone: DEFAULT_GAMEPAD_DEADZONE }
+    }
+}
+
+impl InputBindings {
+    // lines are `<action or axis>: <binding> <binding> ...` or `gamepad_deadzone: <0..1>`;
+    // anything not listed keeps its default
+    pub fn from_file(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read input bindings: {}", path))?;
+        Self::parse(&content).with_context(|| format!("Invalid input bindings in {}", path))
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut bindings = InputBindings::default();
+
+        for (line_index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_number = line_index + 1;
+            let Some((name, values)) = line.split_once(':') else {
+                bail!("line {}: expected '<name>: <bindings>'", line_number);
+            };
+            let (name, values) = (name.trim(), values.split_whitespace());
+
+            if name == "gamepad_deadzone" {
+                let deadzone: f32 = values.collect::<String>().parse()
+                    .with_context(|| format!("line {}: invalid gamepad_deadzone", line_number))?;
+                if !(0.0..1.0).contains(&deadzone) {
+                    bail!("line {}: gamepad_deadzone must be in 0..1", line_number);
+                }
+                bindings.gamepad_deadzone = deadzone;
+            } else if let Some(action) = Action::from_name(name) {
+                let parsed = values.map(|value| parse_binding(value)
+                    .with_context(|| format!("line {}: unknown binding '{}'", line_number, value)))
+                    .collect::<Result<Vec<_>>>()?;
+                bindings.actions.insert(action, parsed);
+            } else if let Some(axis) = Axis::from_name(name) {
+                let parsed = values.map(|value| parse_axis_source(value)
+                    .with_context(|| format!("line {}: unknown axis source '{}'", line_number, value)))
+                    .collect::<Result<Vec<_>>>()?;
+                bindings.axes.insert(axis, parsed);
+            } else {
+                bail!("line {}: unknown action or axis '{}'", line_number, name);
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    fn actions_for(&self, binding: Binding) -> impl Iterator<Item = Action> + '_ {
+        self.actions.iter()
+            .filter(move |(_, bound)| bound.contains(&binding))
+            .map(|(&action, _)| action)
+    }
+}
+
+// a key, mouse button, wheel step or gamepad button; `+PadLeftStickY` / `-PadLeftStickY`
+// bind one direction of a stick
+pub fn parse_binding(value: &str) -> Option<Binding> {
+    if let Some((sign, name)) = split_sign(value) {
+        return pad_axis_from_name(name).map(|axis| Binding::PadAxis(axis, sign > 0.0));
+    }
+    match value {
+        "MouseLeft" => Some(Binding::Mouse(MouseButton::Left)),
+        "MouseRight" => Some(Binding::Mouse(MouseButton::Right)),
+        "MouseMiddle" => Some(Binding::Mouse(MouseButton::Middle)),
+        "MouseBack" => Some(Binding::Mouse(MouseButton::Back)),
+        "MouseForward" => Some(Binding::Mouse(MouseButton::Forward)),
+        "WheelUp" => Some(Binding::WheelUp),
+        "WheelDown" => Some(Binding::WheelDown),
+        _ => key_from_name(value).map(Binding::Key)
+            .or_else(|| pad_button_from_name(value).map(Binding::PadButton)),
+    }
+}
+
+// inverse of parse_binding; None for inputs without a name that parses back (unusual mouse buttons,
+// pad buttons gilrs couldn't identify), which can't be bound anyway
+pub fn binding_name(binding: Binding) -> Option<String> {
+    let name = match binding {
+        Binding::Key(code) => Some(format!("{:?}", code)),
+        Binding::Mouse(MouseButton::Left) => Some("MouseLeft".to_string()),
+        Binding::Mouse(MouseButton::Right) => Some("MouseRight".to_string()),
+        Binding::Mouse(MouseButton::Middle) => Some("MouseMiddle".to_string()),
+        Binding::Mouse(MouseButton::Back) => Some("MouseBack".to_string()),
+        Binding::Mouse(MouseButton::Forward) => Some("MouseForward".to_string()),
+        Binding::Mouse(MouseButton::Other(_)) => None,
+        Binding::WheelUp => Some("WheelUp".to_string()),
+        Binding::WheelDown => Some("WheelDown".to_string()),
+        Binding::PadButton(button) => Some(format!("Pad{:?}", button)),
+        Binding::PadAxis(axis, positive) => Some(format!("{}Pad{:?}", if positive { '+' } else { '-' }, axis)),
+    };
+    name.filter(|name| parse_binding(name) == Some(binding))
+}
+
+// None for axes gilrs couldn't identify
+pub fn pad_axis_name(axis: GamepadAxis) -> Option<String> {
+    Some(format!("Pad{:?}", axis)).filter(|name| pad_axis_from_name(name) == Some(axis))
+}
+
+fn parse_axis_source(value: &str) -> Option<AxisSource> {
+    match value {
+        "MouseX" => Some(AxisSource::MouseX),
+        "MouseY" => Some(AxisSource::MouseY),
+        "Wheel" => Some(AxisSource::Wheel),
+        _ => {
+            let (sign, name) = split_sign(value).unwrap_or((1.0, value));
+            match pad_axis_from_name(name) {
+                Some(axis) => Some(AxisSource::PadAxis(axis, sign)),
+                // held inputs need an explicit direction
+                None if name.len() < value.len() => parse_binding(name).map(|binding| AxisSource::Held(binding, sign)),
+                None => None,
+            }
+        }
+    }
+}
+
+fn split_sign(value: &str) -> Option<(f32, &str)> {
+    match value.split_at_checked(1)? {
+        ("+", name) => Some((1.0, name)),
+        ("-", name) => Some((-1.0, name)),
+        _ => None,
+    }
+}
+
+// maps "<prefix><Variant>" to the enum variant, e.g. KeyW -> KeyCode::KeyW, PadSouth -> Button::South;
+// `$all` lists the variants for tests
+macro_rules! named_variants {
+    ($vis:vis $function:ident, $all:ident, $ty:ty, $prefix:literal { $($variant:ident),* $(,)? }) => {
+        $vis fn $function(name: &str) -> Option<$ty> {
+            let name = name.strip_prefix($prefix)?;
+            match name {
+                $(stringify!($variant) => Some(<$ty>::$variant),)*
+                _ => None,
+            }
+        }
+
+        #[cfg(test)]
+        pub const $all: &[$ty] = &[$(<$ty>::$variant),*];
+    };
+}
+
+// gamepad inputs use gilrs' names, which follow the position on the pad (South = A on Xbox, Cross on PlayStation)
+named_variants!(pad_button_from_name, PAD_BUTTONS, GamepadButton, "Pad" {
+    South, East, North, West, C, Z,
+    LeftTrigger, LeftTrigger2, RightTrigger, RightTrigger2,
+    Select, Start, Mode, LeftThumb, RightThumb,
+    DPadUp, DPadDown, DPadLeft, DPadRight,
+});
+
+named_variants!(pub pad_axis_from_name, PAD_AXES, GamepadAxis, "Pad" {
+    LeftStickX, LeftStickY, LeftZ, RightStickX, RightStickY, RightZ, DPadX, DPadY,
+});
 
-    pub mouse_delta: (f32, f32),
+// keys are named after winit's physical KeyCode variants (KeyW, ArrowUp, Numpad7, ...); all of them are listed
+// so any key can be bound and recorded
+named_variants!(key_from_name, KEY_CODES, KeyCode, "" {
+    Backquote, Backslash, BracketLeft, BracketRight, Comma, Equal, IntlBackslash, IntlRo, IntlYen,
+    Minus, Period, Quote, Semicolon, Slash,
+    Digit0, Digit1, Digit2, Digit3, Digit4, Digit5, Digit6, Digit7, Digit8, Digit9,
+    KeyA, KeyB, KeyC, KeyD, KeyE, KeyF, KeyG, KeyH, KeyI, KeyJ, KeyK, KeyL, KeyM,
+    KeyN, KeyO, KeyP, KeyQ, KeyR, KeyS, KeyT, KeyU, KeyV, KeyW, KeyX, KeyY, KeyZ,
+    AltLeft, AltRight, Backspace, CapsLock, ContextMenu, ControlLeft, ControlRight, Enter,
+    SuperLeft, SuperRight, ShiftLeft, ShiftRight, Space, Tab,
+    Convert, KanaMode, Lang1, Lang2, Lang3, Lang4, Lang5, NonConvert,
+    Delete, End, Help, Home, Insert, PageDown, PageUp,
+    ArrowDown, ArrowLeft, ArrowRight, ArrowUp,
+    NumLock, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6, Numpad7, Numpad8, Numpad9,
+    NumpadAdd, NumpadBackspace, NumpadClear, NumpadClearEntry, NumpadComma, NumpadDecimal, NumpadDivide,
+    NumpadEnter, NumpadEqual, NumpadHash, NumpadMemoryAdd, NumpadMemoryClear, NumpadMemoryRecall,
+    NumpadMemoryStore, NumpadMemorySubtract, NumpadMultiply, NumpadParenLeft, NumpadParenRight,
+    NumpadStar, NumpadSubtract,
+    Escape, Fn, FnLock, PrintScreen, ScrollLock, Pause,
+    BrowserBack, BrowserFavorites, BrowserForward, BrowserHome, BrowserRefresh, BrowserSearch, BrowserStop,
+    Eject, LaunchApp1, LaunchApp2, LaunchMail, MediaPlayPause, MediaSelect, MediaStop, MediaTrackNext,
+    MediaTrackPrevious, Power, Sleep, AudioVolumeDown, AudioVolumeMute, AudioVolumeUp, WakeUp,
+    Meta, Hyper, Turbo, Abort, Resume, Suspend, Again, Copy, Cut, Find, Open, Paste, Props, Select, Undo,
+    Hiragana, Katakana,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15, F16, F17, F18,
+    F19, F20, F21, F22, F23, F24, F25, F26, F27, F28, F29, F30, F31, F32, F33, F34, F35,
+});
+
+// ignore stick noise near the center, then rescale so output still starts at 0 and reaches 1
+fn apply_deadzone(value: f32, deadzone: f32) -> f32 {
+    let magnitude = value.abs();
+    if magnitude <= deadzone {
+        0.0
+    } else {
+        value.signum() * ((magnitude - deadzone) / (1.0 - deadzone)).min(1.0)
+    }
+}
+
+pub struct Input {
+    bindings: InputBindings,
+    // keys and mouse buttons currently held
+    held: HashSet<Binding>,
+    // inputs pressed since the last take_just_pressed, for one-shot commands
+    just_pressed: Vec<Binding>,
+    mouse_delta: (f32, f32),
+    // accumulated wheel movement in lines (positive = away from the user)
+    scroll_delta: f32,
+    // latest state of each connected pad, merged when read
+    pad_buttons: HashMap<(usize, GamepadButton), f32>,
+    pad_axes: HashMap<(usize, GamepadAxis), f32>,
+    // events handled since the last take_recorded_events, kept only while recording
+    recorded: Option<Vec<InputEvent>>,
+    // set during replays so the live devices can't interfere
+    ignore_live: bool,
 }
 
 impl Input {
-    pub fn new() -> Self {
+    pub fn new(bindings: InputBindings) -> Self {
         Self {
-            is_forward_pressed: false,
-            is_backward_pressed: false,
-            is_left_pressed: false,
-            is_right_pressed: false,
-            is_space_pressed: false,
-            is_shift_pressed: false,
-            is_escape_pressed: false,
+            bindings,
+            held: HashSet::new(),
+            just_pressed: Vec::new(),
             mouse_delta: (0.0, 0.0),
+            scroll_delta: 0.0,
+            pad_buttons: HashMap::new(),
+            pad_axes: HashMap::new(),
+            recorded: None,
+            ignore_live: false,
         }
     }
 
+    pub fn set_bindings(&mut self, bindings: InputBindings) {
+        self.bindings = bindings;
+    }
+
     pub fn handle_mouse_move(&mut self, delta_x: f32, delta_y: f32) {
-        self.mouse_delta.0 += delta_x;
-        self.mouse_delta.1 += delta_y;
+        self.handle_event(InputEvent::MouseMove(delta_x, delta_y));
     }
 
-    pub fn take_mouse_delta(&mut self) -> (f32, f32) {
-        let delta = self.mouse_delta;
-        self.mouse_delta = (0.0, 0.0);
-        delta
+    pub fn handle_mouse_button(&mut self, button: MouseButton, is_pressed: bool) {
+        self.handle_event(InputEvent::MouseButton(button, is_pressed));
+    }
+
+    pub fn handle_scroll(&mut self, lines: f32) {
+        self.handle_event(InputEvent::Scroll(lines));
+    }
+
+    pub fn handle_key(&mut self, code: KeyCode, is_pressed: bool) {
+        self.handle_event(InputEvent::Key(code, is_pressed));
+    }
+
+    pub fn release_all(&mut self) {
+        self.handle_event(InputEvent::ReleaseAll);
+    }
+
+    // entry point for live devices
+    pub fn handle_event(&mut self, event: InputEvent) {
+        if self.ignore_live {
+            return;
+        }
+        if let Some(recorded) = &mut self.recorded {
+            recorded.push(event);
+        }
+        self.apply(event);
     }
 
-    pub fn handle_key(&mut self, code: KeyCode, is_pressed: bool) -> bool {
-        match code {
-            KeyCode::KeyW | KeyCode::ArrowUp => {
-                self.is_forward_pressed = is_pressed;
-                true
+    // feed a recorded event, bypassing ignore_live
+    pub fn replay_event(&mut self, event: InputEvent) {
+        self.apply(event);
+    }
+
+    pub fn set_ignore_live(&mut self, ignore_live: bool) {
+        self.ignore_live = ignore_live;
+    }
+
+    pub fn start_recording(&mut self) {
+        self.recorded = Some(Vec::new());
+    }
+
+    pub fn take_recorded_events(&mut self) -> Vec<InputEvent> {
+        self.recorded.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+
+    fn apply(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::Key(code, is_pressed) => self.handle_binding(Binding::Key(code), is_pressed),
+            InputEvent::MouseButton(button, is_pressed) => self.handle_binding(Binding::Mouse(button), is_pressed),
+            InputEvent::MouseMove(delta_x, delta_y) => {
+                self.mouse_delta.0 += delta_x;
+                self.mouse_delta.1 += delta_y;
+            }
+            InputEvent::Scroll(lines) => {
+                self.scroll_delta += lines;
+                if lines > 0.0 {
+                    self.just_pressed.push(Binding::WheelUp);
+                } else if lines < 0.0 {
+                    self.just_pressed.push(Binding::WheelDown);
+                }
             }
+            InputEvent::Gamepad(pad, event) => self.apply_gamepad_event(pad, event),
+            InputEvent::ReleaseAll => self.held.clear(),
+        }
+    }
 
-            KeyCode::KeyS | KeyCode::ArrowDown => {
-                self.is_backward_pressed = is_pressed;
-                true
+    fn apply_gamepad_event(&mut self, pad: usize, event: GamepadEvent) {
+        match event {
+            GamepadEvent::ButtonPressed(button) => {
+                // analog buttons already reported their travel through ButtonChanged
+                self.pad_buttons.entry((pad, button)).or_insert(1.0);
+                self.just_pressed.push(Binding::PadButton(button));
+            }
+            GamepadEvent::ButtonReleased(button) => {
+                self.pad_buttons.remove(&(pad, button));
             }
-            KeyCode::KeyA | KeyCode::ArrowLeft => {
-                self.is_left_pressed = is_pressed;
-                true
+            GamepadEvent::ButtonChanged(button, value) => {
+                self.pad_buttons.insert((pad, button), value);
             }
-            KeyCode::KeyD | KeyCode::ArrowRight => {
-                self.is_right_pressed = is_pressed;
-                true
+            GamepadEvent::AxisChanged(axis, value) => {
+                self.pad_axes.insert((pad, axis), value);
             }
-            KeyCode::Space => {
-                self.is_space_pressed = is_pressed;
-                true
+            GamepadEvent::Disconnected => {
+                self.pad_buttons.retain(|&(other, _), _| other != pad);
+                self.pad_axes.retain(|&(other, _), _| other != pad);
             }
-            KeyCode::ShiftLeft | KeyCode::ShiftRight => {
-                self.is_shift_pressed = is_pressed;
-                true
+        }
+    }
+
+    fn handle_binding(&mut self, binding: Binding, is_pressed: bool) {
+        if is_pressed {
+            self.held.insert(binding);
+            self.just_pressed.push(binding);
+        } else {
+            self.held.remove(&binding);
+        }
+    }
+
+    // how far the action is pushed, 0..1; keys and buttons are either 0 or 1, sticks and triggers in between
+    pub fn action_value(&self, action: Action) -> f32 {
+        self.bindings.actions.get(&action)
+            .map(|bound| bound.iter().map(|&binding| self.binding_value(binding)).fold(0.0, f32::max))
+            .unwrap_or(0.0)
+    }
+
+    pub fn is_action_pressed(&self, action: Action) -> bool {
+        self.action_value(action) >= PRESS_THRESHOLD
+    }
+
+    fn binding_value(&self, binding: Binding) -> f32 {
+        match binding {
+            Binding::Key(_) | Binding::Mouse(_) if self.held.contains(&binding) => 1.0,
+            // the pad pushing the button furthest wins
+            Binding::PadButton(button) => self.pad_buttons.iter()
+                .filter(|&(&(_, other), _)| other == button)
+                .fold(0.0, |value, (_, &travel)| value.max(travel)),
+            Binding::PadAxis(axis, positive) => {
+                let value = self.pad_axis_value(axis);
+                if positive { value.max(0.0) } else { (-value).max(0.0) }
             }
-            KeyCode::Escape => {
-                self.is_escape_pressed = is_pressed;
-                true
+            _ => 0.0,
+        }
+    }
+
+    fn pad_axis_value(&self, axis: GamepadAxis) -> f32 {
+        // the stick furthest from center wins, so a resting pad doesn't cancel a moving one
+        let value = self.pad_axes.iter()
+            .filter(|&(&(_, other), _)| other == axis)
+            .fold(0.0, |value: f32, (_, &position)| if position.abs() > value.abs() { position } else { value });
+        apply_deadzone(value, self.bindings.gamepad_deadzone)
+    }
+
+    // actions triggered since the last call, in press order
+    pub fn take_just_pressed(&mut self) -> Vec<Action> {
+        std::mem::take(&mut self.just_pressed).into_iter()
+            .flat_map(|binding| self.bindings.actions_for(binding).collect::<Vec<_>>())
+            .collect()
+    }
+
+    // sum of everything feeding the axis this frame; mouse and wheel movement are consumed
+    pub fn take_axis(&mut self, axis: Axis, dt: f32) -> f32 {
+        let Some(sources) = self.bindings.axes.get(&axis) else {
+            return 0.0;
+        };
+        let mut value = 0.0;
+        for source in sources {
+            match *source {
+                AxisSource::MouseX => value += std::mem::take(&mut self.mouse_delta.0),
+                AxisSource::MouseY => value += std::mem::take(&mut self.mouse_delta.1),
+                AxisSource::Wheel => value += std::mem::take(&mut self.scroll_delta),
+                AxisSource::Held(binding, sign) => value += sign * self.binding_value(binding) * axis.held_rate() * dt,
+                AxisSource::PadAxis(pad_axis, sign) => value += sign * self.pad_axis_value(pad_axis) * axis.held_rate() * dt,
             }
-            _ => false,
         }
+        value
+    }
+
+    // drop movement no axis consumed, so it doesn't pile up across frames
+    pub fn end_frame(&mut self) {
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll_delta = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pad(input: &mut Input, event: GamepadEvent) {
+        input.handle_event(InputEvent::Gamepad(0, event));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn stick_deadzone_is_removed_and_rescaled() {
+        let mut input = Input::new(InputBindings::default());
+        pad(&mut input, GamepadEvent::AxisChanged(GamepadAxis::LeftStickY, DEFAULT_GAMEPAD_DEADZONE * 0.5));
+        assert_eq!(input.action_value(Action::MoveForward), 0.0);
+
+        let halfway = DEFAULT_GAMEPAD_DEADZONE + (1.0 - DEFAULT_GAMEPAD_DEADZONE) * 0.5;
+        pad(&mut input, GamepadEvent::AxisChanged(GamepadAxis::LeftStickY, halfway));
+        assert!((input.action_value(Action::MoveForward) - 0.5).abs() < 1e-6);
+
+        pad(&mut input, GamepadEvent::AxisChanged(GamepadAxis::LeftStickY, -1.0));
+        assert_eq!(input.action_value(Action::MoveForward), 0.0);
+        assert_eq!(input.action_value(Action::MoveBackward), 1.0);
+    }
+
+    #[test]
+    fn trigger_presses_action_past_threshold() {
+        let mut input = Input::new(InputBindings::default());
+        pad(&mut input, GamepadEvent::ButtonChanged(GamepadButton::RightTrigger2, PRESS_THRESHOLD - 0.1));
+        assert!(!input.is_action_pressed(Action::MoveUp));
+        pad(&mut input, GamepadEvent::ButtonChanged(GamepadButton::RightTrigger2, PRESS_THRESHOLD + 0.1));
+        assert!(input.is_action_pressed(Action::MoveUp));
+        pad(&mut input, GamepadEvent::ButtonReleased(GamepadButton::RightTrigger2));
+        assert!(!input.is_action_pressed(Action::MoveUp));
+    }
+
+    #[test]
+    fn stick_and_keys_add_up_on_an_axis() {
+        let bindings = InputBindings::parse("gamepad_deadzone: 0\nlook_x: +KeyL -KeyJ PadRightStickX").unwrap();
+        let mut input = Input::new(bindings);
+        input.handle_key(KeyCode::KeyL, true);
+        pad(&mut input, GamepadEvent::AxisChanged(GamepadAxis::RightStickX, 0.5));
+        let value = input.take_axis(Axis::LookX, 0.5);
+        assert!((value - 1.5 * HELD_LOOK_RATE * 0.5).abs() < 1e-3);
+
+        // the stick pushing the other way cancels the key
+        pad(&mut input, GamepadEvent::AxisChanged(GamepadAxis::RightStickX, -1.0));
+        assert!(input.take_axis(Axis::LookX, 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn disconnect_releases_pad_state() {
+        let mut input = Input::new(InputBindings::default());
+        pad(&mut input, GamepadEvent::ButtonPressed(GamepadButton::LeftThumb));
+        pad(&mut input, GamepadEvent::AxisChanged(GamepadAxis::LeftStickX, 1.0));
+        assert!(input.is_action_pressed(Action::Sprint));
+        assert!(input.is_action_pressed(Action::MoveRight));
+
+        pad(&mut input, GamepadEvent::Disconnected);
+        assert!(!input.is_action_pressed(Action::Sprint));
+        assert_eq!(input.action_value(Action::MoveRight), 0.0);
+    }
+
+    #[test]
+    fn pads_merge_and_disconnect_separately() {
+        let mut input = Input::new(InputBindings::default());
+        let second = |input: &mut Input, event| input.handle_event(InputEvent::Gamepad(1, event));
+        pad(&mut input, GamepadEvent::ButtonPressed(GamepadButton::LeftThumb));
+        pad(&mut input, GamepadEvent::AxisChanged(GamepadAxis::LeftStickX, 1.0));
+        second(&mut input, GamepadEvent::AxisChanged(GamepadAxis::LeftStickX, 0.0));
+        second(&mut input, GamepadEvent::ButtonChanged(GamepadButton::RightTrigger2, 1.0));
+        assert!(input.is_action_pressed(Action::Sprint));
+        assert_eq!(input.action_value(Action::MoveRight), 1.0);
+        assert!(input.is_action_pressed(Action::MoveUp));
+
+        // releasing a button on one pad leaves it held on the other
+        pad(&mut input, GamepadEvent::ButtonPressed(GamepadButton::RightTrigger2));
+        pad(&mut input, GamepadEvent::ButtonReleased(GamepadButton::RightTrigger2));
+        assert!(input.is_action_pressed(Action::MoveUp));
+
+        second(&mut input, GamepadEvent::Disconnected);
+        assert!(input.is_action_pressed(Action::Sprint));
+        assert_eq!(input.action_value(Action::MoveRight), 1.0);
+        assert!(!input.is_action_pressed(Action::MoveUp));
+
+        pad(&mut input, GamepadEvent::Disconnected);
+        assert!(!input.is_action_pressed(Action::Sprint));
+        assert_eq!(input.action_value(Action::MoveRight), 0.0);
+    }
+}
@@ -0,0 +1,470 @@
+// small JSON reader and writer for glTF; objects keep their key order
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+// nested arrays/objects deeper than this are rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 256;
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser { bytes: text.as_bytes(), position: 0 };
+        let value = parser.value(0)?;
+        parser.skip_whitespace();
+        if parser.position != parser.bytes.len() {
+            return Err(parser.error("unexpected data after the JSON value"));
+        }
+        Ok(value)
+    }
+
+    // member of an object; None for missing keys and non-objects
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_f32(&self) -> Option<f32> {
+        self.as_f64().map(|number| number as f32)
+    }
+
+    // whole, non-negative numbers that fit; `as` would saturate larger ones to usize::MAX
+    pub fn as_usize(&self) -> Option<usize> {
+        self.as_f64().filter(|number| number.fract() == 0.0 && *number >= 0.0 && *number < usize::MAX as f64).map(|number| number as usize)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    // empty for anything that isn't an array, so optional glTF lists can be iterated directly
+    pub fn items(&self) -> &[Json] {
+        match self {
+            Json::Array(items) => items,
+            _ => &[],
+        }
+    }
+
+    pub fn members(&self) -> &[(String, Json)] {
+        match self {
+            Json::Object(members) => members,
+            _ => &[],
+        }
+    }
+
+    pub fn object<I: IntoIterator<Item = (&'static str, Json)>>(members: I) -> Json {
+        Json::Object(members.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+    }
+
+    pub fn array<T: Into<Json>, I: IntoIterator<Item = T>>(items: I) -> Json {
+        Json::Array(items.into_iter().map(Into::into).collect())
+    }
+
+    // adds a member to an object, e.g. an optional glTF property; ignored for anything else
+    pub fn set(&mut self, key: &str, value: impl Into<Json>) {
+        if let Json::Object(members) = self {
+            members.push((key.to_string(), value.into()));
+        }
+    }
+
+    // fixed-length array of numbers, e.g. a glTF translation or color
+    pub fn as_f32_array<const N: usize>(&self) -> Option<[f32; N]> {
+        let items = self.items();
+        if items.len() != N {
+            return None;
+        }
+        let mut values = [0.0; N];
+        for (value, item) in values.iter_mut().zip(items) {
+            *value = item.as_f32()?;
+        }
+        Some(values)
+    }
+}
+
+// the shortest decimal that reads back as the same f32, so 0.1 isn't written as 0.10000000149011612
+impl From<f32> for Json {
+    fn from(value: f32) -> Json {
+        value.to_string().parse().map_or(Json::Null, Json::Number)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(value: usize) -> Json {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Json {
+        Json::Bool(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Json {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Json {
+        Json::String(value)
+    }
+}
+
+impl<const N: usize> From<[f32; N]> for Json {
+    fn from(values: [f32; N]) -> Json {
+        Json::array(values)
+    }
+}
+
+// compact JSON; numbers that JSON can't hold (NaN, infinity) are written as null
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(number) if number.is_finite() => write!(f, "{}", number),
+            Json::Number(_) => write!(f, "null"),
+            Json::String(string) => write_string(f, string),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, string: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for character in string.chars() {
+        match character {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            control if (control as u32) < 0x20 => write!(f, "\\u{:04x}", control as u32)?,
+            _ => write!(f, "{}", character)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> String {
+        format!("{} at byte {}", message, self.position)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.position) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), String> {
+        if self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", literal)))
+        }
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, String> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("JSON nested too deeply"));
+        }
+        self.skip_whitespace();
+        match self.bytes.get(self.position) {
+            None => Err(self.error("unexpected end of JSON")),
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.position += 1;
+                let mut items = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.position) == Some(&b']') {
+                    self.position += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    self.skip_whitespace();
+                    match self.bytes.get(self.position) {
+                        Some(b',') => self.position += 1,
+                        Some(b']') => {
+                            self.position += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(self.error("expected ',' or ']'")),
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.position += 1;
+                let mut members = Vec::new();
+                self.skip_whitespace();
+                if self.bytes.get(self.position) == Some(&b'}') {
+                    self.position += 1;
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.bytes.get(self.position) != Some(&b'"') {
+                        return Err(self.error("expected a string key"));
+                    }
+                    let key = self.string()?;
+                    self.skip_whitespace();
+                    self.expect(":")?;
+                    members.push((key, self.value(depth + 1)?));
+                    self.skip_whitespace();
+                    match self.bytes.get(self.position) {
+                        Some(b',') => self.position += 1,
+                        Some(b'}') => {
+                            self.position += 1;
+                            return Ok(Json::Object(members));
+                        }
+                        _ => return Err(self.error("expected ',' or '}'")),
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    // -?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?; Rust's parser alone would also take `01`, `1.` and `.5`
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+        let digits = |parser: &mut Self| {
+            let first = parser.position;
+            while let Some(b'0'..=b'9') = parser.bytes.get(parser.position) {
+                parser.position += 1;
+            }
+            parser.position - first
+        };
+        if self.bytes.get(self.position) == Some(&b'-') {
+            self.position += 1;
+        }
+        let leading_zero = self.bytes.get(self.position) == Some(&b'0');
+        let mut valid = match digits(self) {
+            0 => false,
+            count => !leading_zero || count == 1,
+        };
+        if self.bytes.get(self.position) == Some(&b'.') {
+            self.position += 1;
+            valid &= digits(self) > 0;
+        }
+        if let Some(b'e' | b'E') = self.bytes.get(self.position) {
+            self.position += 1;
+            if let Some(b'+' | b'-') = self.bytes.get(self.position) {
+                self.position += 1;
+            }
+            valid &= digits(self) > 0;
+        }
+        // the slice is ASCII, so this can't fail
+        let text = std::str::from_utf8(&self.bytes[start..self.position]).unwrap_or_default();
+        text.parse::<f64>().ok()
+            .filter(|number| valid && number.is_finite())
+            .map(Json::Number)
+            .ok_or_else(|| self.error(&format!("invalid number '{}'", text)))
+    }
+
+    // called on the opening quote
+    fn string(&mut self) -> Result<String, String> {
+        self.position += 1;
+        let mut bytes = Vec::new();
+        loop {
+            let Some(&byte) = self.bytes.get(self.position) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.position += 1;
+            match byte {
+                b'"' => break,
+                b'\\' => {
+                    let Some(&escape) = self.bytes.get(self.position) else {
+                        return Err(self.error("unterminated string"));
+                    };
+                    self.position += 1;
+                    let character = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'/' => '/',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => {
+                            let high = self.hex4()?;
+                            // characters outside the BMP come as a surrogate pair
+                            let code = if (0xd800..0xdc00).contains(&high) {
+                                self.expect("\\u")?;
+                                let low = self.hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(self.error("invalid surrogate pair"));
+                                }
+                                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                            } else {
+                                high
+                            };
+                            char::from_u32(code).ok_or_else(|| self.error("invalid \\u escape"))?
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    bytes.extend_from_slice(character.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                control if control < 0x20 => return Err(self.error("control character in a string")),
+                _ => bytes.push(byte),
+            }
+        }
+        String::from_utf8(bytes).map_err(|_| self.error("string is not valid UTF-8"))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits = self.bytes.get(self.position..self.position + 4).ok_or_else(|| self.error("short \\u escape"))?;
+        let code = std::str::from_utf8(digits).ok()
+            .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+            .ok_or_else(|| self.error("invalid \\u escape"))?;
+        self.position += 4;
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_parse_and_keep_key_order() {
+        let json = Json::parse(r#" {"b": [1, -2.5, 3e2, true, null], "a": {"x": false}, "": "" } "#).unwrap();
+        let keys: Vec<&str> = json.members().iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["b", "a", ""]);
+        assert_eq!(json.get("b").unwrap().items(), [
+            Json::Number(1.0), Json::Number(-2.5), Json::Number(300.0), Json::Bool(true), Json::Null,
+        ]);
+        assert_eq!(json.get("a").and_then(|a| a.get("x")).and_then(Json::as_bool), Some(false));
+        assert_eq!(Json::parse("[]").unwrap(), Json::Array(Vec::new()));
+        assert_eq!(Json::parse("{}").unwrap(), Json::Object(Vec::new()));
+    }
+
+    #[test]
+    fn escapes_and_surrogate_pairs_decode() {
+        let json = Json::parse(r#""\"\\\/\b\f\n\r\t\u0041\u00e9\u20ac""#).unwrap();
+        assert_eq!(json.as_str(), Some("\"\\/\u{8}\u{c}\n\r\tA\u{e9}\u{20ac}"));
+        assert_eq!(Json::parse(r#""\ud83d\ude00""#).unwrap().as_str(), Some("\u{1f600}"));
+        assert_eq!(Json::parse("\"h\u{e9}llo\"").unwrap().as_str(), Some("h\u{e9}llo"));
+
+        for invalid in [
+            r#""\ud83d""#,         // high surrogate with nothing after it
+            r#""\ud83dx""#,        // followed by something other than an escape
+            r#""\ud83d\u0041""#,  // followed by a character that isn't a low surrogate
+            r#""\ude00""#,         // low surrogate on its own
+            r#""\u12""#,
+            r#""\u12g4""#,
+            r#""\x""#,
+            "\"tab\there\"",
+        ] {
+            assert!(Json::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn strings_round_trip_through_display() {
+        let text = "quote \" backslash \\ newline \n bell \u{7} emoji \u{1f600}";
+        let written = Json::from(text).to_string();
+        assert_eq!(Json::parse(&written).unwrap().as_str(), Some(text));
+    }
+
+    #[test]
+    fn numbers_follow_the_json_grammar() {
+        for (text, number) in [("0", 0.0), ("-0", -0.0), ("12", 12.0), ("0.5", 0.5), ("-1.25e-2", -0.0125), ("1E+3", 1000.0), ("2e0", 2.0)] {
+            assert_eq!(Json::parse(text).unwrap().as_f64(), Some(number), "{}", text);
+        }
+        for text in ["01", "-", "1.", ".5", "-.5", "1e", "1e+", "+1", "1.2.3", "--1", "1e400", "NaN", "Infinity", "0x10"] {
+            assert!(Json::parse(text).is_err(), "{}", text);
+        }
+
+        assert_eq!(Json::parse("4294967296").unwrap().as_usize(), Some(1 << 32));
+        assert_eq!(Json::parse("1.5").unwrap().as_usize(), None);
+        assert_eq!(Json::parse("-1").unwrap().as_usize(), None);
+        assert_eq!(Json::parse("1e30").unwrap().as_usize(), None);
+        assert_eq!(Json::from(0.1f32).to_string(), "0.1");
+        assert_eq!(Json::Number(f64::NAN).to_string(), "null");
+    }
+
+    #[test]
+    fn nesting_is_capped() {
+        let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(Json::parse(&nested(MAX_DEPTH + 1)).is_ok());
+        assert!(Json::parse(&nested(MAX_DEPTH + 2)).unwrap_err().contains("nested too deeply"));
+        // far past the cap, the error has to come before the stack runs out
+        assert!(Json::parse(&nested(1_000_000)).is_err());
+        let objects = format!("{}1{}", r#"{"a":"#.repeat(MAX_DEPTH + 2), "}".repeat(MAX_DEPTH + 2));
+        assert!(Json::parse(&objects).is_err());
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        let text = r#"{"asset": {"version": "2.0"}, "nodes": [{"mesh": 0, "name": "a\u00e9"}], "ok": true}"#;
+        assert!(Json::parse(text).is_ok());
+        for end in 0..text.len() {
+            assert!(Json::parse(&text[..end]).is_err(), "{}", &text[..end]);
+        }
+        for invalid in ["", "   ", "nul", "tru", "[1,]", "{\"a\"}", "{\"a\":1,}", "{1: 2}", "[1 2]", "{} {}", "\"open"] {
+            assert!(Json::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+}
@@ -1,25 +1,62 @@
 mod vertex;
 mod renderer;
 mod input;
+mod gamepad;
 mod camera;
+mod camera_path;
+mod pattern;
 mod transform;
 mod object;
+mod mesh_cache;
+mod json;
+mod gltf;
+mod gltf_scene;
+mod gltf_export;
+mod ply;
+mod stl;
+mod normals;
+mod primitive;
 mod scene;
+mod preprocessor;
+mod shader;
 mod material;
 mod texture;
+mod watcher;
+mod replay;
 
+use std::path::PathBuf;
 use std::sync::Arc;
 use winit::{
     application::ApplicationHandler, 
-    event::{WindowEvent, DeviceEvent, DeviceId, MouseButton, ElementState}, 
+    event::{WindowEvent, DeviceEvent, DeviceId, MouseButton, MouseScrollDelta, ElementState}, 
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop}, 
     window::{Window, WindowId, CursorGrabMode}
 };
 
+use input::{Action, Input};
 use renderer::State;
+use scene::Scene;
+
+// touchpads report scrolling in pixels, wheels in lines
+const PIXELS_PER_SCROLL_LINE: f32 = 40.0;
+// set to 1 to play back ARBITRA_REPLAY without a window
+const HEADLESS_ENV: &str = "ARBITRA_HEADLESS";
+// headless runs write every frame as a numbered PNG into this directory
+const CAPTURE_DIR_ENV: &str = "ARBITRA_CAPTURE_DIR";
+// name of a camera path to render offline, frame by frame, instead of opening a window
+const RENDER_PATH_ENV: &str = "ARBITRA_RENDER_PATH";
+// image size for offline path renders, as WIDTHxHEIGHT
+const RENDER_SIZE_ENV: &str = "ARBITRA_RENDER_SIZE";
+const PATH_FPS_ENV: &str = "ARBITRA_PATH_FPS";
+// .gltf or .glb file to export the sample scene to, instead of opening a window
+const EXPORT_ENV: &str = "ARBITRA_EXPORT";
+const DEFAULT_RENDER_SIZE: (u32, u32) = (1280, 720);
+const DEFAULT_PATH_FPS: f32 = 30.0;
+const DEFAULT_CAPTURE_DIR: &str = "frames";
 
 #[derive(Default)]
 struct App {
+    window: Option<Arc<Window>>,
     state: Option<State>,
     is_focused: bool,
     cursor_in_window: bool,
@@ -36,6 +73,7 @@ impl ApplicationHandler for App {
 
         let state = pollster::block_on(State::new(window.clone()));
         self.state = Some(state);
+        self.window = Some(window.clone());
         self.is_focused = true;
         self.cursor_in_window = false;
         self.cursor_grabbed = false;
@@ -44,7 +82,9 @@ impl ApplicationHandler for App {
     }
 
     fn window_event(&mut self, event_loop: &ActiveEventLoop, _id: WindowId, event: WindowEvent) {
-        let state = self.state.as_mut().unwrap();
+        let (Some(state), Some(window)) = (self.state.as_mut(), self.window.as_ref()) else {
+            return;
+        };
         match event {
             WindowEvent::CloseRequested => {
                 event_loop.exit();
@@ -52,8 +92,7 @@ impl ApplicationHandler for App {
             WindowEvent::Focused(focused) => {
                 self.is_focused = focused;
                 if !focused {
-                    let _ = state.window.set_cursor_grab(CursorGrabMode::None);
-                    state.window.set_cursor_visible(true);
+                    release_cursor(window, &mut state.input);
                     self.cursor_grabbed = false;
                 }
             },
@@ -63,18 +102,17 @@ impl ApplicationHandler for App {
             WindowEvent::CursorLeft { .. } => {
                 self.cursor_in_window = false;
                 if self.cursor_grabbed {
-                    let _ = state.window.set_cursor_grab(CursorGrabMode::None);
-                    state.window.set_cursor_visible(true);
+                    release_cursor(window, &mut state.input);
                     self.cursor_grabbed = false;
                 }
             },
-            WindowEvent::MouseInput { state: button_state, button, .. } => {
-                if button == MouseButton::Left && button_state == ElementState::Pressed && self.is_focused && self.cursor_in_window {
-                    let _ = state.window.set_cursor_grab(CursorGrabMode::Confined)
-                        .or_else(|_| state.window.set_cursor_grab(CursorGrabMode::Locked));
-                    state.window.set_cursor_visible(false);
-                    self.cursor_grabbed = true;
-                }
+            WindowEvent::MouseInput { state: button_state, button, .. }
+                if button == MouseButton::Left && button_state == ElementState::Pressed
+                    && !self.cursor_grabbed && self.is_focused && self.cursor_in_window => {
+                let _ = window.set_cursor_grab(CursorGrabMode::Confined)
+                    .or_else(|_| window.set_cursor_grab(CursorGrabMode::Locked));
+                window.set_cursor_visible(false);
+                self.cursor_grabbed = true;
             },
             WindowEvent::RedrawRequested => {
                 state.render();
@@ -82,17 +120,34 @@ impl ApplicationHandler for App {
             WindowEvent::Resized(size) =>{
                 state.resize(size);
             }
+            WindowEvent::MouseInput { state: button_state, button, .. } if self.cursor_grabbed => {
+                state.input.handle_mouse_button(button, button_state.is_pressed());
+                if state.input.is_action_pressed(Action::ReleaseCursor) {
+                    release_cursor(window, &mut state.input);
+                    self.cursor_grabbed = false;
+                }
+            }
+            WindowEvent::MouseWheel { delta, .. } if self.cursor_grabbed => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_SCROLL_LINE,
+                };
+                state.input.handle_scroll(lines);
+            }
             WindowEvent::KeyboardInput { event, .. } => {
                 if !self.cursor_grabbed {
                     return;
                 }
                 use winit::keyboard::PhysicalKey;
+                // held keys are tracked as state, so auto-repeat adds nothing
+                if event.repeat {
+                    return;
+                }
                 if let PhysicalKey::Code(key_code) = event.physical_key {
                     let is_pressed = event.state.is_pressed();
                     state.input.handle_key(key_code, is_pressed);
-                    if state.input.is_escape_pressed {
-                        let _ = state.window.set_cursor_grab(CursorGrabMode::None);
-                        state.window.set_cursor_visible(true);
+                    if state.input.is_action_pressed(Action::ReleaseCursor) {
+                        release_cursor(window, &mut state.input);
                         self.cursor_grabbed = false;
                     }
                 }
@@ -106,19 +161,111 @@ impl ApplicationHandler for App {
             return;
         }
         
-        if let Some(state) = self.state.as_mut() {
-            match event {
-                DeviceEvent::MouseMotion { delta } => {
-                    state.input.handle_mouse_move(delta.0 as f32, delta.1 as f32);
-                }
-                _ => {}
-            }
+        if let Some(state) = self.state.as_mut()
+            && let DeviceEvent::MouseMotion { delta } = event {
+            state.input.handle_mouse_move(delta.0 as f32, delta.1 as f32);
+        }
+    }
+}
+
+// give the cursor back; held inputs are dropped since their release events won't arrive
+fn release_cursor(window: &Window, input: &mut Input) {
+    let _ = window.set_cursor_grab(CursorGrabMode::None);
+    window.set_cursor_visible(true);
+    input.release_all();
+}
+
+// replay a recording as fast as possible, optionally saving every frame
+fn run_headless() -> Result<(), String> {
+    let mut state = pollster::block_on(State::new_headless())?;
+    let capture_dir = std::env::var_os(CAPTURE_DIR_ENV).map(PathBuf::from);
+    if let Some(dir) = &capture_dir {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create capture directory '{}': {}", dir.display(), e))?;
+    }
+
+    let mut frame = 0;
+    while state.is_replaying() {
+        state.render();
+        if let Some(dir) = &capture_dir {
+            state.save_frame(&dir.join(format!("frame_{:05}.png", frame)))?;
         }
+        frame += 1;
     }
+    Ok(())
+}
+
+fn render_camera_path(name: &str) -> Result<(), String> {
+    let (width, height) = match std::env::var(RENDER_SIZE_ENV) {
+        Ok(value) => value.split_once('x')
+            .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+            .filter(|&(width, height)| width > 0 && height > 0)
+            .ok_or_else(|| format!("Invalid {} '{}', expected e.g. 1920x1080", RENDER_SIZE_ENV, value))?,
+        Err(_) => DEFAULT_RENDER_SIZE,
+    };
+    let frames_per_second = match std::env::var(PATH_FPS_ENV) {
+        Ok(value) => value.parse().ok()
+            .filter(|fps: &f32| *fps > 0.0)
+            .ok_or_else(|| format!("Invalid {} '{}'", PATH_FPS_ENV, value))?,
+        Err(_) => DEFAULT_PATH_FPS,
+    };
+    let capture_dir = std::env::var_os(CAPTURE_DIR_ENV).map_or_else(|| PathBuf::from(DEFAULT_CAPTURE_DIR), PathBuf::from);
+    std::fs::create_dir_all(&capture_dir)
+        .map_err(|e| format!("Failed to create capture directory '{}': {}", capture_dir.display(), e))?;
+
+    let mut state = pollster::block_on(State::new_offscreen(width, height));
+    let frame_count = state.render_camera_path(name, frames_per_second)?;
+    println!("Rendering camera path '{}': {} frames at {}x{}, {} fps", name, frame_count, width, height, frames_per_second);
+
+    let mut frame = 0;
+    while state.is_playing_camera_path() {
+        state.render();
+        state.save_frame(&capture_dir.join(format!("frame_{:05}.png", frame)))?;
+        frame += 1;
+    }
+    println!("Wrote {} frames to {}", frame, capture_dir.display());
+    Ok(())
+}
+
+// export the scene, then load the file back and check nothing glTF can hold was lost
+fn export_scene(path: &str) -> Result<(), String> {
+    let scene = Scene::load_from_arsc(renderer::DEFAULT_SCENE, renderer::ASSETS_ROOT)
+        .map_err(|e| format!("Failed to load scene '{}': {}", renderer::DEFAULT_SCENE, e))?;
+    scene.export_gltf(path, renderer::ASSETS_ROOT)?;
+
+    let imported = Scene::load_from_gltf(path, renderer::ASSETS_ROOT)
+        .map_err(|e| format!("Failed to load the exported file back: {}", e))?;
+    let differences = gltf_export::compare_round_trip(&scene, &imported, renderer::ASSETS_ROOT);
+    if !differences.is_empty() {
+        return Err(format!("The exported file doesn't match the scene:\n  - {}", differences.join("\n  - ")));
+    }
+    println!("Loaded '{}' back: it matches the scene", path);
+    Ok(())
 }
 
 fn main() {
     env_logger::init();
+    if let Ok(path) = std::env::var(EXPORT_ENV) {
+        if let Err(e) = export_scene(&path) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Ok(name) = std::env::var(RENDER_PATH_ENV) {
+        if let Err(e) = render_camera_path(&name) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if std::env::var(HEADLESS_ENV).is_ok_and(|value| value == "1" || value == "true") {
+        if let Err(e) = run_headless() {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
     let mut app = App::default();
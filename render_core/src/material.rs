@@ -1,15 +1,50 @@
 use anyhow::{Context, Result};
 use std::fs;
 
+/// How the mesh's per-vertex colors combine with the albedo texture
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VertexColorMode {
+    #[default]
+    Ignore,
+    Multiply,
+    // vertex color alone; the texture only supplies alpha for ALPHA_MASK
+    Replace,
+}
+
+impl VertexColorMode {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ignore" => Some(VertexColorMode::Ignore),
+            "multiply" => Some(VertexColorMode::Multiply),
+            "replace" => Some(VertexColorMode::Replace),
+            _ => None,
+        }
+    }
+
+    // matches the VERTEX_COLOR_* constants in structs.wgsl
+    pub fn shader_value(self) -> u32 {
+        match self {
+            VertexColorMode::Ignore => 0,
+            VertexColorMode::Multiply => 1,
+            VertexColorMode::Replace => 2,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Material {
     #[allow(dead_code)]
     pub name: String,
     pub albedo_texture: String,
+    // rgba factor the albedo texture is multiplied by
+    pub base_color: [f32; 4],
     #[allow(dead_code)]
     pub roughness: f32,
     #[allow(dead_code)]
     pub metallic: f32,
+    pub vertex_color: VertexColorMode,
+    // .armat file this material was loaded from, used by hot reload
+    pub source_file: Option<String>,
 }
 
 impl Material {
@@ -19,8 +54,10 @@ impl Material {
 
         let mut name = String::from("Unnamed");
         let mut albedo_texture = String::from("textures/white.png");
+        let mut base_color = [1.0; 4];
         let mut roughness = 0.5;
         let mut metallic = 0.0;
+        let mut vertex_color = VertexColorMode::default();
 
         for line in content.lines() {
             let line = line.trim();
@@ -32,20 +69,35 @@ impl Material {
                 name = value.trim().to_string();
             } else if let Some(value) = line.strip_prefix("albedo_texture: ") {
                 albedo_texture = value.trim().to_string();
+            } else if let Some(value) = line.strip_prefix("base_color: ") {
+                // alpha is optional
+                let values: Vec<f32> = value.split_whitespace().map(str::parse).collect::<Result<_, _>>()
+                    .with_context(|| format!("Invalid base_color value in {}", path))?;
+                base_color = match values[..] {
+                    [r, g, b] => [r, g, b, 1.0],
+                    [r, g, b, a] => [r, g, b, a],
+                    _ => anyhow::bail!("base_color in {} needs r g b or r g b a", path),
+                };
             } else if let Some(value) = line.strip_prefix("roughness: ") {
                 roughness = value.trim().parse()
                     .with_context(|| format!("Invalid roughness value in {}", path))?;
             } else if let Some(value) = line.strip_prefix("metallic: ") {
                 metallic = value.trim().parse()
                     .with_context(|| format!("Invalid metallic value in {}", path))?;
+            } else if let Some(value) = line.strip_prefix("vertex_color: ") {
+                vertex_color = VertexColorMode::from_name(value.trim())
+                    .with_context(|| format!("Invalid vertex_color '{}' in {}, expected ignore, multiply or replace", value.trim(), path))?;
             }
         }
 
         Ok(Material {
             name,
             albedo_texture,
+            base_color,
             roughness,
             metallic,
+            vertex_color,
+            source_file: Some(path.to_string()),
         })
     }
 
@@ -53,8 +105,11 @@ impl Material {
         Material {
             name: String::from("Default"),
             albedo_texture: String::from("textures/white.png"),
+            base_color: [1.0; 4],
             roughness: 0.5,
             metallic: 0.0,
+            vertex_color: VertexColorMode::default(),
+            source_file: None,
         }
     }
 }
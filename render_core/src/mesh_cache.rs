@@ -0,0 +1,260 @@
+// compiled mesh cache: model files that are slow to parse are stored, after normals are generated,
+// as .armesh files that load with a single copy. A cache file is used only while its header matches
+// the current format, vertex layout, source file contents and normal settings
+//
+// layout: a little-endian header of HEADER_SIZE bytes, then the vertices as `Vertex` structs are laid out
+// in memory, then the indices at the header's index width
+use std::fs;
+use std::mem::{offset_of, size_of};
+use std::path::{Component, Path, PathBuf};
+
+use crate::object::MeshData;
+use crate::vertex::Vertex;
+
+const MAGIC: &[u8; 8] = b"ARMESH\0\0";
+// bump whenever a loader or the normal generation changes what it produces, so old caches are rebuilt
+const CACHE_VERSION: u32 = 2;
+const HEADER_SIZE: usize = 104;
+const CACHE_EXTENSION: &str = "armesh";
+// cache folder, or 0 / off to always parse the model files
+const CACHE_DIR_ENV: &str = "ARBITRA_MESH_CACHE";
+const DEFAULT_CACHE_DIR: &str = "assets/.cache";
+// glTF is binary already and comes with a material, so only these are cached
+const CACHED_EXTENSIONS: [&str; 3] = ["obj", "ply", "stl"];
+// (byte offset, float count) of position, color, normal and uv
+const VERTEX_LAYOUT: [(usize, usize); 4] = [
+    (offset_of!(Vertex, position), 3),
+    (offset_of!(Vertex, color), 3),
+    (offset_of!(Vertex, normal), 3),
+    (offset_of!(Vertex, uv), 2),
+];
+// 64-bit FNV-1a
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Whether meshes of this file's format go through the cache, whether or not the cache is turned on
+pub fn is_cached_format(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| CACHED_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+}
+
+/// The cached mesh for `mesh_file` (relative to the assets folder, found at `path`), built from the
+/// file contents `source` with `settings` (the .arobj's normal mode); None when there is no cache file
+/// or it is out of date
+pub fn load(path: &Path, mesh_file: &str, source: &[u8], settings: &str) -> Option<MeshData> {
+    load_in(&cache_dir()?, path, mesh_file, source, settings)
+}
+
+/// Write `mesh`, parsed from the file contents `source`, to the cache for the next launch;
+/// failures are reported and otherwise ignored
+pub fn store(path: &Path, mesh_file: &str, source: &[u8], settings: &str, mesh: &MeshData) {
+    if let Some(dir) = cache_dir() {
+        store_in(&dir, path, mesh_file, source, settings, mesh);
+    }
+}
+
+// None when caching is turned off
+fn cache_dir() -> Option<PathBuf> {
+    match std::env::var(CACHE_DIR_ENV) {
+        Ok(value) if value == "0" || value.eq_ignore_ascii_case("off") => None,
+        Ok(value) => Some(PathBuf::from(value)),
+        Err(_) => Some(PathBuf::from(DEFAULT_CACHE_DIR)),
+    }
+}
+
+fn load_in(dir: &Path, path: &Path, mesh_file: &str, source: &[u8], settings: &str) -> Option<MeshData> {
+    let bytes = fs::read(cache_path(dir, path, mesh_file)?).ok()?;
+    match read(&bytes, hash(source), hash(settings.as_bytes())) {
+        Ok(mesh) => Some(mesh),
+        Err(reason) => {
+            println!("Rebuilding the mesh cache for '{}': {}", mesh_file, reason);
+            None
+        }
+    }
+}
+
+fn store_in(dir: &Path, path: &Path, mesh_file: &str, source: &[u8], settings: &str, mesh: &MeshData) {
+    let Some(cache_path) = cache_path(dir, path, mesh_file) else {
+        return;
+    };
+    if let Err(e) = write(&cache_path, mesh, hash(source), hash(settings.as_bytes())) {
+        eprintln!("Failed to write the mesh cache '{}': {}", cache_path.display(), e);
+    }
+}
+
+// None when the format isn't cached or the file is outside the assets folder
+fn cache_path(dir: &Path, path: &Path, mesh_file: &str) -> Option<PathBuf> {
+    if !is_cached_format(path) {
+        return None;
+    }
+    let relative = Path::new(mesh_file);
+    if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+        return None;
+    }
+    Some(dir.join(format!("{}.{}", mesh_file, CACHE_EXTENSION)))
+}
+
+fn read(bytes: &[u8], source_hash: u64, settings_hash: u64) -> Result<MeshData, String> {
+    if bytes.len() < HEADER_SIZE || &bytes[..8] != MAGIC {
+        return Err("not a mesh cache file".to_string());
+    }
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let u64_at = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+    if u32_at(8) != CACHE_VERSION {
+        return Err(format!("written by cache version {}, this is {}", u32_at(8), CACHE_VERSION));
+    }
+    let layout_matches = u32_at(12) as usize == size_of::<Vertex>()
+        && u32_at(16) as usize == VERTEX_LAYOUT.len()
+        && VERTEX_LAYOUT.iter().enumerate()
+            .all(|(attribute, &(offset, floats))| u32_at(20 + attribute * 8) as usize == offset && u32_at(24 + attribute * 8) as usize == floats);
+    if !layout_matches {
+        return Err("the vertex layout changed".to_string());
+    }
+    if u64_at(88) != source_hash {
+        return Err("the model file changed".to_string());
+    }
+    if u64_at(96) != settings_hash {
+        return Err("the normal settings changed".to_string());
+    }
+
+    let index_width = u32_at(52) as usize;
+    let vertex_count = u32_at(56) as usize;
+    let index_count = u32_at(60) as usize;
+    let vertices_end = HEADER_SIZE + vertex_count * size_of::<Vertex>();
+    if !matches!(index_width, 2 | 4) || bytes.len() != vertices_end + index_count * index_width {
+        return Err("the file is damaged".to_string());
+    }
+
+    // a Vec<u8> has no particular alignment, so the vertices are copied out rather than cast in place
+    let vertices = bytemuck::pod_collect_to_vec(&bytes[HEADER_SIZE..vertices_end]);
+    let indices: Vec<u32> = match index_width {
+        2 => bytes[vertices_end..].chunks_exact(2).map(|index| u16::from_le_bytes([index[0], index[1]]) as u32).collect(),
+        _ => bytes[vertices_end..].chunks_exact(4).map(|index| u32::from_le_bytes(index.try_into().unwrap())).collect(),
+    };
+    // meshes are indexed on the CPU too (surface scatter, export), so a bad index must not get through
+    if indices.iter().any(|&index| index as usize >= vertex_count) {
+        return Err("the file is damaged".to_string());
+    }
+    Ok(MeshData { vertices, indices, ..MeshData::new() })
+}
+
+fn write(cache_path: &Path, mesh: &MeshData, source_hash: u64, settings_hash: u64) -> Result<(), String> {
+    let index_width: usize = if mesh.vertices.len() <= u16::MAX as usize + 1 { 2 } else { 4 };
+    let (min, max) = bounds(&mesh.vertices);
+
+    let mut bytes = Vec::with_capacity(HEADER_SIZE + mesh.vertices.len() * size_of::<Vertex>() + mesh.indices.len() * index_width);
+    bytes.extend_from_slice(MAGIC);
+    let push_u32 = |bytes: &mut Vec<u8>, value: usize| bytes.extend_from_slice(&(value as u32).to_le_bytes());
+    push_u32(&mut bytes, CACHE_VERSION as usize);
+    push_u32(&mut bytes, size_of::<Vertex>());
+    push_u32(&mut bytes, VERTEX_LAYOUT.len());
+    for (offset, floats) in VERTEX_LAYOUT {
+        push_u32(&mut bytes, offset);
+        push_u32(&mut bytes, floats);
+    }
+    push_u32(&mut bytes, index_width);
+    push_u32(&mut bytes, mesh.vertices.len());
+    push_u32(&mut bytes, mesh.indices.len());
+    for value in min.into_iter().chain(max) {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&source_hash.to_le_bytes());
+    bytes.extend_from_slice(&settings_hash.to_le_bytes());
+    debug_assert_eq!(bytes.len(), HEADER_SIZE);
+
+    bytes.extend_from_slice(bytemuck::cast_slice(&mesh.vertices));
+    for &index in &mesh.indices {
+        match index_width {
+            2 => bytes.extend_from_slice(&(index as u16).to_le_bytes()),
+            _ => bytes.extend_from_slice(&index.to_le_bytes()),
+        }
+    }
+
+    // written beside the cache file and renamed over it, so a running engine never maps half a file
+    if let Some(dir) = cache_path.parent() {
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    let temporary = cache_path.with_extension(format!("{}.tmp", CACHE_EXTENSION));
+    fs::write(&temporary, &bytes).map_err(|e| e.to_string())?;
+    fs::rename(&temporary, cache_path).map_err(|e| e.to_string())
+}
+
+fn bounds(vertices: &[Vertex]) -> ([f32; 3], [f32; 3]) {
+    if vertices.is_empty() {
+        return ([0.0; 3], [0.0; 3]);
+    }
+    vertices.iter().fold(([f32::MAX; 3], [f32::MIN; 3]), |(min, max), vertex| {
+        (
+            std::array::from_fn(|axis| min[axis].min(vertex.position[axis])),
+            std::array::from_fn(|axis| max[axis].max(vertex.position[axis])),
+        )
+    })
+}
+
+fn hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET, |hash, &byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_mesh() -> MeshData {
+        let vertex = |position: [f32; 3], uv: [f32; 2]| Vertex { position, color: [1.0, 0.5, 0.25], normal: [0.0, 0.0, 1.0], uv };
+        MeshData {
+            vertices: vec![vertex([0.0, 0.0, 0.0], [0.0, 0.0]), vertex([1.0, 0.0, 0.0], [1.0, 0.0]), vertex([0.0, 2.0, -1.0], [0.0, 1.0])],
+            indices: vec![0, 1, 2],
+            ..MeshData::new()
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("arbitra_{}_{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn stored_meshes_load_back_until_the_source_changes() {
+        let dir = temp_dir("mesh_cache");
+        let (path, source, mesh) = (Path::new("assets/models/tri.obj"), b"v 0 0 0".as_slice(), test_mesh());
+        store_in(&dir, path, "models/tri.obj", source, "None", &mesh);
+
+        let loaded = load_in(&dir, path, "models/tri.obj", source, "None").expect("the cache was not used");
+        assert_eq!(bytemuck::cast_slice::<Vertex, u8>(&loaded.vertices), bytemuck::cast_slice::<Vertex, u8>(&mesh.vertices));
+        assert_eq!(loaded.indices, mesh.indices);
+        assert!(load_in(&dir, path, "models/tri.obj", b"v 0 0 1", "None").is_none());
+        assert!(load_in(&dir, path, "models/tri.obj", source, "Some(Flat)").is_none());
+        // glTF isn't cached, and paths leaving the assets folder aren't either
+        assert!(cache_path(&dir, Path::new("a.gltf"), "a.gltf").is_none());
+        assert!(cache_path(&dir, Path::new("../a.obj"), "../a.obj").is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn damaged_cache_files_are_rejected() {
+        let dir = temp_dir("mesh_cache_damaged");
+        let cache_path = dir.join("tri.obj.armesh");
+        write(&cache_path, &test_mesh(), 1, 2).unwrap();
+        let good = fs::read(&cache_path).unwrap();
+        assert!(read(&good, 1, 2).is_ok());
+
+        let damaged = |change: &dyn Fn(&mut Vec<u8>)| {
+            let mut bytes = good.clone();
+            change(&mut bytes);
+            read(&bytes, 1, 2)
+        };
+        assert!(damaged(&|bytes| bytes[0] = b'X').is_err(), "magic");
+        assert!(damaged(&|bytes| bytes[8] += 1).is_err(), "version");
+        assert!(damaged(&|bytes| bytes[12] += 4).is_err(), "vertex size");
+        assert!(damaged(&|bytes| bytes[56] += 1).is_err(), "vertex count");
+        assert!(damaged(&|bytes| bytes.truncate(HEADER_SIZE - 1)).is_err(), "short header");
+        assert!(damaged(&|bytes| bytes.truncate(bytes.len() - 1)).is_err(), "truncated indices");
+        // an index past the last vertex
+        assert!(damaged(&|bytes| { let last = bytes.len() - 2; bytes[last] = 3; }).is_err(), "index out of range");
+        assert!(read(&good, 9, 2).is_err() && read(&good, 1, 9).is_err(), "hashes");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::object::{ObjectGeometry, MAX_VERTICES};
+use crate::vertex::Vertex;
+
+// used for OBJs without normals when the .arobj doesn't pick a mode
+const DEFAULT_CREASE_ANGLE: f32 = 60.0;
+// corners closer than this (in model units) count as one point, so rounding noise at seams doesn't split them;
+// it is also the size of the grid cells used to find them, so close corners are at most one cell apart
+const WELD_DISTANCE: f32 = 1e-5;
+
+/// How vertex normals are computed from the triangles of a mesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalMode {
+    // every triangle gets its own vertices, facing the way the triangle does
+    Flat,
+    // faces meeting at a vertex are averaged; with a crease angle in degrees, only faces
+    // that differ from each other by at most that much, so hard edges stay sharp
+    Smooth { weighting: Weighting, crease: Option<f32> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Weighting {
+    // by the angle of the triangle's corner at the vertex; long thin triangles don't dominate
+    Angle,
+    // by triangle area
+    Area,
+}
+
+impl Default for NormalMode {
+    fn default() -> Self {
+        NormalMode::Smooth { weighting: Weighting::Angle, crease: Some(DEFAULT_CREASE_ANGLE) }
+    }
+}
+
+impl NormalMode {
+    // `flat` or `smooth [angle|area] [crease degrees]`, the words after `normals:`
+    pub fn parse(words: &[&str]) -> Result<Self, String> {
+        match words.split_first() {
+            Some((&"flat", [])) => Ok(NormalMode::Flat),
+            Some((&"smooth", options)) => {
+                let mut weighting = Weighting::Angle;
+                let mut crease = None;
+                for &option in options {
+                    match option {
+                        "angle" => weighting = Weighting::Angle,
+                        "area" => weighting = Weighting::Area,
+                        _ => crease = Some(option.parse::<f32>().ok()
+                            .filter(|angle| (0.0..=180.0).contains(angle))
+                            .ok_or_else(|| format!("invalid smooth option '{}', expected angle, area or a crease angle from 0 to 180", option))?),
+                    }
+                }
+                Ok(NormalMode::Smooth { weighting, crease })
+            }
+            _ => Err(format!("invalid normals '{}', expected flat or smooth [angle|area] [crease degrees]", words.join(" "))),
+        }
+    }
+}
+
+impl ObjectGeometry {
+    /// Replace the vertex normals with ones computed from the triangles; vertices are split where
+    /// one needs different normals on different faces
+    pub fn generate_normals(&mut self, mode: NormalMode) -> Result<(), String> {
+        let indices: Vec<u32> = self.indices.iter().map(|&index| index as u32).collect();
+        let (vertices, indices) = generate_normals(&self.vertices, &indices, mode);
+        if vertices.len() > MAX_VERTICES {
+            return Err(format!("'{}' needs more than {} vertices once its normals are split", self.name, MAX_VERTICES));
+        }
+        self.vertices = vertices;
+        self.indices = indices.into_iter().map(|index| index as u16).collect();
+        Ok(())
+    }
+}
+
+// mesh with the normals computed for `mode`, for meshes that aren't an ObjectGeometry yet
+pub fn generate_normals(source: &[Vertex], source_indices: &[u32], mode: NormalMode) -> (Vec<Vertex>, Vec<u32>) {
+    let triangles: Vec<[usize; 3]> = source_indices.chunks_exact(3)
+        .map(|triangle| [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize])
+        .collect();
+    let position = |index: usize| Vector3::from(source[index].position);
+    // length is twice the area, so summing them weighs by area
+    let face_normals: Vec<Vector3<f32>> = triangles.iter()
+        .map(|&[a, b, c]| (position(b) - position(a)).cross(position(c) - position(a)))
+        .collect();
+
+    // normal for every triangle corner
+    let corner_normals: Vec<Vector3<f32>> = match mode {
+        NormalMode::Flat => face_normals.iter().flat_map(|&normal| [normal; 3]).collect(),
+        NormalMode::Smooth { weighting, crease } => {
+            let weighted = |triangle: usize, corner: usize| {
+                let normal = face_normals[triangle];
+                match weighting {
+                    Weighting::Area => normal,
+                    Weighting::Angle => {
+                        let vertices = triangles[triangle].map(position);
+                        let (here, next, previous) = (vertices[corner], vertices[(corner + 1) % 3], vertices[(corner + 2) % 3]);
+                        let (to_next, to_previous) = (next - here, previous - here);
+                        if normal.magnitude2() == 0.0 || to_next.magnitude2() == 0.0 || to_previous.magnitude2() == 0.0 {
+                            return Vector3::new(0.0, 0.0, 0.0);
+                        }
+                        normal.normalize() * to_next.angle(to_previous).0
+                    }
+                }
+            };
+
+            // corners at the same position share normals even when the OBJ split them for UVs
+            let cell = |point: Vector3<f32>| [point.x, point.y, point.z].map(|coordinate| (coordinate / WELD_DISTANCE).floor() as i64);
+            let mut by_cell: HashMap<[i64; 3], Vec<(usize, usize)>> = HashMap::new();
+            for (triangle, indices) in triangles.iter().enumerate() {
+                for (corner, &index) in indices.iter().enumerate() {
+                    by_cell.entry(cell(position(index))).or_default().push((triangle, corner));
+                }
+            }
+            let min_cos = crease.map(|degrees| degrees.to_radians().cos());
+
+            let mut corner_normals = vec![Vector3::new(0.0, 0.0, 0.0); triangles.len() * 3];
+            for (triangle, indices) in triangles.iter().enumerate() {
+                for (corner, &index) in indices.iter().enumerate() {
+                    let point = position(index);
+                    let [x, y, z] = cell(point);
+                    let own = face_normals[triangle];
+                    // two points that close can still straddle a cell boundary, so the neighbouring cells are searched too
+                    let nearby = (-1..=1).flat_map(|dx| (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| [x + dx, y + dy, z + dz])))
+                        .filter_map(|neighbour| by_cell.get(&neighbour))
+                        .flatten();
+                    corner_normals[triangle * 3 + corner] = nearby
+                        .filter(|&&(other, other_corner)| (position(triangles[other][other_corner]) - point).magnitude() <= WELD_DISTANCE)
+                        .filter(|&&(other, _)| match min_cos {
+                            None => true,
+                            Some(min_cos) => other == triangle || cos_between(own, face_normals[other]) >= min_cos,
+                        })
+                        .map(|&(other, other_corner)| weighted(other, other_corner))
+                        .sum();
+                }
+            }
+            corner_normals
+        }
+    };
+
+    // reuse a vertex for every corner that ended up with the same normal
+    let mut vertices = Vec::with_capacity(source.len());
+    let mut indices = Vec::with_capacity(source_indices.len());
+    let mut shared: HashMap<(usize, [u32; 3]), u32> = HashMap::new();
+    for (triangle, corners) in triangles.iter().enumerate() {
+        for (corner, &index) in corners.iter().enumerate() {
+            let normal = corner_normals[triangle * 3 + corner];
+            let normal: [f32; 3] = if normal.magnitude2() > 0.0 {
+                normal.normalize().into()
+            } else {
+                // degenerate triangle with nothing to average; the vertex is invisible anyway
+                [0.0, 1.0, 0.0]
+            };
+            let key = match mode {
+                NormalMode::Flat => (triangle * 3 + corner, [0; 3]),
+                NormalMode::Smooth { .. } => (index, normal.map(f32::to_bits)),
+            };
+            let shared_index = *shared.entry(key).or_insert_with(|| {
+                vertices.push(Vertex { normal, ..source[index] });
+                vertices.len() as u32 - 1
+            });
+            indices.push(shared_index);
+        }
+    }
+    (vertices, indices)
+}
+
+// cosine of the angle between two face normals; zero-area faces count as matching everything
+fn cos_between(a: Vector3<f32>, b: Vector3<f32>) -> f32 {
+    if a.magnitude2() == 0.0 || b.magnitude2() == 0.0 {
+        return 1.0;
+    }
+    a.normalize().dot(b.normalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vertex(position: [f32; 3]) -> Vertex {
+        Vertex { position, color: [1.0; 3], normal: [0.0; 3], uv: [0.0; 2] }
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-4), "{:?} != {:?}", a, b);
+    }
+
+    // corners at the origin: a big triangle facing +z and a small one facing +x, both with a 90 degree corner there
+    fn two_faces() -> (Vec<Vertex>, Vec<u32>) {
+        let positions = [[0.0, 0.0, 0.0], [10.0, 0.0, 0.0], [0.0, 10.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+        (positions.map(vertex).to_vec(), vec![0, 1, 2, 0, 3, 4])
+    }
+
+    fn normal_at_origin(vertices: &[Vertex], indices: &[u32], corner: usize) -> [f32; 3] {
+        vertices[indices[corner] as usize].normal
+    }
+
+    // unit cube with outward-facing triangles
+    fn cube() -> (Vec<Vertex>, Vec<u32>) {
+        let vertices = (0..8).map(|i| vertex([(i & 1) as f32, (i >> 1 & 1) as f32, (i >> 2 & 1) as f32])).collect();
+        let quads = [[0, 2, 3, 1], [4, 5, 7, 6], [0, 1, 5, 4], [2, 6, 7, 3], [0, 4, 6, 2], [1, 3, 7, 5]];
+        let indices = quads.iter().flat_map(|&[a, b, c, d]| [a, b, c, a, c, d]).collect();
+        (vertices, indices)
+    }
+
+    #[test]
+    fn flat_normals_give_every_triangle_its_own_vertices() {
+        let (vertices, indices) = cube();
+        let (vertices, indices) = generate_normals(&vertices, &indices, NormalMode::Flat);
+        assert_eq!(vertices.len(), 36);
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| Vector3::from(vertices[triangle[corner] as usize].position));
+            let face: [f32; 3] = (b - a).cross(c - a).normalize().into();
+            for &index in triangle {
+                assert_close(vertices[index as usize].normal, face);
+            }
+        }
+    }
+
+    #[test]
+    fn cube_creases_keep_the_faces_flat() {
+        let (source, source_indices) = cube();
+        let crease = NormalMode::Smooth { weighting: Weighting::Angle, crease: Some(30.0) };
+        let (vertices, indices) = generate_normals(&source, &source_indices, crease);
+        // one vertex per corner of every face
+        assert_eq!(vertices.len(), 24);
+        let (flat, _) = generate_normals(&source, &source_indices, NormalMode::Flat);
+        for (corner, &index) in indices.iter().enumerate() {
+            assert_close(vertices[index as usize].normal, flat[corner].normal);
+        }
+
+        // without a crease every cube corner points away from the center
+        let smooth = NormalMode::Smooth { weighting: Weighting::Angle, crease: None };
+        let (vertices, _) = generate_normals(&source, &source_indices, smooth);
+        assert_eq!(vertices.len(), 8);
+        for vertex in &vertices {
+            let outward: [f32; 3] = (Vector3::from(vertex.position) - Vector3::new(0.5, 0.5, 0.5)).normalize().into();
+            assert_close(vertex.normal, outward);
+        }
+    }
+
+    #[test]
+    fn smooth_sphere_normals_point_away_from_the_center() {
+        let sphere = ObjectGeometry::uv_sphere(32, 16).unwrap();
+        let indices: Vec<u32> = sphere.indices.iter().map(|&index| index as u32).collect();
+        for weighting in [Weighting::Angle, Weighting::Area] {
+            let (vertices, _) = generate_normals(&sphere.vertices, &indices, NormalMode::Smooth { weighting, crease: None });
+            for vertex in &vertices {
+                let outward = Vector3::from(vertex.position).normalize();
+                assert!(Vector3::from(vertex.normal).dot(outward) > 0.99, "{:?} at {:?}", vertex.normal, vertex.position);
+            }
+        }
+    }
+
+    #[test]
+    fn weighting_and_crease_change_shared_corners() {
+        let (vertices, indices) = two_faces();
+        let diagonal = [std::f32::consts::FRAC_1_SQRT_2, 0.0, std::f32::consts::FRAC_1_SQRT_2];
+
+        let angle = NormalMode::Smooth { weighting: Weighting::Angle, crease: None };
+        let (result, result_indices) = generate_normals(&vertices, &indices, angle);
+        assert_close(normal_at_origin(&result, &result_indices, 0), diagonal);
+        assert_eq!(result_indices[0], result_indices[3]);
+
+        // the big triangle has 100 times the area of the small one
+        let area = NormalMode::Smooth { weighting: Weighting::Area, crease: None };
+        let (result, result_indices) = generate_normals(&vertices, &indices, area);
+        assert_close(normal_at_origin(&result, &result_indices, 0), Vector3::new(1.0, 0.0, 100.0).normalize().into());
+
+        // the faces are 90 degrees apart, so a smaller crease keeps them separate
+        for (crease, split) in [(89.0, true), (91.0, false)] {
+            let mode = NormalMode::Smooth { weighting: Weighting::Angle, crease: Some(crease) };
+            let (result, result_indices) = generate_normals(&vertices, &indices, mode);
+            assert_eq!(result_indices[0] != result_indices[3], split, "crease {}", crease);
+            if split {
+                assert_close(normal_at_origin(&result, &result_indices, 0), [0.0, 0.0, 1.0]);
+                assert_close(normal_at_origin(&result, &result_indices, 3), [1.0, 0.0, 0.0]);
+            }
+        }
+    }
+
+    #[test]
+    fn corners_within_the_weld_distance_share_normals() {
+        // the second face's copy of the shared corner sits just across a grid cell boundary
+        let (mut vertices, mut indices) = two_faces();
+        vertices.push(vertex([WELD_DISTANCE * 0.55, 0.0, 0.0]));
+        vertices[0].position = [-WELD_DISTANCE * 0.4, 0.0, 0.0];
+        indices[3] = 5;
+        let smooth = NormalMode::Smooth { weighting: Weighting::Angle, crease: None };
+        let (result, result_indices) = generate_normals(&vertices, &indices, smooth);
+        assert_close(normal_at_origin(&result, &result_indices, 0), normal_at_origin(&result, &result_indices, 3));
+
+        // farther apart they stay separate
+        vertices[5].position = [WELD_DISTANCE * 2.0, 0.0, 0.0];
+        let (result, result_indices) = generate_normals(&vertices, &indices, smooth);
+        assert_close(normal_at_origin(&result, &result_indices, 0), [0.0, 0.0, 1.0]);
+    }
+
+    #[test]
+    fn modes_parse_from_arobj_words() {
+        assert_eq!(NormalMode::parse(&["flat"]), Ok(NormalMode::Flat));
+        assert_eq!(NormalMode::parse(&["smooth", "area", "45"]), Ok(NormalMode::Smooth { weighting: Weighting::Area, crease: Some(45.0) }));
+        assert_eq!(NormalMode::parse(&["smooth"]), Ok(NormalMode::Smooth { weighting: Weighting::Angle, crease: None }));
+        assert!(NormalMode::parse(&["smooth", "200"]).is_err());
+        assert!(NormalMode::parse(&["flat", "area"]).is_err());
+        assert!(NormalMode::parse(&[]).is_err());
+    }
+}
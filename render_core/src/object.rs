@@ -1,21 +1,68 @@
+use crate::material::Material;
+use crate::normals::{self, NormalMode};
 use crate::vertex::Vertex;
+use crate::{gltf, mesh_cache, ply, stl};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+
+// indices are u16 on the GPU
+pub const MAX_VERTICES: usize = u16::MAX as usize + 1;
 
 #[derive(Debug, Clone)]
 pub struct ObjectGeometry {
     pub name: String,
     pub vertices: Vec<Vertex>,
     pub indices: Vec<u16>,
+    // files this geometry was built from (.arobj first), used by hot reload
+    pub source_files: Vec<PathBuf>,
+    // material that came with the mesh file (glTF), used by objects without `material:`
+    pub material: Option<Material>,
+}
+
+/// Triangles read from a model file, before the .arobj settings are applied
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    // false when the file has no normals and they still need generating
+    pub has_normals: bool,
+    pub material: Option<Material>,
+    // files read besides the model file itself, e.g. glTF buffers
+    pub extra_files: Vec<PathBuf>,
+}
+
+impl MeshData {
+    pub fn new() -> Self {
+        MeshData {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            has_normals: true,
+            material: None,
+            extra_files: Vec::new(),
+        }
+    }
+
+    // adds the triangles of `other`; its normals must already be there
+    pub fn append(&mut self, other: MeshData) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend(other.vertices);
+        self.indices.extend(other.indices.iter().map(|&index| index + offset));
+    }
+
+    pub fn generate_normals(&mut self, mode: NormalMode) {
+        (self.vertices, self.indices) = normals::generate_normals(&self.vertices, &self.indices, mode);
+        self.has_normals = true;
+    }
 }
 
 impl ObjectGeometry {
     pub fn load_from_arobj<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
         let path = path.as_ref();
         let content = fs::read_to_string(path)?;
-        
-        let mut name = String::from("Unnamed");
-        let mut obj_file: Option<String> = None;
+
+        let mut name: Option<String> = None;
+        let mut mesh_file: Option<String> = None;
+        let mut primitive: Option<String> = None;
+        let mut normal_mode: Option<NormalMode> = None;
 
         for line in content.lines() {
             let line = line.trim();
@@ -29,84 +76,192 @@ impl ObjectGeometry {
             }
 
             match parts[0] {
-                "name" => {
-                    if parts.len() > 1 {
-                        name = parts[1..].join(" ");
-                    }
+                "name" if parts.len() > 1 => {
+                    name = Some(parts[1..].join(" "));
+                }
+                // obj_file: is the older name, from when OBJ was the only format
+                "mesh_file:" | "obj_file:" if parts.len() > 1 => {
+                    mesh_file = Some(parts[1].to_string());
+                }
+                "primitive:" if parts.len() > 1 => {
+                    primitive = Some(parts[1..].join(" "));
                 }
-                "obj_file:" => {
-                    if parts.len() > 1 {
-                        obj_file = Some(parts[1].to_string());
-                    }
+                "normals:" => {
+                    normal_mode = Some(NormalMode::parse(&parts[1..]).map_err(|e| format!("{}: {}", path.display(), e))?);
                 }
                 _ => {}
             }
         }
 
-        let obj_file = obj_file.ok_or("Missing obj_file in .arobj metadata")?;
-        let obj_path = Path::new("assets").join(obj_file);
-
-        let (models, _materials) = tobj::load_obj(
-            &obj_path,
-            &tobj::LoadOptions {
-                single_index: true,
-                triangulate: true,
-                ..Default::default()
-            },
-        )?;
+        // built in code, so the .arobj is the only file to watch
+        if let Some(spec) = primitive {
+            let mut geometry = Self::from_primitive(&spec).map_err(|e| format!("{}: {}", path.display(), e))?;
+            if let Some(name) = name {
+                geometry.name = name;
+            }
+            if let Some(mode) = normal_mode {
+                geometry.generate_normals(mode)?;
+            }
+            geometry.source_files = vec![path.to_path_buf()];
+            return Ok(geometry);
+        }
 
-        if models.is_empty() {
-            return Err("OBJ file contains no models".into());
+        let name = name.unwrap_or_else(|| String::from("Unnamed"));
+        let mesh_file = mesh_file.ok_or("Missing mesh_file or primitive in .arobj metadata")?;
+        let mesh_path = Path::new("assets").join(&mesh_file);
+        // cached meshes have their normals already
+        let normal_settings = format!("{:?}", normal_mode);
+        // cached formats are read once, so the cache is checked against and written for the bytes that are parsed,
+        // even if the file changes in the meantime
+        let source = mesh_cache::is_cached_format(&mesh_path)
+            .then(|| fs::read(&mesh_path).map_err(|e| format!("{}: {}", mesh_path.display(), e)))
+            .transpose()?;
+        let cached = source.as_deref().and_then(|source| mesh_cache::load(&mesh_path, &mesh_file, source, &normal_settings));
+        let (mesh, cached) = match cached {
+            Some(mesh) => (mesh, true),
+            None => {
+                let mut mesh = match source.as_deref() {
+                    Some(source) => parse_mesh_file(&mesh_path, source)?,
+                    None => load_mesh_file(&mesh_path, &mesh_file)?,
+                };
+                // an explicit mode replaces the file's normals; files without normals get the default mode
+                if let Some(mode) = normal_mode.or((!mesh.has_normals).then(NormalMode::default)) {
+                    mesh.generate_normals(mode);
+                }
+                if let Some(source) = source.as_deref() {
+                    mesh_cache::store(&mesh_path, &mesh_file, source, &normal_settings, &mesh);
+                }
+                (mesh, false)
+            }
+        };
+        if mesh.vertices.len() > MAX_VERTICES {
+            return Err(format!("'{}' has {} vertices, at most {} are supported", mesh_path.display(), mesh.vertices.len(), MAX_VERTICES).into());
         }
 
-        let mesh = &models[0].mesh;
-        let mut vertices = Vec::new();
-        let vertex_count = mesh.positions.len() / 3;
-
-        for i in 0..vertex_count {
-            let position = [
-                mesh.positions[i * 3],
-                mesh.positions[i * 3 + 1],
-                mesh.positions[i * 3 + 2],
-            ];
-
-            let color = [1.0, 1.0, 1.0];
-
-            let normal = if mesh.normals.is_empty() {
-                [0.0, 1.0, 0.0]
-            } else {
-                [
-                    mesh.normals[i * 3],
-                    mesh.normals[i * 3 + 1],
-                    mesh.normals[i * 3 + 2],
-                ]
-            };
-
-            let uv = if mesh.texcoords.is_empty() {
-                [0.0, 0.0]
-            } else {
-                [
-                    mesh.texcoords[i * 2],
-                    mesh.texcoords[i * 2 + 1],
-                ]
-            };
-
-            vertices.push(Vertex {
-                position,
-                color,
-                normal,
-                uv,
-            });
+        let mut source_files = vec![path.to_path_buf(), mesh_path];
+        source_files.extend(mesh.extra_files);
+        let geometry = ObjectGeometry {
+            name,
+            vertices: mesh.vertices,
+            indices: mesh.indices.into_iter().map(|index| index as u16).collect(),
+            source_files,
+            material: mesh.material,
+        };
+
+        println!("Loaded '{}'{}: {} vertices, {} indices", geometry.name, if cached { " from the mesh cache" } else { "" },
+            geometry.vertices.len(), geometry.indices.len());
+
+        Ok(geometry)
+    }
+
+    // axis-aligned bounds (min, max) in object space
+    pub fn bounds(&self) -> ([f32; 3], [f32; 3]) {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for vertex in &self.vertices {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(vertex.position[axis]);
+                max[axis] = max[axis].max(vertex.position[axis]);
+            }
+        }
+        if self.vertices.is_empty() {
+            return ([0.0; 3], [0.0; 3]);
         }
+        (min, max)
+    }
+}
+
+// picks the loader by extension for formats that read their own files; `asset_path` is the same file
+// relative to the assets folder, which texture paths in the file are resolved against
+fn load_mesh_file(path: &Path, asset_path: &str) -> Result<MeshData, String> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("gltf" | "glb") => gltf::load_mesh(path, asset_path),
+        _ => Err(format!("Unsupported mesh file '{}', expected .obj, .gltf, .glb, .ply or .stl", path.display())),
+    }
+}
 
-        let indices: Vec<u16> = mesh.indices.iter().map(|&i| i as u16).collect();
+// the formats in mesh_cache::CACHED_EXTENSIONS, parsed from the file's contents
+fn parse_mesh_file(path: &Path, bytes: &[u8]) -> Result<MeshData, String> {
+    let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+    match extension.as_deref() {
+        Some("obj") => load_obj(path, bytes),
+        Some("ply") => ply::load(path, bytes),
+        Some("stl") => stl::load(path, bytes),
+        _ => Err(format!("Unsupported mesh file '{}', expected .obj, .gltf, .glb, .ply or .stl", path.display())),
+    }
+}
 
-        println!("Loaded '{}': {} vertices, {} indices", name, vertices.len(), indices.len());
+fn load_obj(path: &Path, bytes: &[u8]) -> Result<MeshData, String> {
+    // materials come from .armat files, so the OBJ's mtllib is never read
+    let (models, _materials) = tobj::load_obj_buf(
+        &mut std::io::BufReader::new(bytes),
+        &tobj::LoadOptions {
+            single_index: true,
+            triangulate: true,
+            ..Default::default()
+        },
+        |_| Err(tobj::LoadError::OpenFileFailed),
+    ).map_err(|e| format!("{}: {}", path.display(), e))?;
 
-        Ok(ObjectGeometry {
-            name,
-            vertices,
-            indices,
-        })
+    if models.is_empty() {
+        return Err(format!("{}: OBJ file contains no models", path.display()));
     }
+
+    let mesh = &models[0].mesh;
+    let mut vertices = Vec::new();
+    let vertex_count = mesh.positions.len() / 3;
+
+    for i in 0..vertex_count {
+        let position = [
+            mesh.positions[i * 3],
+            mesh.positions[i * 3 + 1],
+            mesh.positions[i * 3 + 2],
+        ];
+
+        // white unless the OBJ has `v x y z r g b` lines
+        let color = if mesh.vertex_color.len() == mesh.positions.len() {
+            [
+                mesh.vertex_color[i * 3],
+                mesh.vertex_color[i * 3 + 1],
+                mesh.vertex_color[i * 3 + 2],
+            ]
+        } else {
+            [1.0, 1.0, 1.0]
+        };
+
+        // generated by the caller when the OBJ has none
+        let normal = if mesh.normals.is_empty() {
+            [0.0, 1.0, 0.0]
+        } else {
+            [
+                mesh.normals[i * 3],
+                mesh.normals[i * 3 + 1],
+                mesh.normals[i * 3 + 2],
+            ]
+        };
+
+        let uv = if mesh.texcoords.is_empty() {
+            [0.0, 0.0]
+        } else {
+            [
+                mesh.texcoords[i * 2],
+                mesh.texcoords[i * 2 + 1],
+            ]
+        };
+
+        vertices.push(Vertex {
+            position,
+            color,
+            normal,
+            uv,
+        });
+    }
+
+    Ok(MeshData {
+        vertices,
+        indices: mesh.indices.clone(),
+        has_normals: !mesh.normals.is_empty(),
+        ..MeshData::new()
+    })
 }
@@ -0,0 +1,275 @@
+use cgmath::{Deg, InnerSpace, Matrix4, Point3, Quaternion, Rotation, Rotation3, Transform as _, Vector3};
+
+use crate::object::ObjectGeometry;
+use crate::transform::{RotationOrder, Transform};
+
+// mixed into the seed for the rotation/scale stream, so changing those ranges doesn't move scattered copies
+const VARIATION_STREAM: u64 = 0x5bd1_e995_9e37_79b9;
+/// Most copies one pattern may make, so a typo like `array: 1000 1000 1000` fails instead of exhausting memory
+pub const MAX_PATTERN_COPIES: u64 = 100_000;
+
+/// How an object block is repeated when the scene loads
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    // counts[axis] copies along each axis, offset[axis] apart
+    Array { counts: [u32; 3], offset: [f32; 3] },
+    // copies spread evenly around an axis through the object's position
+    Radial { count: u32, axis: usize, radius: f32 },
+    Scatter { count: u32, region: ScatterRegion },
+}
+
+#[derive(Debug, Clone)]
+pub enum ScatterRegion {
+    // corners relative to the object's position
+    Box { min: [f32; 3], max: [f32; 3] },
+    // name of the object whose triangles the copies are placed on
+    Surface(String),
+}
+
+impl Pattern {
+    // `array: nx ny nz dx dy dz`, `radial: count x|y|z radius`,
+    // `scatter: count box x0 y0 z0 x1 y1 z1` or `scatter: count surface <object>`
+    pub fn parse(parts: &[&str]) -> Result<Pattern, String> {
+        let number = |index: usize| -> Result<f32, String> {
+            let value = parts.get(index).ok_or_else(|| format!("{} is missing values", parts[0]))?;
+            value.parse().map_err(|_| format!("invalid number '{}' in {}", value, parts[0]))
+        };
+        let count = |index: usize| -> Result<u32, String> {
+            let value = parts.get(index).ok_or_else(|| format!("{} is missing a count", parts[0]))?;
+            match value.parse() {
+                Ok(count) if count > 0 => Ok(count),
+                _ => Err(format!("invalid count '{}' in {}, expected a whole number above 0", value, parts[0])),
+            }
+        };
+        let vec3 = |index: usize| -> Result<[f32; 3], String> { Ok([number(index)?, number(index + 1)?, number(index + 2)?]) };
+
+        let pattern = match parts[0] {
+            "array:" => Pattern::Array {
+                counts: [count(1)?, count(2)?, count(3)?],
+                offset: vec3(4)?,
+            },
+            "radial:" => {
+                let axis = match parts.get(2) {
+                    Some(&"x") => 0,
+                    Some(&"y") => 1,
+                    Some(&"z") => 2,
+                    _ => return Err("radial: needs an axis (x, y or z) after the count".to_string()),
+                };
+                Pattern::Radial { count: count(1)?, axis, radius: number(3)? }
+            }
+            "scatter:" => {
+                let region = match parts.get(2) {
+                    Some(&"box") => ScatterRegion::Box { min: vec3(3)?, max: vec3(6)? },
+                    Some(&"surface") => {
+                        let target = parts.get(3).ok_or("scatter: surface needs an object name")?;
+                        ScatterRegion::Surface(target.to_string())
+                    }
+                    _ => return Err("scatter: needs 'box' or 'surface' after the count".to_string()),
+                };
+                Pattern::Scatter { count: count(1)?, region }
+            }
+            key => return Err(format!("unknown pattern '{}'", key)),
+        };
+        if pattern.copy_count() > MAX_PATTERN_COPIES {
+            return Err(format!("{} makes {} copies, at most {} are allowed", parts[0], pattern.copy_count(), MAX_PATTERN_COPIES));
+        }
+        Ok(pattern)
+    }
+
+    pub fn copy_count(&self) -> u64 {
+        match self {
+            Pattern::Array { counts, .. } => counts.iter().map(|&count| count as u64).product(),
+            Pattern::Radial { count, .. } | Pattern::Scatter { count, .. } => *count as u64,
+        }
+    }
+}
+
+/// Random rotation and scale given to every copy; the same seed always gives the same layout
+#[derive(Debug, Clone, Copy)]
+pub struct Variation {
+    pub seed: u64,
+    // up to this many degrees either way, per axis
+    pub rotation: [f32; 3],
+    // uniform factor between min and max
+    pub scale: [f32; 2],
+}
+
+impl Default for Variation {
+    fn default() -> Self {
+        Variation {
+            seed: 0,
+            rotation: [0.0; 3],
+            scale: [1.0, 1.0],
+        }
+    }
+}
+
+/// SplitMix64; small and defined here so layouts don't change with a dependency update
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> f32 {
+        min + (max - min) * self.next_f32()
+    }
+}
+
+/// Triangles of a mesh, picked in proportion to their area
+pub struct SurfaceSampler {
+    triangles: Vec<[Point3<f32>; 3]>,
+    // running total of the triangle areas
+    cumulative_area: Vec<f32>,
+}
+
+impl SurfaceSampler {
+    // `matrix` takes the mesh into the space the samples should be in; None if the mesh has no area
+    pub fn new(geometry: &ObjectGeometry, matrix: Matrix4<f32>) -> Option<Self> {
+        let mut triangles = Vec::new();
+        let mut cumulative_area = Vec::new();
+        let mut total = 0.0;
+        for triangle in geometry.indices.chunks_exact(3) {
+            let corners = [0, 1, 2].map(|corner| {
+                matrix.transform_point(Point3::from(geometry.vertices[triangle[corner] as usize].position))
+            });
+            let area = 0.5 * (corners[1] - corners[0]).cross(corners[2] - corners[0]).magnitude();
+            if area > 0.0 {
+                total += area;
+                triangles.push(corners);
+                cumulative_area.push(total);
+            }
+        }
+        (total > 0.0).then_some(SurfaceSampler { triangles, cumulative_area })
+    }
+
+    pub fn sample(&self, rng: &mut Rng) -> [f32; 3] {
+        let total = *self.cumulative_area.last().unwrap();
+        let target = rng.next_f32() * total;
+        let index = self.cumulative_area.partition_point(|&area| area <= target).min(self.triangles.len() - 1);
+        let [a, b, c] = self.triangles[index];
+
+        // fold points from the far half of the parallelogram back into the triangle
+        let (mut u, mut v) = (rng.next_f32(), rng.next_f32());
+        if u + v > 1.0 {
+            (u, v) = (1.0 - u, 1.0 - v);
+        }
+        let point = a + (b - a) * u + (c - a) * v;
+        [point.x, point.y, point.z]
+    }
+}
+
+/// Transforms of all copies, in the same space as `base`; surface scatter needs a sampler in that space
+pub fn expand(pattern: &Pattern, base: &Transform, variation: &Variation, surface: Option<&SurfaceSampler>) -> Vec<Transform> {
+    let mut rng = Rng::new(variation.seed);
+    let translated = |offset: [f32; 3]| Transform { position: offset, ..Transform::new() }.combine(base);
+
+    let mut copies: Vec<Transform> = match pattern {
+        Pattern::Array { counts, offset } => {
+            let mut copies = Vec::new();
+            for z in 0..counts[2] {
+                for y in 0..counts[1] {
+                    for x in 0..counts[0] {
+                        let steps = [x as f32, y as f32, z as f32];
+                        copies.push(translated(std::array::from_fn(|axis| steps[axis] * offset[axis])));
+                    }
+                }
+            }
+            copies
+        }
+        Pattern::Radial { count, axis, radius } => {
+            let axis_vector = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()][*axis];
+            // copies start on +X, or on +Y when turning around X
+            let start = if *axis == 0 { Vector3::unit_y() } else { Vector3::unit_x() } * *radius;
+            let pivot = Vector3::from(base.position);
+            let shifted = Transform { position: (pivot + start).into(), ..*base };
+            (0..*count)
+                .map(|copy| {
+                    let rotation = Quaternion::from_axis_angle(axis_vector, Deg(360.0 * copy as f32 / *count as f32));
+                    // turn about the pivot rather than the origin, so copies also face outward
+                    let around_pivot = Transform {
+                        position: (pivot - rotation.rotate_vector(pivot)).into(),
+                        rotation,
+                        scale: [1.0; 3],
+                    };
+                    around_pivot.combine(&shifted)
+                })
+                .collect()
+        }
+        Pattern::Scatter { count, region } => (0..*count)
+            .map(|_| {
+                let offset = match (region, surface) {
+                    (ScatterRegion::Box { min, max }, _) => std::array::from_fn(|axis| rng.range(min[axis], max[axis])),
+                    (ScatterRegion::Surface(_), Some(surface)) => surface.sample(&mut rng),
+                    (ScatterRegion::Surface(_), None) => [0.0; 3],
+                };
+                translated(offset)
+            })
+            .collect(),
+    };
+
+    let mut rng = Rng::new(variation.seed ^ VARIATION_STREAM);
+    for copy in &mut copies {
+        let [x, y, z] = variation.rotation;
+        let angles = [rng.range(-x, x), rng.range(-y, y), rng.range(-z, z)];
+        copy.rotation = copy.rotation * Transform::rotation_from_euler(angles, RotationOrder::Xyz);
+        let factor = rng.range(variation.scale[0], variation.scale[1]);
+        copy.scale = copy.scale.map(|scale| scale * factor);
+    }
+    copies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Pattern {
+        Pattern::parse(&line.split_whitespace().collect::<Vec<_>>()).unwrap()
+    }
+
+    fn values(transforms: &[Transform]) -> Vec<[f32; 10]> {
+        transforms.iter()
+            .map(|transform| {
+                let [x, y, z] = transform.position;
+                let rotation = transform.rotation;
+                let [sx, sy, sz] = transform.scale;
+                [x, y, z, rotation.s, rotation.v.x, rotation.v.y, rotation.v.z, sx, sy, sz]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_gives_same_layout() {
+        let pattern = parse("scatter: 50 box -2 0 -2 2 1 2");
+        let base = Transform::new().with_position(1.0, 0.5, -3.0).with_rotation(0.0, 30.0, 0.0);
+        let variation = Variation { seed: 42, rotation: [10.0, 180.0, 10.0], scale: [0.5, 1.5] };
+
+        let first = expand(&pattern, &base, &variation, None);
+        assert_eq!(first.len(), 50);
+        assert_eq!(values(&first), values(&expand(&pattern, &base, &variation, None)));
+
+        let reseeded = Variation { seed: 43, ..variation };
+        assert_ne!(values(&first), values(&expand(&pattern, &base, &reseeded, None)));
+    }
+
+    #[test]
+    fn too_many_copies_are_rejected() {
+        assert!(Pattern::parse(&["array:", "1000", "1000", "1000", "1", "1", "1"]).is_err());
+        assert!(Pattern::parse(&["scatter:", "4294967295", "box", "0", "0", "0", "1", "1", "1"]).is_err());
+        assert_eq!(parse("array: 100 100 10 1 1 1").copy_count(), MAX_PATTERN_COPIES);
+    }
+}
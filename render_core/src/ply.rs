@@ -0,0 +1,314 @@
+use std::path::Path;
+
+use crate::object::MeshData;
+use crate::vertex::Vertex;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Scalar::I8),
+            "uchar" | "uint8" => Some(Scalar::U8),
+            "short" | "int16" => Some(Scalar::I16),
+            "ushort" | "uint16" => Some(Scalar::U16),
+            "int" | "int32" => Some(Scalar::I32),
+            "uint" | "uint32" => Some(Scalar::U32),
+            "float" | "float32" => Some(Scalar::F32),
+            "double" | "float64" => Some(Scalar::F64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // colors stored as integers are scaled by this to get 0..1
+    fn full_scale(self) -> f32 {
+        match self {
+            Scalar::U8 => 255.0,
+            Scalar::U16 => 65535.0,
+            Scalar::U32 => u32::MAX as f32,
+            Scalar::I8 => 127.0,
+            Scalar::I16 => 32767.0,
+            Scalar::I32 => i32::MAX as f32,
+            Scalar::F32 | Scalar::F64 => 1.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Property {
+    Scalar(String, Scalar),
+    // count type and item type
+    List(String, Scalar, Scalar),
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn find(&self, names: &[&str]) -> Option<(usize, Scalar)> {
+        self.properties.iter().enumerate().find_map(|(index, property)| match property {
+            Property::Scalar(name, scalar) | Property::List(name, _, scalar) if names.contains(&name.as_str()) => Some((index, *scalar)),
+            _ => None,
+        })
+    }
+}
+
+// one property of one element: scalars as a single value
+enum Value {
+    Scalar(f64),
+    List(Vec<f64>),
+}
+
+impl Value {
+    fn scalar(&self) -> Result<f32, String> {
+        match self {
+            Value::Scalar(value) => Ok(*value as f32),
+            Value::List(_) => Err("a vertex property is a list where a number is expected".to_string()),
+        }
+    }
+}
+
+enum Body<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary { bytes: &'a [u8], position: usize, big_endian: bool },
+}
+
+impl Body<'_> {
+    fn read(&mut self, scalar: Scalar) -> Result<f64, String> {
+        match self {
+            Body::Ascii(words) => {
+                let word = words.next().ok_or("the file ends early")?;
+                word.parse().map_err(|_| format!("invalid number '{}'", word))
+            }
+            Body::Binary { bytes, position, big_endian } => {
+                let data = bytes.get(*position..*position + scalar.size()).ok_or("the file ends early")?;
+                *position += scalar.size();
+                // reversing to little endian leaves one set of conversions
+                let mut buffer = [0u8; 8];
+                buffer[..data.len()].copy_from_slice(data);
+                if *big_endian {
+                    buffer[..data.len()].reverse();
+                }
+                let [a, b, c, d, e, f, g, h] = buffer;
+                Ok(match scalar {
+                    Scalar::I8 => a as i8 as f64,
+                    Scalar::U8 => a as f64,
+                    Scalar::I16 => i16::from_le_bytes([a, b]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([a, b]) as f64,
+                    Scalar::I32 => i32::from_le_bytes([a, b, c, d]) as f64,
+                    Scalar::U32 => u32::from_le_bytes([a, b, c, d]) as f64,
+                    Scalar::F32 => f32::from_le_bytes([a, b, c, d]) as f64,
+                    Scalar::F64 => f64::from_le_bytes([a, b, c, d, e, f, g, h]),
+                })
+            }
+        }
+    }
+
+    fn read_element(&mut self, element: &Element) -> Result<Vec<Value>, String> {
+        element.properties.iter()
+            .map(|property| match property {
+                Property::Scalar(_, scalar) => self.read(*scalar).map(Value::Scalar),
+                Property::List(_, count, item) => {
+                    let count = self.read(*count)? as usize;
+                    (0..count).map(|_| self.read(*item)).collect::<Result<_, _>>().map(Value::List)
+                }
+            })
+            .collect()
+    }
+}
+
+/// ASCII or binary PLY: positions, and normals, vertex colors and UVs when present;
+/// polygons are split into triangle fans. `bytes` are the contents of the file at `path`
+pub fn load(path: &Path, bytes: &[u8]) -> Result<MeshData, String> {
+    let in_file = |e: String| format!("{}: {}", path.display(), e);
+    let (format, elements, body_start) = parse_header(bytes).map_err(in_file)?;
+    let mut body = match format {
+        Format::Ascii => Body::Ascii(std::str::from_utf8(&bytes[body_start..])
+            .map_err(|_| in_file("ASCII data is not valid text".to_string()))?
+            .split_ascii_whitespace()),
+        _ => Body::Binary { bytes: &bytes[body_start..], position: 0, big_endian: format == Format::BinaryBigEndian },
+    };
+
+    let mut mesh = MeshData::new();
+    let mut has_normals = false;
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => {
+                let find_all = |names: [&[&str]; 3]| -> Option<[(usize, Scalar); 3]> {
+                    Some([element.find(names[0])?, element.find(names[1])?, element.find(names[2])?])
+                };
+                let position = find_all([&["x"], &["y"], &["z"]]).ok_or_else(|| in_file("vertices have no x, y and z".to_string()))?;
+                let normal = find_all([&["nx"], &["ny"], &["nz"]]);
+                let color = find_all([&["red", "diffuse_red"], &["green", "diffuse_green"], &["blue", "diffuse_blue"]]);
+                let uv = [&["u", "s", "texture_u", "texture_s"][..], &["v", "t", "texture_v", "texture_t"][..]]
+                    .map(|names| element.find(names));
+                has_normals = normal.is_some();
+
+                for _ in 0..element.count {
+                    let values = body.read_element(element).map_err(in_file)?;
+                    let read3 = |properties: [(usize, Scalar); 3], scaled: bool| -> Result<[f32; 3], String> {
+                        let [a, b, c] = properties.map(|(index, scalar)| {
+                            values[index].scalar().map(|value| value / if scaled { scalar.full_scale() } else { 1.0 })
+                        });
+                        Ok([a?, b?, c?])
+                    };
+                    mesh.vertices.push(Vertex {
+                        position: read3(position, false).map_err(in_file)?,
+                        color: color.map_or(Ok([1.0; 3]), |color| read3(color, true)).map_err(in_file)?,
+                        normal: normal.map_or(Ok([0.0, 1.0, 0.0]), |normal| read3(normal, false)).map_err(in_file)?,
+                        uv: match uv {
+                            [Some((u, _)), Some((v, _))] => [values[u].scalar().map_err(in_file)?, values[v].scalar().map_err(in_file)?],
+                            _ => [0.0; 2],
+                        },
+                    });
+                }
+            }
+            "face" => {
+                let indices = element.properties.iter()
+                    .position(|property| matches!(property, Property::List(name, _, _) if name == "vertex_indices" || name == "vertex_index"))
+                    .ok_or_else(|| in_file("faces have no vertex_indices list".to_string()))?;
+                for _ in 0..element.count {
+                    let values = body.read_element(element).map_err(in_file)?;
+                    if let Value::List(corners) = &values[indices] {
+                        // negative, fractional or NaN indices would otherwise cast to some other vertex
+                        if let Some(corner) = corners.iter().find(|&&corner| corner.fract() != 0.0 || !(0.0..=u32::MAX as f64).contains(&corner)) {
+                            return Err(in_file(format!("invalid vertex index {} in a face", corner)));
+                        }
+                        for i in 1..corners.len().saturating_sub(1) {
+                            mesh.indices.extend([corners[0], corners[i], corners[i + 1]].map(|corner| corner as u32));
+                        }
+                    }
+                }
+            }
+            // other elements (edges, materials, ...) are read past
+            _ => {
+                for _ in 0..element.count {
+                    body.read_element(element).map_err(in_file)?;
+                }
+            }
+        }
+    }
+
+    if mesh.indices.iter().any(|&index| index as usize >= mesh.vertices.len()) {
+        return Err(in_file("a face refers to a vertex that doesn't exist".to_string()));
+    }
+    mesh.has_normals = has_normals;
+    Ok(mesh)
+}
+
+// format, elements in file order and where the data starts
+fn parse_header(bytes: &[u8]) -> Result<(Format, Vec<Element>, usize), String> {
+    if !bytes.starts_with(b"ply") {
+        return Err("not a PLY file".to_string());
+    }
+    let end = bytes.windows(10).position(|window| window == b"end_header").ok_or("the header has no end_header")?;
+    let body_start = bytes[end..].iter().position(|&byte| byte == b'\n').map_or(bytes.len(), |newline| end + newline + 1);
+    let header = std::str::from_utf8(&bytes[..end]).map_err(|_| "the header is not text")?;
+
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    for line in header.lines().skip(1) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", name, _version] => {
+                format = Some(match *name {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(format!("unknown format '{}'", name)),
+                });
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| format!("invalid element count '{}'", count))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let element = elements.last_mut().ok_or("a property comes before any element")?;
+                let scalar = |name: &str| Scalar::from_name(name).ok_or_else(|| format!("unknown property type '{}'", name));
+                element.properties.push(Property::List(name.to_string(), scalar(count)?, scalar(item)?));
+            }
+            ["property", kind, name] => {
+                let element = elements.last_mut().ok_or("a property comes before any element")?;
+                let scalar = Scalar::from_name(kind).ok_or_else(|| format!("unknown property type '{}'", kind))?;
+                element.properties.push(Property::Scalar(name.to_string(), scalar));
+            }
+            ["comment", ..] | ["obj_info", ..] | [] => {}
+            _ => return Err(format!("unexpected header line '{}'", line)),
+        }
+    }
+    if let Some(element) = elements.iter().find(|element| element.properties.is_empty() && element.count > 0) {
+        return Err(format!("element '{}' has no properties", element.name));
+    }
+    Ok((format.ok_or("the header has no format line")?, elements, body_start))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ascii(vertex_properties: &str, vertices: &str, faces: &str) -> Vec<u8> {
+        format!(
+            "ply\nformat ascii 1.0\nelement vertex 3\n{}element face 1\nproperty list uchar int vertex_indices\nend_header\n{}{}",
+            vertex_properties, vertices, faces,
+        ).into_bytes()
+    }
+
+    const XYZ: &str = "property float x\nproperty float y\nproperty float z\n";
+    const VERTICES: &str = "0 0 0\n1 0 0\n0 1 0\n";
+
+    #[test]
+    fn triangles_load_with_their_indices() {
+        let mesh = load(Path::new("triangle.ply"), &ascii(XYZ, VERTICES, "3 0 1 2\n")).unwrap();
+        assert_eq!(mesh.vertices.len(), 3);
+        assert_eq!(mesh.indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn invalid_face_indices_are_rejected() {
+        for face in ["3 0 1 -1\n", "3 0 1 1.5\n", "3 0 1 NaN\n", "3 0 1 3\n", "3 0 1 4294967296\n"] {
+            let result = load(Path::new("bad.ply"), &ascii(XYZ, VERTICES, face));
+            assert!(result.is_err(), "face '{}' was accepted", face.trim());
+        }
+    }
+
+    #[test]
+    fn list_typed_vertex_properties_are_rejected() {
+        let properties = "property float x\nproperty float y\nproperty list uchar float z\n";
+        let vertices = "0 0 1 0\n1 0 1 0\n0 1 1 0\n";
+        let Err(error) = load(Path::new("list.ply"), &ascii(properties, vertices, "3 0 1 2\n")) else {
+            panic!("a list-typed z was accepted");
+        };
+        assert!(error.contains("list"), "{}", error);
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::{HashMap, HashSet};
+
+/// Minimal WGSL preprocessor supporting `#include`, `#define`, `#undef`,
+/// `#ifdef`/`#ifndef`/`#else`/`#endif`
+pub struct Preprocessor<F: Fn(&str) -> Result<String, String>> {
+    defines: HashMap<String, String>,
+    // resolves an include name to its source text
+    resolve_include: F,
+    // each file is emitted once, so shared modules can include each other freely
+    included: HashSet<String>,
+}
+
+// state of one #ifdef/#ifndef block
+struct Conditional {
+    // whether the enclosing block was active when this one opened
+    parent_active: bool,
+    condition: bool,
+    in_else: bool,
+}
+
+impl Conditional {
+    fn is_active(&self) -> bool {
+        self.parent_active && (self.condition != self.in_else)
+    }
+}
+
+impl<F: Fn(&str) -> Result<String, String>> Preprocessor<F> {
+    pub fn new(defines: HashMap<String, String>, resolve_include: F) -> Self {
+        Preprocessor {
+            defines,
+            resolve_include,
+            included: HashSet::new(),
+        }
+    }
+
+    // expand the named root file
+    pub fn process(&mut self, name: &str) -> Result<String, String> {
+        let mut output = String::new();
+        self.process_file(name, &mut output)?;
+        Ok(output)
+    }
+
+    fn process_file(&mut self, name: &str, output: &mut String) -> Result<(), String> {
+        if !self.included.insert(name.to_string()) {
+            return Ok(());
+        }
+        let source = (self.resolve_include)(name)?;
+        let mut conditionals: Vec<Conditional> = Vec::new();
+
+        for (line_index, line) in source.lines().enumerate() {
+            let error = |message: &str| format!("{}:{}: {}", name, line_index + 1, message);
+            let active = conditionals.last().is_none_or(Conditional::is_active);
+            let trimmed = line.trim();
+
+            let Some(directive) = trimmed.strip_prefix('#') else {
+                if active {
+                    output.push_str(&self.substitute(line));
+                    output.push('\n');
+                }
+                continue;
+            };
+
+            let mut parts = directive.split_whitespace();
+            let keyword = parts.next().unwrap_or("");
+            let argument = parts.next();
+
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    let symbol = argument.ok_or_else(|| error(&format!("#{} needs a symbol", keyword)))?;
+                    let defined = self.defines.contains_key(symbol);
+                    conditionals.push(Conditional {
+                        parent_active: active,
+                        condition: if keyword == "ifdef" { defined } else { !defined },
+                        in_else: false,
+                    });
+                }
+                "else" => {
+                    let conditional = conditionals.last_mut()
+                        .filter(|conditional| !conditional.in_else)
+                        .ok_or_else(|| error("#else without matching #ifdef"))?;
+                    conditional.in_else = true;
+                }
+                "endif" => {
+                    conditionals.pop().ok_or_else(|| error("#endif without matching #ifdef"))?;
+                }
+                _ if !active => {}
+                "include" => {
+                    let include = argument
+                        .map(|arg| arg.trim_matches('"'))
+                        .ok_or_else(|| error("#include needs a file name"))?;
+                    self.process_file(include, output)
+                        .map_err(|e| format!("{}\n  included from {}:{}", e, name, line_index + 1))?;
+                }
+                "define" => {
+                    let symbol = argument.ok_or_else(|| error("#define needs a symbol"))?;
+                    let value = parts.collect::<Vec<_>>().join(" ");
+                    self.defines.insert(symbol.to_string(), value);
+                }
+                "undef" => {
+                    let symbol = argument.ok_or_else(|| error("#undef needs a symbol"))?;
+                    self.defines.remove(symbol);
+                }
+                _ => return Err(error(&format!("unknown directive '#{}'", keyword))),
+            }
+        }
+
+        if !conditionals.is_empty() {
+            return Err(format!("{}: unterminated #ifdef/#ifndef", name));
+        }
+        Ok(())
+    }
+
+    // replace whole-word occurrences of defined symbols that carry a value
+    fn substitute(&self, line: &str) -> String {
+        let mut result = String::with_capacity(line.len());
+        let mut word = String::new();
+        for c in line.chars().chain(std::iter::once('\n')) {
+            if c.is_alphanumeric() || c == '_' {
+                word.push(c);
+                continue;
+            }
+            match self.defines.get(&word) {
+                Some(value) if !value.is_empty() => result.push_str(value),
+                _ => result.push_str(&word),
+            }
+            word.clear();
+            if c != '\n' {
+                result.push(c);
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // preprocess `root` with in-memory files
+    fn run(files: &[(&str, &str)], defines: &[(&str, &str)], root: &str) -> Result<String, String> {
+        let files: HashMap<String, String> = files.iter().map(|(name, text)| (name.to_string(), text.to_string())).collect();
+        let defines = defines.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect();
+        Preprocessor::new(defines, |name: &str| files.get(name).cloned().ok_or_else(|| format!("no file '{}'", name)))
+            .process(root)
+    }
+
+    #[test]
+    fn nested_includes_are_expanded_once_in_place() {
+        let files = [
+            ("main.wgsl", "// main\n#include \"lighting.wgsl\"\n#include \"structs.wgsl\"\nfn main() {}"),
+            ("lighting.wgsl", "#include \"structs.wgsl\"\nfn light() {}"),
+            ("structs.wgsl", "struct Light {}"),
+        ];
+        assert_eq!(run(&files, &[], "main.wgsl").unwrap(), "// main\nstruct Light {}\nfn light() {}\nfn main() {}\n");
+    }
+
+    #[test]
+    fn include_cycles_stop_at_the_file_already_being_expanded() {
+        let files = [("a.wgsl", "fn a() {}\n#include \"b.wgsl\"\nfn a2() {}"), ("b.wgsl", "#include \"a.wgsl\"\nfn b() {}")];
+        assert_eq!(run(&files, &[], "a.wgsl").unwrap(), "fn a() {}\nfn b() {}\nfn a2() {}\n");
+        let files = [("self.wgsl", "#include \"self.wgsl\"\nfn s() {}")];
+        assert_eq!(run(&files, &[], "self.wgsl").unwrap(), "fn s() {}\n");
+    }
+
+    #[test]
+    fn conditionals_nest_and_follow_defines() {
+        let source = "\
+#ifdef SHADOWS
+shadows
+#ifndef SOFT
+hard
+#else
+soft
+#endif
+#else
+no_shadows
+#ifdef SOFT
+hidden
+#endif
+#endif
+#define LATE 3
+#ifdef LATE
+late = LATE;
+#endif
+#undef LATE
+#ifndef LATE
+undefined LATE
+#endif";
+        let files = [("main.wgsl", source)];
+        assert_eq!(run(&files, &[], "main.wgsl").unwrap(), "no_shadows\nlate = 3;\nundefined LATE\n");
+        assert_eq!(run(&files, &[("SHADOWS", "")], "main.wgsl").unwrap(), "shadows\nhard\nlate = 3;\nundefined LATE\n");
+        assert_eq!(run(&files, &[("SHADOWS", ""), ("SOFT", "")], "main.wgsl").unwrap(), "shadows\nsoft\nlate = 3;\nundefined LATE\n");
+
+        // directives in inactive blocks don't run, but conditionals are still counted
+        let files = [("main.wgsl", "#ifdef OFF\n#include \"missing.wgsl\"\n#define ON 1\n#unknown\n#endif\n#ifdef ON\non\n#endif")];
+        assert_eq!(run(&files, &[], "main.wgsl").unwrap(), "");
+    }
+
+    #[test]
+    fn values_replace_whole_words_only() {
+        let files = [("main.wgsl", "let n = COUNT + COUNTER + MY_COUNT + COUNT_2;")];
+        assert_eq!(run(&files, &[("COUNT", "8u")], "main.wgsl").unwrap(), "let n = 8u + COUNTER + MY_COUNT + COUNT_2;\n");
+    }
+
+    #[test]
+    fn errors_name_the_file_line_and_include_chain() {
+        let files = [
+            ("main.wgsl", "fn main() {}\n\n#include \"lighting.wgsl\""),
+            ("lighting.wgsl", "#ifdef A\n#else\n#else\n#endif"),
+        ];
+        assert_eq!(run(&files, &[], "main.wgsl").unwrap_err(),
+            "lighting.wgsl:3: #else without matching #ifdef\n  included from main.wgsl:3");
+
+        for (source, error) in [
+            ("a\n#endif", "main.wgsl:2: #endif without matching #ifdef"),
+            ("#ifdef A\nb", "main.wgsl: unterminated #ifdef/#ifndef"),
+            ("#ifdef", "main.wgsl:1: #ifdef needs a symbol"),
+            ("x\ny\n  #pragma once", "main.wgsl:3: unknown directive '#pragma'"),
+            ("#include", "main.wgsl:1: #include needs a file name"),
+            ("\n#include \"gone.wgsl\"", "no file 'gone.wgsl'\n  included from main.wgsl:2"),
+        ] {
+            assert_eq!(run(&[("main.wgsl", source)], &[], "main.wgsl").unwrap_err(), error, "{:?}", source);
+        }
+    }
+}
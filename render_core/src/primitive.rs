@@ -0,0 +1,553 @@
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+
+use cgmath::{InnerSpace, Vector3};
+
+use crate::object::{ObjectGeometry, MAX_VERTICES};
+use crate::vertex::Vertex;
+
+// all primitives fit the same unit box as models/cube.obj: -0.5..0.5 on every axis
+impl ObjectGeometry {
+    /// Build a primitive from a spec such as `sphere segments=32 rings=16`.
+    /// Parameters left out get defaults, and the geometry is named after the full spec,
+    /// so identical primitives share one geometry
+    pub fn from_primitive(spec: &str) -> Result<Self, String> {
+        let mut words = spec.split_whitespace();
+        let kind = words.next().ok_or("empty primitive")?;
+        let params = Params::parse(kind, words)?;
+
+        let (geometry, name) = match kind {
+            "sphere" => {
+                params.only(&["segments", "rings"])?;
+                let (segments, rings) = (params.count("segments", 32, 3)?, params.count("rings", 16, 2)?);
+                (Self::uv_sphere(segments, rings), format!("sphere segments={} rings={}", segments, rings))
+            }
+            "icosphere" => {
+                params.only(&["subdivisions"])?;
+                let subdivisions = params.count("subdivisions", 2, 0)?;
+                (Self::icosphere(subdivisions), format!("icosphere subdivisions={}", subdivisions))
+            }
+            "cylinder" => {
+                params.only(&["segments"])?;
+                let segments = params.count("segments", 32, 3)?;
+                (Self::cylinder(segments), format!("cylinder segments={}", segments))
+            }
+            "cone" => {
+                params.only(&["segments"])?;
+                let segments = params.count("segments", 32, 3)?;
+                (Self::cone(segments), format!("cone segments={}", segments))
+            }
+            "torus" => {
+                params.only(&["segments", "sides", "tube"])?;
+                let (segments, sides) = (params.count("segments", 32, 3)?, params.count("sides", 16, 3)?);
+                let tube = params.number("tube", 0.15, 0.0, 0.25)?;
+                (Self::torus(segments, sides, tube), format!("torus segments={} sides={} tube={}", segments, sides, tube))
+            }
+            "capsule" => {
+                params.only(&["segments", "rings", "radius"])?;
+                let (segments, rings) = (params.count("segments", 32, 3)?, params.count("rings", 8, 1)?);
+                let radius = params.number("radius", 0.25, 0.0, 0.5)?;
+                (Self::capsule(segments, rings, radius), format!("capsule segments={} rings={} radius={}", segments, rings, radius))
+            }
+            "plane" => {
+                params.only(&["subdivisions"])?;
+                let subdivisions = params.count("subdivisions", 1, 1)?;
+                (Self::subdivided_plane(subdivisions), format!("plane subdivisions={}", subdivisions))
+            }
+            "rounded_box" => {
+                params.only(&["radius", "segments"])?;
+                let radius = params.number("radius", 0.1, 0.0, 0.5)?;
+                let segments = params.count("segments", 4, 1)?;
+                (Self::rounded_box(radius, segments), format!("rounded_box radius={} segments={}", radius, segments))
+            }
+            _ => return Err(format!(
+                "unknown primitive '{}', expected sphere, icosphere, cylinder, cone, torus, capsule, plane or rounded_box", kind
+            )),
+        };
+
+        let mut geometry = geometry?;
+        geometry.name = name;
+        println!("Built primitive '{}': {} vertices, {} indices", geometry.name, geometry.vertices.len(), geometry.indices.len());
+        Ok(geometry)
+    }
+
+    /// Sphere of latitude rings and longitude segments, radius 0.5
+    pub fn uv_sphere(segments: u32, rings: u32) -> Result<Self, String> {
+        let mut mesh = MeshBuilder::new("sphere");
+        mesh.grid(segments, rings, |column, row| {
+            let (u, v) = (column as f32 / segments as f32, row as f32 / rings as f32);
+            let normal = sphere_direction(u * TAU, v * PI);
+            vertex(normal * 0.5, normal, [u, v])
+        })?;
+        mesh.finish()
+    }
+
+    /// Subdivided icosahedron, radius 0.5; evenly sized triangles without pinched poles
+    pub fn icosphere(subdivisions: u32) -> Result<Self, String> {
+        // corners of an icosahedron from three orthogonal golden rectangles
+        let t = (1.0 + 5f32.sqrt()) / 2.0;
+        let mut points: Vec<Vector3<f32>> = [
+            [-1.0, t, 0.0], [1.0, t, 0.0], [-1.0, -t, 0.0], [1.0, -t, 0.0],
+            [0.0, -1.0, t], [0.0, 1.0, t], [0.0, -1.0, -t], [0.0, 1.0, -t],
+            [t, 0.0, -1.0], [t, 0.0, 1.0], [-t, 0.0, -1.0], [-t, 0.0, 1.0],
+        ].iter().map(|&point| Vector3::from(point).normalize()).collect();
+        let mut triangles: Vec<[usize; 3]> = vec![
+            [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+            [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+            [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+            [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            if points.len() + triangles.len() * 3 / 2 > MAX_VERTICES {
+                return Err(format!("icosphere with {} subdivisions has too many vertices", subdivisions));
+            }
+            // each edge is split once and the midpoint shared by both triangles on it
+            let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+            let mut midpoint = |a: usize, b: usize, points: &mut Vec<Vector3<f32>>| {
+                *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    points.push((points[a] + points[b]).normalize());
+                    points.len() - 1
+                })
+            };
+            triangles = triangles.iter()
+                .flat_map(|&[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b, &mut points), midpoint(b, c, &mut points), midpoint(c, a, &mut points));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        // spherical UVs need their own vertices where a triangle crosses the seam or touches a pole
+        let mut mesh = MeshBuilder::new("icosphere");
+        let mut shared: HashMap<([u32; 3], [u32; 2]), u32> = HashMap::new();
+        for triangle in &triangles {
+            let mut uvs = triangle.map(|point| {
+                let p = points[point];
+                [0.5 + p.z.atan2(p.x) / TAU, p.y.clamp(-1.0, 1.0).acos() / PI]
+            });
+            // the triangle lies on the far side of the widest gap between its longitudes; when that gap isn't the
+            // one across the seam, the corners below it move past u = 1. Points right on the seam may be 0 or 1
+            let mut order = [0, 1, 2];
+            order.sort_by(|&a, &b| uvs[a][0].total_cmp(&uvs[b][0]));
+            let u = order.map(|corner| uvs[corner][0]);
+            let gaps = [u[1] - u[0], u[2] - u[1], u[0] + 1.0 - u[2]];
+            let moved = if gaps[0] > gaps[2] && gaps[0] >= gaps[1] {
+                1
+            } else if gaps[1] > gaps[2] && gaps[1] > gaps[0] {
+                2
+            } else {
+                0
+            };
+            for &corner in &order[..moved] {
+                uvs[corner][0] += 1.0;
+            }
+            for corner in 0..3 {
+                let p = points[triangle[corner]];
+                if p.x.abs() < 1e-6 && p.z.abs() < 1e-6 {
+                    // longitude is undefined at a pole, use the middle of the other two corners
+                    uvs[corner][0] = (uvs[(corner + 1) % 3][0] + uvs[(corner + 2) % 3][0]) / 2.0;
+                }
+            }
+            for polygon in split_at_seam(triangle.map(|point| (point, points[point])), uvs) {
+                let indices: Vec<u32> = polygon.iter()
+                    .map(|&(position, uv)| {
+                        let key: [f32; 3] = position.into();
+                        *shared.entry((key.map(f32::to_bits), uv.map(f32::to_bits))).or_insert_with(|| {
+                            mesh.push(vertex(position * 0.5, position.normalize(), uv))
+                        })
+                    })
+                    .collect();
+                for i in 1..indices.len() - 1 {
+                    mesh.indices.extend([indices[0], indices[i], indices[i + 1]]);
+                }
+            }
+        }
+        mesh.finish()
+    }
+
+    /// Capped cylinder along Y, radius 0.5 and height 1
+    pub fn cylinder(segments: u32) -> Result<Self, String> {
+        let mut mesh = MeshBuilder::new("cylinder");
+        mesh.grid(segments, 1, |column, row| {
+            let u = column as f32 / segments as f32;
+            let normal = Vector3::new((u * TAU).cos(), 0.0, (u * TAU).sin());
+            let y = 0.5 - row as f32;
+            vertex(normal * 0.5 + Vector3::unit_y() * y, normal, [u, row as f32])
+        })?;
+        mesh.disc(segments, 0.5, 1.0)?;
+        mesh.disc(segments, -0.5, -1.0)?;
+        mesh.finish()
+    }
+
+    /// Cone along Y with its tip at the top, base radius 0.5 and height 1
+    pub fn cone(segments: u32) -> Result<Self, String> {
+        let (radius, height) = (0.5, 1.0);
+        let mut mesh = MeshBuilder::new("cone");
+        mesh.grid(segments, 1, |column, row| {
+            let u = column as f32 / segments as f32;
+            let (cos, sin) = ((u * TAU).cos(), (u * TAU).sin());
+            // the slope tilts the side normals up by radius/height
+            let normal = Vector3::new(cos * height, radius, sin * height).normalize();
+            let ring_radius = radius * row as f32;
+            vertex(Vector3::new(cos * ring_radius, 0.5 - row as f32 * height, sin * ring_radius), normal, [u, row as f32])
+        })?;
+        mesh.disc(segments, -0.5, -1.0)?;
+        mesh.finish()
+    }
+
+    /// Ring in the XZ plane; `tube` is the tube radius and the outer edge stays at 0.5
+    pub fn torus(segments: u32, sides: u32, tube: f32) -> Result<Self, String> {
+        let ring_radius = 0.5 - tube;
+        let mut mesh = MeshBuilder::new("torus");
+        mesh.grid(segments, sides, |column, row| {
+            let (u, v) = (column as f32 / segments as f32, row as f32 / sides as f32);
+            let (around, across) = (u * TAU, v * TAU);
+            let outward = Vector3::new(around.cos(), 0.0, around.sin());
+            let normal = outward * across.cos() + Vector3::unit_y() * across.sin();
+            vertex(outward * ring_radius + normal * tube, normal, [u, v])
+        })?;
+        mesh.finish()
+    }
+
+    /// Cylinder with hemispherical ends along Y, 1 tall overall; `rings` is per hemisphere
+    pub fn capsule(segments: u32, rings: u32, radius: f32) -> Result<Self, String> {
+        let half_length = 0.5 - radius;
+        let mut mesh = MeshBuilder::new("capsule");
+        // rows 0..=rings cover the top cap, the rest the bottom cap; the cell between them is the straight part
+        let rows = rings.checked_mul(2).and_then(|rows| rows.checked_add(1)).ok_or_else(|| too_many_vertices("capsule"))?;
+        mesh.grid(segments, rows, |column, row| {
+            let u = column as f32 / segments as f32;
+            let (polar, center) = if row <= rings {
+                (row as f32 / rings as f32 * PI / 2.0, half_length)
+            } else {
+                (PI / 2.0 + (row - rings - 1) as f32 / rings as f32 * PI / 2.0, -half_length)
+            };
+            let normal = sphere_direction(u * TAU, polar);
+            let position = normal * radius + Vector3::unit_y() * center;
+            vertex(position, normal, [u, 0.5 - position.y])
+        })?;
+        mesh.finish()
+    }
+
+    /// Flat square in the XZ plane facing +Y, 1 wide, split into subdivisions x subdivisions cells
+    pub fn subdivided_plane(subdivisions: u32) -> Result<Self, String> {
+        let mut mesh = MeshBuilder::new("plane");
+        mesh.grid(subdivisions, subdivisions, |column, row| {
+            let (u, v) = (column as f32 / subdivisions as f32, row as f32 / subdivisions as f32);
+            vertex(Vector3::new(u - 0.5, 0.0, v - 0.5), Vector3::unit_y(), [u, v])
+        })?;
+        mesh.finish()
+    }
+
+    /// Unit cube whose edges and corners are rounded with `radius`, using `segments` steps per rounded edge
+    pub fn rounded_box(radius: f32, segments: u32) -> Result<Self, String> {
+        let inner = 0.5 - radius;
+        // six faces of (2 * segments + 2)^2 vertices; checked before the steps below are allocated
+        let cells = segments.checked_mul(2).and_then(|cells| cells.checked_add(1));
+        if cells.and_then(|cells| grid_vertex_count(cells, cells)).and_then(|face| face.checked_mul(6)).is_none_or(|count| count > MAX_VERTICES) {
+            return Err(too_many_vertices("rounded_box"));
+        }
+        // coordinates along a face: the rounded band, then straight across, then the other band
+        let steps: Vec<f32> = (0..=segments)
+            .map(|step| -0.5 + radius * step as f32 / segments as f32)
+            .chain((0..=segments).map(|step| inner + radius * step as f32 / segments as f32))
+            .collect();
+        let cells = steps.len() as u32 - 1;
+
+        let mut mesh = MeshBuilder::new("rounded_box");
+        for axis in 0..3 {
+            for side in [1.0f32, -1.0] {
+                let (first, second) = ((axis + 1) % 3, (axis + 2) % 3);
+                let mut face_normal = Vector3::new(0.0, 0.0, 0.0);
+                face_normal[axis] = side;
+                mesh.grid(cells, cells, |column, row| {
+                    // point on the plain cube, pulled onto the rounded surface around the inner box
+                    let mut point = face_normal * 0.5;
+                    point[first] = steps[column as usize];
+                    point[second] = steps[row as usize];
+                    let core = point.map(|coordinate| coordinate.clamp(-inner, inner));
+                    let offset = point - core;
+                    let normal = if offset.magnitude2() > 0.0 { offset.normalize() } else { face_normal };
+                    vertex(core + normal * radius, normal, [point[first] + 0.5, point[second] + 0.5])
+                })?;
+            }
+        }
+        mesh.finish()
+    }
+}
+
+// unit vector at `around` radians about +Y and `polar` radians down from +Y
+fn sphere_direction(around: f32, polar: f32) -> Vector3<f32> {
+    Vector3::new(polar.sin() * around.cos(), polar.cos(), polar.sin() * around.sin())
+}
+
+// an icosphere triangle whose u was moved past 1 to cross the seam, cut along u = 1 into the part before it
+// and the part after it (moved back by 1), so every u stays within 0..1 and the texture is sampled as before.
+// Cut points are interpolated between the edge's points in index order, so both triangles on an edge agree
+fn split_at_seam(corners: [(usize, Vector3<f32>); 3], uvs: [[f32; 2]; 3]) -> Vec<Vec<(Vector3<f32>, [f32; 2])>> {
+    if uvs.iter().all(|uv| uv[0] <= 1.0) {
+        return vec![corners.iter().zip(uvs).map(|(&(_, position), uv)| (position, uv)).collect()];
+    }
+    [false, true].into_iter()
+        .map(|after| {
+            let mut polygon = Vec::new();
+            for corner in 0..3 {
+                let next = (corner + 1) % 3;
+                let u = uvs[corner][0];
+                if (after && u >= 1.0) || (!after && u <= 1.0) {
+                    polygon.push((corners[corner].1, uvs[corner]));
+                }
+                if (u - 1.0) * (uvs[next][0] - 1.0) < 0.0 {
+                    let (a, b) = if corners[corner].0 < corners[next].0 { (corner, next) } else { (next, corner) };
+                    let t = (1.0 - uvs[a][0]) / (uvs[b][0] - uvs[a][0]);
+                    let position = corners[a].1 + (corners[b].1 - corners[a].1) * t;
+                    polygon.push((position, [1.0, uvs[a][1] + (uvs[b][1] - uvs[a][1]) * t]));
+                }
+            }
+            if after {
+                polygon.iter_mut().for_each(|(_, uv)| uv[0] -= 1.0);
+            }
+            polygon
+        })
+        .filter(|polygon| polygon.len() >= 3)
+        .collect()
+}
+
+// vertices in a (columns + 1) x (rows + 1) grid; None when that doesn't even fit in a usize
+fn grid_vertex_count(columns: u32, rows: u32) -> Option<usize> {
+    (columns as usize).checked_add(1)?.checked_mul((rows as usize).checked_add(1)?)
+}
+
+fn too_many_vertices(name: &str) -> String {
+    format!("{} has too many vertices, at most {} are supported", name, MAX_VERTICES)
+}
+
+fn vertex(position: Vector3<f32>, normal: Vector3<f32>, uv: [f32; 2]) -> Vertex {
+    Vertex {
+        position: position.into(),
+        color: [1.0, 1.0, 1.0],
+        normal: normal.into(),
+        uv,
+    }
+}
+
+struct MeshBuilder {
+    name: &'static str,
+    vertices: Vec<Vertex>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuilder {
+    fn new(name: &'static str) -> Self {
+        MeshBuilder { name, vertices: Vec::new(), indices: Vec::new() }
+    }
+
+    // fails before anything is allocated if `count` more vertices would go over the u16 limit
+    fn reserve(&mut self, count: Option<usize>) -> Result<(), String> {
+        match count.and_then(|count| count.checked_add(self.vertices.len())) {
+            Some(total) if total <= MAX_VERTICES => {
+                self.vertices.reserve(total - self.vertices.len());
+                Ok(())
+            }
+            _ => Err(too_many_vertices(self.name)),
+        }
+    }
+
+    fn push(&mut self, vertex: Vertex) -> u32 {
+        self.vertices.push(vertex);
+        self.vertices.len() as u32 - 1
+    }
+
+    // (columns + 1) x (rows + 1) vertices, two triangles per cell
+    fn grid(&mut self, columns: u32, rows: u32, mut vertex: impl FnMut(u32, u32) -> Vertex) -> Result<(), String> {
+        self.reserve(grid_vertex_count(columns, rows))?;
+        let first = self.vertices.len() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                self.vertices.push(vertex(column, row));
+            }
+        }
+        let index = |column: u32, row: u32| first + row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                let (a, b) = (index(column, row), index(column + 1, row));
+                let (c, d) = (index(column, row + 1), index(column + 1, row + 1));
+                self.indices.extend_from_slice(&[a, c, b, b, c, d]);
+            }
+        }
+        Ok(())
+    }
+
+    // flat cap of radius 0.5 at height y, facing up (1) or down (-1)
+    fn disc(&mut self, segments: u32, y: f32, facing: f32) -> Result<(), String> {
+        self.reserve((segments as usize).checked_add(1))?;
+        let normal = Vector3::unit_y() * facing;
+        let center = self.push(vertex(Vector3::unit_y() * y, normal, [0.5, 0.5]));
+        let first = self.vertices.len() as u32;
+        for segment in 0..segments {
+            let angle = segment as f32 / segments as f32 * TAU;
+            let (cos, sin) = (angle.cos(), angle.sin());
+            self.push(vertex(Vector3::new(cos * 0.5, y, sin * 0.5), normal, [0.5 + cos * 0.5, 0.5 + sin * 0.5]));
+        }
+        for segment in 0..segments {
+            self.indices.extend_from_slice(&[center, first + segment, first + (segment + 1) % segments]);
+        }
+        Ok(())
+    }
+
+    // wind every triangle counter-clockwise as seen from where its normals point, then check the u16 limit
+    fn finish(mut self) -> Result<ObjectGeometry, String> {
+        if self.vertices.len() > MAX_VERTICES {
+            return Err(too_many_vertices(self.name));
+        }
+        for triangle in self.indices.chunks_exact_mut(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| &self.vertices[triangle[corner] as usize]);
+            let position = |vertex: &Vertex| Vector3::from(vertex.position);
+            let face = (position(b) - position(a)).cross(position(c) - position(a));
+            let normals = Vector3::from(a.normal) + Vector3::from(b.normal) + Vector3::from(c.normal);
+            if face.dot(normals) < 0.0 {
+                triangle.swap(1, 2);
+            }
+        }
+        Ok(ObjectGeometry {
+            name: self.name.to_string(),
+            vertices: self.vertices,
+            indices: self.indices.into_iter().map(|index| index as u16).collect(),
+            source_files: Vec::new(),
+            material: None,
+        })
+    }
+}
+
+// `key=value` parameters after the primitive's kind
+struct Params<'a> {
+    kind: &'a str,
+    values: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Params<'a> {
+    fn parse(kind: &'a str, words: impl Iterator<Item = &'a str>) -> Result<Self, String> {
+        let values = words
+            .map(|word| word.split_once('=').ok_or_else(|| format!("expected key=value in {}, got '{}'", kind, word)))
+            .collect::<Result<_, _>>()?;
+        Ok(Params { kind, values })
+    }
+
+    fn only(&self, known: &[&str]) -> Result<(), String> {
+        match self.values.iter().find(|(key, _)| !known.contains(key)) {
+            Some((key, _)) => Err(format!("unknown {} parameter '{}', expected {}", self.kind, key, known.join(", "))),
+            None => Ok(()),
+        }
+    }
+
+    fn value(&self, key: &str) -> Option<&'a str> {
+        self.values.iter().rev().find(|(name, _)| *name == key).map(|(_, value)| *value)
+    }
+
+    fn count(&self, key: &str, default: u32, min: u32) -> Result<u32, String> {
+        match self.value(key) {
+            None => Ok(default),
+            Some(value) => value.parse().ok()
+                .filter(|&count| count >= min)
+                .ok_or_else(|| format!("{} {} must be a whole number of at least {}, got '{}'", self.kind, key, min, value)),
+        }
+    }
+
+    // `above` is excluded: a zero tube or radius would collapse the shape
+    fn number(&self, key: &str, default: f32, above: f32, max: f32) -> Result<f32, String> {
+        match self.value(key) {
+            None => Ok(default),
+            Some(value) => value.parse().ok()
+                .filter(|&number| number > above && number <= max)
+                .ok_or_else(|| format!("{} {} must be a number above {} and at most {}, got '{}'", self.kind, key, above, max, value)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_primitives_are_rejected_before_building() {
+        for spec in [
+            "sphere segments=100000 rings=100000",
+            "sphere segments=4294967295 rings=4294967295",
+            "capsule segments=4294967295 rings=4294967295",
+            "cylinder segments=4294967295",
+            "rounded_box segments=4294967295",
+            "plane subdivisions=4294967295",
+        ] {
+            let error = ObjectGeometry::from_primitive(spec).err().unwrap_or_else(|| panic!("'{}' was built", spec));
+            assert!(error.contains("too many vertices"), "'{}': {}", spec, error);
+        }
+        assert!(ObjectGeometry::from_primitive("sphere segments=64 rings=32").is_ok());
+    }
+
+    #[test]
+    fn degenerate_sizes_are_rejected() {
+        for spec in ["torus tube=0", "torus tube=-0.1", "torus tube=0.3", "capsule radius=0", "capsule radius=NaN", "rounded_box radius=0", "rounded_box radius=0.6"] {
+            assert!(ObjectGeometry::from_primitive(spec).is_err(), "'{}' was built", spec);
+        }
+        for spec in ["torus tube=0.25", "capsule radius=0.5", "rounded_box radius=0.5", "torus tube=0.001"] {
+            assert!(ObjectGeometry::from_primitive(spec).is_ok(), "'{}' was rejected", spec);
+        }
+    }
+
+    #[test]
+    fn normals_are_unit_length_and_point_outward() {
+        for spec in [
+            "sphere", "sphere segments=3 rings=2", "icosphere", "icosphere subdivisions=0", "cylinder", "cone segments=3",
+            "torus", "torus tube=0.2", "capsule", "capsule radius=0.5 rings=1", "plane subdivisions=3",
+            "rounded_box", "rounded_box radius=0.5 segments=1",
+        ] {
+            let geometry = ObjectGeometry::from_primitive(spec).unwrap();
+            // direction from the inside of the surface; around the tube's center circle for a torus
+            let tube = spec.starts_with("torus").then(|| 0.5 - spec.split_once("tube=").map_or(0.15, |(_, tube)| tube.parse().unwrap()));
+            let outward = |position: Vector3<f32>| match tube {
+                Some(ring_radius) => position - Vector3::new(position.x, 0.0, position.z).normalize() * ring_radius,
+                None if spec.starts_with("plane") => Vector3::unit_y(),
+                None => position,
+            };
+
+            for vertex in &geometry.vertices {
+                let normal = Vector3::from(vertex.normal);
+                assert!((normal.magnitude() - 1.0).abs() < 1e-4, "'{}': normal {:?}", spec, vertex.normal);
+                assert!(normal.dot(outward(vertex.position.into())) > 0.0, "'{}': normal {:?} at {:?}", spec, vertex.normal, vertex.position);
+                assert!(vertex.uv.iter().all(|uv| (0.0..=1.0).contains(uv)), "'{}': uv {:?}", spec, vertex.uv);
+            }
+            // the winding faces the same way as the normals
+            for triangle in geometry.indices.chunks_exact(3) {
+                let [a, b, c] = [0, 1, 2].map(|corner| Vector3::from(geometry.vertices[triangle[corner] as usize].position));
+                let face = (b - a).cross(c - a);
+                if face.magnitude2() > 1e-12 {
+                    assert!(face.dot(outward((a + b + c) / 3.0)) > 0.0, "'{}': triangle {:?} faces inward", spec, [a, b, c]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn icosphere_seam_splits_keep_the_surface_closed() {
+        for subdivisions in 0..4 {
+            let geometry = ObjectGeometry::icosphere(subdivisions).unwrap();
+            // every edge, by position, is shared by exactly two triangles
+            let mut edges: HashMap<[[u32; 3]; 2], usize> = HashMap::new();
+            for triangle in geometry.indices.chunks_exact(3) {
+                let corners = [0, 1, 2].map(|corner| geometry.vertices[triangle[corner] as usize].position.map(f32::to_bits));
+                for corner in 0..3 {
+                    let (a, b) = (corners[corner], corners[(corner + 1) % 3]);
+                    *edges.entry([a.min(b), a.max(b)]).or_default() += 1;
+                }
+            }
+            assert!(edges.values().all(|&count| count == 2), "icosphere subdivisions={} has open edges", subdivisions);
+            // and none stretches the texture across the seam
+            for triangle in geometry.indices.chunks_exact(3) {
+                let u = [0, 1, 2].map(|corner| geometry.vertices[triangle[corner] as usize].uv[0]);
+                let span = u.iter().copied().fold(f32::MIN, f32::max) - u.iter().copied().fold(f32::MAX, f32::min);
+                assert!(span <= 0.5, "icosphere subdivisions={} has a triangle with u {:?}", subdivisions, u);
+            }
+        }
+    }
+}
@@ -1,15 +1,31 @@
 use std::sync::Arc;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use wgpu::{PrimitiveTopology, ShaderModuleDescriptor, util::DeviceExt};
 use winit::window::Window;
 
 use crate::vertex::Vertex;
-use crate::camera::{Camera, CameraController, CameraUniform};
-use crate::input::Input;
-use crate::scene::Scene;
+use crate::camera::{Camera, CameraController, CameraUniform, ViewPreset};
+use crate::gamepad::Gamepads;
+use crate::input::{Action, InputBindings, Input, BINDINGS_FILE};
+use crate::material::Material;
+use crate::replay::{self, CameraPose, Recorder, Replay};
+use crate::object::ObjectGeometry;
+use crate::scene::{Light, ObjectInstance, Scene, EMISSIVE_LIGHT_INTENSITY, MAX_POINT_LIGHTS};
+use crate::shader::{ShaderLoader, StructLayout};
+use crate::transform::Transform;
+use crate::watcher::{self, AssetWatcher};
 use crate::texture::Texture;
 
-const MAX_POINT_LIGHTS: usize = 8;
+// longest frame time (seconds) fed to the camera controller
+const MAX_FRAME_TIME: f32 = 0.1;
+// set to 1 to use a reversed-Z depth buffer with an infinite far plane
+const REVERSED_Z_ENV: &str = "ARBITRA_REVERSED_Z";
+pub const ASSETS_ROOT: &str = "assets";
+pub const DEFAULT_SCENE: &str = "assets/scenes/sample.arsc";
+// asset types picked up by hot reload
+const WATCHED_EXTENSIONS: [&str; 11] = ["arsc", "arobj", "armat", "obj", "gltf", "glb", "bin", "ply", "stl", "png", "arbind"];
 
 // single point light (position + color + intensity)
 #[repr(C)]
@@ -43,13 +59,125 @@ struct LightUniform {
     _padding3: [f32; 2],
 }
 
-// per-instance model matrix + emissive strength
+impl LightUniform {
+    fn from_light(light: &Light) -> Self {
+        LightUniform {
+            direction: light.direction,
+            _padding1: 0.0,
+            color: light.color,
+            _padding2: 0.0,
+            intensity: light.intensity,
+            ambient_strength: light.ambient_strength,
+            _padding3: [0.0; 2],
+        }
+    }
+}
+
+// per-instance model matrix, emissive strength and the material's vertex color mode and base
+// color, streamed as an instance-rate vertex buffer
 #[repr(C)]
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
-struct ModelUniform {
+struct InstanceRaw {
     model: [[f32; 4]; 4],
     emissive: f32,
-    _padding: [f32; 3],
+    vertex_color_mode: u32,
+    base_color: [f32; 4],
+}
+
+impl InstanceRaw {
+    fn from_instance(instance: &ObjectInstance, world: cgmath::Matrix4<f32>) -> Self {
+        InstanceRaw {
+            model: world.into(),
+            emissive: instance.emissive,
+            vertex_color_mode: instance.material.vertex_color.shader_value(),
+            base_color: instance.material.base_color,
+        }
+    }
+
+    // locations 4-7 hold the model matrix columns, 8 the emissive strength, 9 the vertex color mode,
+    // 10 the base color; attribute offsets follow the field order
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        const ATTRIBUTES: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
+            4 => Float32x4,
+            5 => Float32x4,
+            6 => Float32x4,
+            7 => Float32x4,
+            8 => Float32,
+            9 => Uint32,
+            10 => Float32x4,
+        ];
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<InstanceRaw>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &ATTRIBUTES,
+        }
+    }
+}
+
+// depth buffer format, test and clear value; reversed-Z trades the usual 1 = far for 0 = far,
+// which with a float buffer spreads precision evenly over distance
+#[derive(Debug, Clone, Copy)]
+struct DepthConfig {
+    format: wgpu::TextureFormat,
+    compare: wgpu::CompareFunction,
+    clear: f32,
+}
+
+impl DepthConfig {
+    fn new(reversed_z: bool) -> Self {
+        if reversed_z {
+            DepthConfig {
+                format: wgpu::TextureFormat::Depth32Float,
+                compare: wgpu::CompareFunction::Greater,
+                clear: 0.0,
+            }
+        } else {
+            DepthConfig {
+                format: wgpu::TextureFormat::Depth24Plus,
+                compare: wgpu::CompareFunction::Less, // closer objects pass depth test
+                clear: 1.0,
+            }
+        }
+    }
+}
+
+// Rust mirrors of the uniform structs in shaders/structs.wgsl, checked whenever a shader is built
+fn gpu_struct_layouts() -> Vec<StructLayout> {
+    use std::mem::{offset_of, size_of};
+    vec![
+        StructLayout {
+            name: "CameraUniform",
+            size: size_of::<CameraUniform>(),
+            members: vec![("view_proj", offset_of!(CameraUniform, view_proj))],
+        },
+        StructLayout {
+            name: "Light",
+            size: size_of::<LightUniform>(),
+            members: vec![
+                ("direction", offset_of!(LightUniform, direction)),
+                ("color", offset_of!(LightUniform, color)),
+                ("intensity", offset_of!(LightUniform, intensity)),
+                ("ambient_strength", offset_of!(LightUniform, ambient_strength)),
+            ],
+        },
+        StructLayout {
+            name: "PointLight",
+            size: size_of::<PointLight>(),
+            members: vec![
+                ("position", offset_of!(PointLight, position)),
+                ("intensity", offset_of!(PointLight, intensity)),
+                ("color", offset_of!(PointLight, color)),
+            ],
+        },
+        StructLayout {
+            name: "PointLights",
+            size: size_of::<PointLightsUniform>(),
+            members: vec![
+                ("lights", offset_of!(PointLightsUniform, lights)),
+                ("count", offset_of!(PointLightsUniform, count)),
+            ],
+        },
+    ]
 }
 
 // GPU buffers for a geometry (vertex buffer, index buffer, index count)
@@ -59,47 +187,88 @@ struct GeometryBuffers {
     num_indices: u32,
 }
 
-// per-instance data (separate buffers prevent GPU write conflicts)
-struct InstanceData {
-    #[allow(dead_code)]
-    model_buffer: wgpu::Buffer,
-    model_bind_group: wgpu::BindGroup,
+// instances sharing geometry + albedo texture, drawn with a single instanced call
+struct InstanceBatch {
+    geometry_name: String,
+    instance_indices: Vec<usize>,
+    instance_buffer: wgpu::Buffer,
+    instance_count: u32,
     texture_bind_group: wgpu::BindGroup,
 }
 
+// where frames go: the window's swapchain, or a texture for headless runs
+enum RenderTarget {
+    Window {
+        window: Arc<Window>,
+        surface: wgpu::Surface<'static>,
+    },
+    // COPY_SRC, so frames can be read back and saved
+    Offscreen {
+        texture: wgpu::Texture,
+    },
+}
+
+// a camera path being played back; elapsed is in seconds since it started
+struct PathPlayback {
+    index: usize,
+    elapsed: f32,
+    // offline renders stop at the last key even if the path loops
+    play_once: bool,
+}
+
 pub struct State {
-    pub window: Arc<Window>,
+    target: RenderTarget,
     device: wgpu::Device,
     queue: wgpu::Queue,
     size: winit::dpi::PhysicalSize<u32>,
-    surface: wgpu::Surface<'static>,
+    // color format of the render target
     surface_format: wgpu::TextureFormat,
     render_pipeline: wgpu::RenderPipeline,
+    render_pipeline_layout: wgpu::PipelineLayout,
+    shader_loader: ShaderLoader,
+    shader_watcher: Option<AssetWatcher>,
     scene: Scene,
+    scene_path: String,
+    asset_watcher: Option<AssetWatcher>,
     geometry_buffers: HashMap<String, GeometryBuffers>,
-    instance_data: Vec<InstanceData>,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    texture_bind_groups: HashMap<String, wgpu::BindGroup>,
+    instance_batches: Vec<InstanceBatch>,
+    // set by the scene mutation API, consumed by sync_scene at the start of a frame
+    batches_dirty: bool,
+    // instances moved or reparented since; only the batches holding them or their descendants are rewritten
+    moved_instances: Vec<usize>,
     camera: Camera,
     camera_uniform: CameraUniform,
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
 
-    #[allow(dead_code)]
     light_buffer: wgpu::Buffer,
-    #[allow(dead_code)]
     point_lights_buffer: wgpu::Buffer,
     light_bind_group: wgpu::BindGroup,
 
-    #[allow(dead_code)]
-    model_bind_group_layout: wgpu::BindGroupLayout,
     camera_controller: CameraController,
     pub input: Input,
+    gamepads: Option<Gamepads>,
+    recorder: Option<Recorder>,
+    // while set, input and frame times come from the recording instead of the devices
+    replay: Option<Replay>,
+    path_playback: Option<PathPlayback>,
+    // path started by the next play_camera_path press
+    next_camera_path: usize,
+    // set for offline rendering, replaces the measured frame time
+    fixed_frame_time: Option<f32>,
+    // instance framed by the orbit camera, cycled with Tab
+    selected_instance: usize,
     frame_count: u32,
+    last_frame: Instant,
+    depth_config: DepthConfig,
     depth_texture: wgpu::Texture,
     depth_texture_view: wgpu::TextureView,
 }
 
 impl State {
-    fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32) -> (wgpu::Texture, wgpu::TextureView) {
+    fn create_depth_texture(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) -> (wgpu::Texture, wgpu::TextureView) {
         let size = wgpu::Extent3d {
             width,
             height,
@@ -111,7 +280,7 @@ impl State {
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth24Plus,
+            format,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
             view_formats: &[],
         };
@@ -120,7 +289,200 @@ impl State {
         (texture, view)
     }
 
+    fn create_geometry_buffers(device: &wgpu::Device, geom_name: &str, geometry: &ObjectGeometry) -> GeometryBuffers {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Vertex Buffer", geom_name)),
+            contents: bytemuck::cast_slice(&geometry.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some(&format!("{} Index Buffer", geom_name)),
+            contents: bytemuck::cast_slice(&geometry.indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        GeometryBuffers {
+            vertex_buffer,
+            index_buffer,
+            num_indices: geometry.indices.len() as u32,
+        }
+    }
+
+    // emissive instances become point lights (first MAX_POINT_LIGHTS only)
+    fn collect_point_lights(scene: &Scene) -> PointLightsUniform {
+        let mut point_lights = [PointLight {
+            position: [0.0; 3],
+            intensity: 0.0,
+            color: [0.0; 3],
+            _padding: 0.0,
+        }; MAX_POINT_LIGHTS];
+
+        let mut point_light_count = 0;
+        let emissive_instances = scene.instances.iter()
+            .enumerate()
+            .filter(|(_, instance)| instance.emissive > 0.0)
+            .take(MAX_POINT_LIGHTS);
+        for (index, instance) in emissive_instances {
+            point_lights[point_light_count] = PointLight {
+                position: scene.world_position(index),
+                intensity: instance.emissive * EMISSIVE_LIGHT_INTENSITY,
+                color: instance.emissive_color,
+                _padding: 0.0,
+            };
+            point_light_count += 1;
+        }
+
+        PointLightsUniform {
+            lights: point_lights,
+            count: point_light_count as u32,
+            _padding: [0.0; 3],
+        }
+    }
+
+    fn create_render_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        surface_format: wgpu::TextureFormat,
+        depth_config: DepthConfig,
+        shader_source: &str,
+    ) -> wgpu::RenderPipeline {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("shader"),
+            source: wgpu::ShaderSource::Wgsl(shader_source.into())
+        });
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("render_pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState { module: (&shader), entry_point: (Some("vs_main")), compilation_options: (Default::default()), buffers: (&[Vertex::desc(), InstanceRaw::desc()]) },
+            primitive: wgpu::PrimitiveState {topology: PrimitiveTopology::TriangleList, strip_index_format: None, front_face: wgpu::FrontFace::Ccw, cull_mode: None, unclipped_depth: false, polygon_mode: wgpu::PolygonMode::Fill, conservative: false},
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: depth_config.format,
+                depth_write_enabled: true,
+                depth_compare: depth_config.compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState { count: (1), mask: (!0), alpha_to_coverage_enabled: (false) },
+            fragment: Some(wgpu::FragmentState {module: &shader, entry_point: Some("fs_main"), compilation_options: Default::default(), targets: &[Some(wgpu::ColorTargetState {format: surface_format, blend: Some(wgpu::BlendState::REPLACE), write_mask: wgpu::ColorWrites::ALL})]}),
+            multiview: None,
+            cache: None,
+        })
+    }
+
+    // loads an albedo texture (relative to assets/) and wraps it in a bind group, white on failure
+    fn create_texture_bind_group(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+        albedo_texture: &str,
+    ) -> wgpu::BindGroup {
+        let texture = Texture::from_file(device, queue, &format!("{}/{}", ASSETS_ROOT, albedo_texture))
+            .unwrap_or_else(|e| {
+                eprintln!("Failed to load texture '{}': {}. Using white.", albedo_texture, e);
+                Texture::create_white_texture(device, queue)
+            });
+
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&texture.sampler),
+                }
+            ],
+            label: Some(&format!("{} Texture Bind Group", albedo_texture)),
+        })
+    }
+
+    // groups instances by (geometry, albedo texture) and packs each group into one instance buffer
+    fn build_instance_batches(
+        device: &wgpu::Device,
+        scene: &Scene,
+        texture_bind_groups: &HashMap<String, wgpu::BindGroup>,
+    ) -> Vec<InstanceBatch> {
+        let mut batch_keys: Vec<(&str, &str)> = Vec::new();
+        let mut batch_instances: HashMap<(&str, &str), Vec<usize>> = HashMap::new();
+        for (idx, instance) in scene.instances.iter().enumerate() {
+            let key = (instance.geometry_name.as_str(), instance.material.albedo_texture.as_str());
+            batch_instances.entry(key)
+                .or_insert_with(|| {
+                    batch_keys.push(key);
+                    Vec::new()
+                })
+                .push(idx);
+        }
+
+        let world_matrices = scene.world_matrices();
+        batch_keys.into_iter()
+            .filter_map(|key| {
+                let texture_bind_group = texture_bind_groups.get(key.1)?;
+                let instance_indices = batch_instances.remove(&key)?;
+                let instances: Vec<InstanceRaw> = instance_indices.iter()
+                    .map(|&idx| InstanceRaw::from_instance(&scene.instances[idx], world_matrices[idx]))
+                    .collect();
+                let instance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some(&format!("{} Instance Buffer", key.0)),
+                    contents: bytemuck::cast_slice(&instances),
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                });
+                Some(InstanceBatch {
+                    geometry_name: key.0.to_string(),
+                    instance_indices,
+                    instance_buffer,
+                    instance_count: instances.len() as u32,
+                    texture_bind_group: texture_bind_group.clone(),
+                })
+            })
+            .collect()
+    }
+
+    fn create_offscreen_texture(device: &wgpu::Device, format: wgpu::TextureFormat, width: u32, height: u32) -> wgpu::Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("offscreen_target"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        })
+    }
+
+    // a recording that fails to load leaves the session live
+    fn load_replay() -> Option<Replay> {
+        let path = std::env::var(replay::REPLAY_ENV).ok()?;
+        Replay::load(&path)
+            .map_err(|e| eprintln!("{:#}. Using live input.", e))
+            .ok()
+    }
+
     pub async fn new(window: Arc<Window>) -> State {
+        let size = window.inner_size();
+        Self::create(Some(window), size, Self::load_replay()).await
+    }
+
+    // render into an offscreen texture without replaying anything, e.g. for camera paths
+    pub async fn new_offscreen(width: u32, height: u32) -> State {
+        Self::create(None, winit::dpi::PhysicalSize::new(width, height), None).await
+    }
+
+    // render into an offscreen texture, driven by the recording in ARBITRA_REPLAY
+    pub async fn new_headless() -> Result<State, String> {
+        let path = std::env::var(replay::REPLAY_ENV)
+            .map_err(|_| format!("Headless mode needs a recording in {}", replay::REPLAY_ENV))?;
+        let replay = Replay::load(&path).map_err(|e| format!("{:#}", e))?;
+        let size = winit::dpi::PhysicalSize::new(replay.size.0, replay.size.1);
+        Ok(Self::create(None, size, Some(replay)).await)
+    }
+
+    async fn create(window: Option<Arc<Window>>, size: winit::dpi::PhysicalSize<u32>, replay: Option<Replay>) -> State {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions::default())
@@ -131,20 +493,37 @@ impl State {
             .await
             .unwrap();
 
-        let size = window.inner_size();
-
-        let surface = instance.create_surface(window.clone()).unwrap();
-        let cap = surface.get_capabilities(&adapter);
-        let surface_format = cap.formats[0];
+        let headless = window.is_none();
+        let (target, surface_format) = match window {
+            Some(window) => {
+                let surface = instance.create_surface(window.clone()).unwrap();
+                let surface_format = surface.get_capabilities(&adapter).formats[0];
+                (RenderTarget::Window { window, surface }, surface_format)
+            }
+            None => {
+                let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+                let texture = Self::create_offscreen_texture(&device, format, size.width, size.height);
+                (RenderTarget::Offscreen { texture }, format)
+            }
+        };
 
-        let shader = device.create_shader_module(ShaderModuleDescriptor {
-            label: Some("shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/shader.wgsl").into())
+        let shader_loader = ShaderLoader::from_env(gpu_struct_layouts());
+        let shader_source = shader_loader.load()
+            .or_else(|e| {
+                eprintln!("{}\nFalling back to the embedded shader.", e);
+                shader_loader.load_embedded()
+            })
+            .unwrap_or_else(|e| panic!("Embedded shader is invalid:\n{}", e));
+        let shader_watcher = shader_loader.dir().and_then(|dir| {
+            println!("Loading shaders from '{}' with hot reload", dir.display());
+            AssetWatcher::new(dir, &["wgsl"])
+                .map_err(|e| eprintln!("Shader hot reload disabled: {}", e))
+                .ok()
         });
 
         // load the default scene
         // use relative paths from current working directory
-        let scene = Scene::load_from_arsc("assets/scenes/sample.arsc", "assets")
+        let scene = Scene::load_from_arsc(DEFAULT_SCENE, ASSETS_ROOT)
             .expect("Failed to load scene");
 
         println!("Scene loaded: '{}' with {} instances", scene.name, scene.instances.len());
@@ -176,41 +555,21 @@ impl State {
 
         let mut geometry_buffers = HashMap::new();
         for (geom_name, geometry) in &scene.geometries {
-            let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{} Vertex Buffer", geom_name)),
-                contents: bytemuck::cast_slice(&geometry.vertices),
-                usage: wgpu::BufferUsages::VERTEX,
-            });
-
-            let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("{} Index Buffer", geom_name)),
-                contents: bytemuck::cast_slice(&geometry.indices),
-                usage: wgpu::BufferUsages::INDEX,
-            });
+            geometry_buffers.insert(geom_name.clone(), Self::create_geometry_buffers(&device, geom_name, geometry));
+        }
 
-            let num_indices = geometry.indices.len() as u32;
+        let reversed_z = std::env::var(REVERSED_Z_ENV).is_ok_and(|value| value == "1" || value == "true");
+        let depth_config = DepthConfig::new(reversed_z);
+        if reversed_z {
+            println!("Using reversed-Z depth ({:?})", depth_config.format);
+        }
 
-            geometry_buffers.insert(
-                geom_name.clone(),
-                GeometryBuffers {
-                    vertex_buffer,
-                    index_buffer,
-                    num_indices,
-                },
-            );
-        }
-
-        let camera = Camera {
-            eye: (0.0, 1.0, 2.0).into(),
-            target: (0.0, 0.0, 0.0).into(),
-            up: cgmath::Vector3::unit_y(),
-            aspect: size.width as f32 / size.height as f32,
-            fovy: 45.0,
-            znear: 0.1,
-            zfar: 100.0,
-            yaw: -std::f32::consts::FRAC_PI_2,
-            pitch: -0.4,
-        };
+        let mut camera = Camera::new(size.width as f32 / size.height as f32);
+        camera.reversed_z = reversed_z;
+        if let Some(scene_camera) = scene.default_camera() {
+            println!("Starting at camera '{}'", scene_camera.name);
+            camera.apply(scene_camera);
+        }
 
         let mut camera_uniform = CameraUniform::new();
         camera_uniform.update_view_proj(&camera);
@@ -257,15 +616,7 @@ impl State {
         println!("  Intensity: {}", scene.light.intensity);
         println!("  Ambient: {}", scene.light.ambient_strength);
         
-        let light_uniform = LightUniform {
-            direction: scene.light.direction,
-            _padding1: 0.0,
-            color: scene.light.color,
-            _padding2: 0.0,
-            intensity: scene.light.intensity,
-            ambient_strength: scene.light.ambient_strength,
-            _padding3: [0.0; 2],
-        };
+        let light_uniform = LightUniform::from_light(&scene.light);
 
         let light_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("light_buffer"),
@@ -300,34 +651,12 @@ impl State {
         });
 
         // collect point lights from emissive objects
-        let mut point_lights = [PointLight {
-            position: [0.0; 3],
-            intensity: 0.0,
-            color: [0.0; 3],
-            _padding: 0.0,
-        }; MAX_POINT_LIGHTS];
-        
-        let mut point_light_count = 0;
-        for instance in &scene.instances {
-            if instance.emissive > 0.0 && point_light_count < MAX_POINT_LIGHTS {
-                point_lights[point_light_count] = PointLight {
-                    position: instance.transform.position,
-                    intensity: instance.emissive * 5.0,  // scale up for visibility
-                    color: instance.emissive_color,
-                    _padding: 0.0,
-                };
-                point_light_count += 1;
-                println!("Point light {} at {:?} with color {:?}, intensity {}", 
-                    point_light_count, instance.transform.position, instance.emissive_color, instance.emissive * 5.0);
-            }
+        let point_lights_uniform = Self::collect_point_lights(&scene);
+        for (i, light) in point_lights_uniform.lights[..point_lights_uniform.count as usize].iter().enumerate() {
+            println!("Point light {} at {:?} with color {:?}, intensity {}", 
+                i + 1, light.position, light.color, light.intensity);
         }
         
-        let point_lights_uniform = PointLightsUniform {
-            lights: point_lights,
-            count: point_light_count as u32,
-            _padding: [0.0; 3],
-        };
-        
         let point_lights_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("point_lights_buffer"),
             contents: bytemuck::cast_slice(&[point_lights_uniform]),
@@ -349,93 +678,27 @@ impl State {
             label: Some("light_bind_group")
         });
 
-        let model_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[
-                wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }
-            ],
-            label: Some("model_bind_group_layout")
-        });
-
-        let mut instance_data = Vec::new();
+        // one texture bind group per distinct albedo texture, shared by every batch using it
+        let mut texture_bind_groups = HashMap::new();
         for instance in &scene.instances {
-            let model_matrix = instance.transform.to_matrix();
-            let model_matrix_array: &[f32; 16] = model_matrix.as_ref();
-            
-            let model_uniform = ModelUniform {
-                model: [
-                    [model_matrix_array[0], model_matrix_array[1], model_matrix_array[2], model_matrix_array[3]],
-                    [model_matrix_array[4], model_matrix_array[5], model_matrix_array[6], model_matrix_array[7]],
-                    [model_matrix_array[8], model_matrix_array[9], model_matrix_array[10], model_matrix_array[11]],
-                    [model_matrix_array[12], model_matrix_array[13], model_matrix_array[14], model_matrix_array[15]],
-                ],
-                emissive: instance.emissive,
-                _padding: [0.0; 3],
-            };
-            
+            let albedo_texture = &instance.material.albedo_texture;
+            if !texture_bind_groups.contains_key(albedo_texture) {
+                let bind_group = Self::create_texture_bind_group(&device, &queue, &texture_bind_group_layout, albedo_texture);
+                texture_bind_groups.insert(albedo_texture.clone(), bind_group);
+            }
             if instance.emissive > 0.0 {
                 println!("Object '{}' has emissive: {}", instance.name, instance.emissive);
             }
-
-            let model_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some(&format!("model_buffer_{}", instance.name)),
-                contents: bytemuck::cast_slice(&[model_uniform]),
-                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            });
-
-            let model_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &model_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: model_buffer.as_entire_binding(),
-                    }
-                ],
-                label: Some(&format!("model_bind_group_{}", instance.name)),
-            });
-
-            let texture = Texture::from_file(&device, &queue, &format!("assets/{}", instance.material.albedo_texture))
-                .unwrap_or_else(|e| {
-                    eprintln!("Failed to load texture for '{}': {}. Using white.", instance.name, e);
-                    Texture::create_white_texture(&device, &queue)
-                });
-
-            let texture_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-                layout: &texture_bind_group_layout,
-                entries: &[
-                    wgpu::BindGroupEntry {
-                        binding: 0,
-                        resource: wgpu::BindingResource::TextureView(&texture.view),
-                    },
-                    wgpu::BindGroupEntry {
-                        binding: 1,
-                        resource: wgpu::BindingResource::Sampler(&texture.sampler),
-                    }
-                ],
-                label: Some(&format!("{} Texture Bind Group", instance.name)),
-            });
-
-            instance_data.push(InstanceData {
-                model_buffer,
-                model_bind_group,
-                texture_bind_group,
-            });
         }
 
+        let instance_batches = Self::build_instance_batches(&device, &scene, &texture_bind_groups);
+        println!("Grouped {} instances into {} batches", scene.instances.len(), instance_batches.len());
+
         let render_pipeline_layout = device.create_pipeline_layout(
             &wgpu::PipelineLayoutDescriptor {
                 label: Some("render_pipeline_layout"),
                 bind_group_layouts: &[
                     &camera_bind_group_layout,
-                    &model_bind_group_layout,
                     &light_bind_group_layout,
                     &texture_bind_group_layout,
                 ],
@@ -443,40 +706,67 @@ impl State {
             }
         );
 
-        let camera_controller = CameraController::new(0.004);
-        let input = Input::new();
+        // hot reload is best effort: without a watcher the scene simply stays static
+        let asset_watcher = AssetWatcher::new(ASSETS_ROOT, &WATCHED_EXTENSIONS)
+            .map_err(|e| eprintln!("Asset hot reload disabled: {}", e))
+            .ok();
+
+        let camera_controller = CameraController::new(3.0);
+        let mut input = Input::new(Self::load_input_bindings());
+        // keyboard and mouse keep working when no gamepad backend is available
+        let gamepads = if headless {
+            None
+        } else {
+            Gamepads::new()
+                .map_err(|e| eprintln!("Gamepad input disabled: {}", e))
+                .ok()
+        };
+
+        let recorder = match std::env::var(replay::RECORD_ENV) {
+            Ok(_) if replay.is_some() => {
+                eprintln!("{} is ignored while replaying", replay::RECORD_ENV);
+                None
+            }
+            Ok(path) => Recorder::create(&path, DEFAULT_SCENE, (size.width, size.height))
+                .inspect(|_| println!("Recording input to '{}'", path))
+                .map_err(|e| eprintln!("{:#}. Recording disabled.", e))
+                .ok(),
+            Err(_) => None,
+        };
+        if recorder.is_some() {
+            input.start_recording();
+        }
+        if let Some(replay) = &replay {
+            input.set_ignore_live(true);
+            println!("Replaying a {}x{} recording of '{}'", replay.size.0, replay.size.1, replay.scene_path);
+            if replay.scene_path != DEFAULT_SCENE {
+                eprintln!("The recording was made with scene '{}', playback may diverge", replay.scene_path);
+            }
+        }
         
-        let (depth_texture, depth_texture_view) = Self::create_depth_texture(&device, size.width, size.height);
+        let (depth_texture, depth_texture_view) = Self::create_depth_texture(&device, depth_config.format, size.width, size.height);
         
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("render_pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState { module: (&shader), entry_point: (Some("vs_main")), compilation_options: (Default::default()), buffers: (&[Vertex::desc()]) },
-            primitive: wgpu::PrimitiveState {topology: PrimitiveTopology::TriangleList, strip_index_format: None, front_face: wgpu::FrontFace::Ccw, cull_mode: None, unclipped_depth: false, polygon_mode: wgpu::PolygonMode::Fill, conservative: false},
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: wgpu::TextureFormat::Depth24Plus,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::Less, // closer objects pass depth test
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState { count: (1), mask: (!0), alpha_to_coverage_enabled: (false) },
-            fragment: Some(wgpu::FragmentState {module: &shader, entry_point: Some("fs_main"), compilation_options: Default::default(), targets: &[Some(wgpu::ColorTargetState {format: surface_format, blend: Some(wgpu::BlendState::REPLACE), write_mask: wgpu::ColorWrites::ALL})]}),
-            multiview: None,
-            cache: None,
-        });
+        let render_pipeline = Self::create_render_pipeline(&device, &render_pipeline_layout, surface_format, depth_config, &shader_source);
 
         let state = State {
-            window,
+            target,
             device,
             queue,
             size,
-            surface,
             surface_format,
             render_pipeline,
+            render_pipeline_layout,
+            shader_loader,
+            shader_watcher,
             scene,
+            scene_path: DEFAULT_SCENE.to_string(),
+            asset_watcher,
             geometry_buffers,
-            instance_data,
+            texture_bind_group_layout,
+            texture_bind_groups,
+            instance_batches,
+            batches_dirty: false,
+            moved_instances: Vec::new(),
             camera,
             camera_uniform,
             camera_buffer,
@@ -484,10 +774,18 @@ impl State {
             light_buffer,
             point_lights_buffer,
             light_bind_group,
-            model_bind_group_layout,
             camera_controller,
             input,
+            gamepads,
+            recorder,
+            replay,
+            path_playback: None,
+            next_camera_path: 0,
+            fixed_frame_time: None,
+            selected_instance: 0,
             frame_count: 0,
+            last_frame: Instant::now(),
+            depth_config,
             depth_texture,
             depth_texture_view,
         };
@@ -498,6 +796,9 @@ impl State {
     }
 
     fn configure_surface(&self) {
+        let RenderTarget::Window { surface, .. } = &self.target else {
+            return;
+        };
         let surface_config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
             format: self.surface_format,
@@ -508,7 +809,7 @@ impl State {
             desired_maximum_frame_latency: 2,
             present_mode: wgpu::PresentMode::Immediate,
         };
-        self.surface.configure(&self.device, &surface_config);
+        surface.configure(&self.device, &surface_config);
     }
 
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>)
@@ -520,40 +821,525 @@ impl State {
         self.camera.aspect = new_size.width as f32 / new_size.height as f32;
         
         // recreate depth buffer for new window size
-        let (depth_texture, depth_texture_view) = Self::create_depth_texture(&self.device, new_size.width, new_size.height);
+        let (depth_texture, depth_texture_view) = Self::create_depth_texture(&self.device, self.depth_config.format, new_size.width, new_size.height);
         self.depth_texture = depth_texture;
         self.depth_texture_view = depth_texture_view;
         
         self.configure_surface();
     }
 
-    pub fn render(&mut self) {
-        if self.size.width == 0 || self.size.height == 0 {
+    #[allow(dead_code)]
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    // set an instance's local transform; its descendants follow. The instance buffers holding them are
+    // rewritten on the next frame
+    #[allow(dead_code)]
+    pub fn set_instance_transform(&mut self, index: usize, transform: Transform) {
+        if let Some(instance) = self.scene.instances.get_mut(index) {
+            instance.transform = transform;
+            self.moved_instances.push(index);
+        }
+    }
+
+    // add an instance (its geometry must already be in the scene), returns its index
+    #[allow(dead_code)]
+    pub fn spawn_instance(&mut self, instance: ObjectInstance) -> usize {
+        self.scene.add_instance(instance);
+        self.batches_dirty = true;
+        self.scene.instances.len() - 1
+    }
+
+    // remove an instance and its descendants; indices of the instances after them shift down
+    #[allow(dead_code)]
+    pub fn despawn_instance(&mut self, index: usize) -> Option<ObjectInstance> {
+        let removed = self.scene.remove_instance(index)?;
+        self.batches_dirty = true;
+        Some(removed)
+    }
+
+    // reparent an instance, keeping its local transform; fails if it would create a cycle
+    #[allow(dead_code)]
+    pub fn set_instance_parent(&mut self, index: usize, parent: Option<usize>) -> Result<(), String> {
+        self.scene.set_parent(index, parent)?;
+        self.moved_instances.push(index);
+        Ok(())
+    }
+
+    #[allow(dead_code)]
+    pub fn instance_world_matrix(&self, index: usize) -> Option<cgmath::Matrix4<f32>> {
+        (index < self.scene.instances.len()).then(|| self.scene.world_matrix(index))
+    }
+
+    #[allow(dead_code)]
+    pub fn set_instance_material(&mut self, index: usize, material: Material) {
+        if let Some(instance) = self.scene.instances.get_mut(index) {
+            instance.material = material;
+            self.batches_dirty = true;
+        }
+    }
+
+    // register a geometry so spawned instances can reference it, uploaded on the next frame;
+    // a geometry with the same name is replaced, also for the instances already using it
+    #[allow(dead_code)]
+    pub fn add_geometry(&mut self, geometry: ObjectGeometry) {
+        self.geometry_buffers.remove(&geometry.name);
+        self.scene.geometries.insert(geometry.name.clone(), geometry);
+        self.batches_dirty = true;
+    }
+
+    // rebuild the pipeline when a watched shader changes; on any error the last good pipeline stays
+    fn reload_changed_shaders(&mut self) {
+        let Some(shader_watcher) = &self.shader_watcher else {
+            return;
+        };
+        if shader_watcher.poll_changes().is_empty() {
             return;
         }
-        
-        self.camera_controller.update_camera(&mut self.camera, &mut self.input);
-        self.camera_uniform.update_view_proj(&self.camera);
-        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
 
-        let surface_texture = match self.surface.get_current_texture() {
-            Ok(texture) => texture,
-            Err(wgpu::SurfaceError::Outdated) => {
-                self.configure_surface();
+        let shader_source = match self.shader_loader.load() {
+            Ok(source) => source,
+            Err(e) => {
+                eprintln!("{}\nShader reload failed, keeping the previous pipeline.", e);
                 return;
             }
+        };
+
+        // naga validation passed, but wgpu can still reject the pipeline (e.g. a layout mismatch)
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let render_pipeline = Self::create_render_pipeline(&self.device, &self.render_pipeline_layout, self.surface_format, self.depth_config, &shader_source);
+        if let Some(error) = pollster::block_on(self.device.pop_error_scope()) {
+            eprintln!("Shader reload failed, keeping the previous pipeline: {}", error);
+            return;
+        }
+
+        self.render_pipeline = render_pipeline;
+        println!("Reloaded shaders");
+    }
+
+    // apply asset file changes reported by the watcher; the camera is left untouched
+    fn reload_changed_assets(&mut self) {
+        let Some(asset_watcher) = &self.asset_watcher else {
+            return;
+        };
+        let changed = asset_watcher.poll_changes();
+
+        // a scene file change (or one of its includes) reloads the scene as a whole, which covers everything below
+        let scene_path = Path::new(&self.scene_path);
+        let is_scene_file = |path: &Path| {
+            watcher::is_same_file(path, scene_path)
+                || self.scene.included_files.iter().any(|included| watcher::is_same_file(path, included))
+        };
+        if changed.iter().any(|path| is_scene_file(path)) {
+            self.reload_scene();
+            return;
+        }
+
+        for path in &changed {
+            match path.extension().and_then(|ext| ext.to_str()) {
+                Some("arobj" | "obj" | "gltf" | "glb" | "bin" | "ply" | "stl") => self.reload_geometries(path),
+                Some("armat") => self.reload_materials(path),
+                Some("png") => self.reload_textures(path),
+                Some("arbind") if watcher::is_same_file(path, Path::new(BINDINGS_FILE)) => self.reload_input_bindings(),
+                _ => {}
+            }
+        }
+    }
+
+    // a missing or broken bindings file falls back to the built-in layout
+    fn load_input_bindings() -> InputBindings {
+        InputBindings::from_file(BINDINGS_FILE).unwrap_or_else(|e| {
+            eprintln!("{:#}. Using the default input bindings.", e);
+            InputBindings::default()
+        })
+    }
+
+    fn reload_input_bindings(&mut self) {
+        match InputBindings::from_file(BINDINGS_FILE) {
+            Ok(bindings) => {
+                self.input.set_bindings(bindings);
+                println!("Reloaded input bindings");
+            }
+            Err(e) => eprintln!("{:#}. Keeping the current bindings.", e),
+        }
+    }
+
+    fn reload_scene(&mut self) {
+        let scene = match Scene::load_from_arsc(&self.scene_path, ASSETS_ROOT) {
+            Ok(scene) => scene,
             Err(e) => {
-                panic!("failed to acquire next swapchain texture: {:?}", e);
+                eprintln!("Failed to reload scene '{}': {}. Keeping the current scene.", self.scene_path, e);
+                return;
+            }
+        };
+        println!("Reloaded scene '{}' with {} instances", scene.name, scene.instances.len());
+
+        self.queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[LightUniform::from_light(&scene.light)]));
+        self.scene = scene;
+        // geometries were re-read from disk with the scene, re-upload them and their embedded images on sync
+        self.geometry_buffers.clear();
+        self.texture_bind_groups.retain(|albedo_texture, _| !albedo_texture.contains('#'));
+        self.batches_dirty = true;
+    }
+
+    fn reload_geometries(&mut self, changed: &Path) {
+        let affected: Vec<(String, PathBuf)> = self.scene.geometries.iter()
+            .filter(|(_, geometry)| geometry.source_files.iter().any(|source| watcher::is_same_file(source, changed)))
+            .map(|(name, geometry)| (name.clone(), geometry.source_files[0].clone()))
+            .collect();
+
+        for (name, arobj_path) in affected {
+            match ObjectGeometry::load_from_arobj(&arobj_path) {
+                // instances refer to geometry by name, so a renamed geometry needs the full scene
+                Ok(geometry) if geometry.name != name => self.reload_scene(),
+                Ok(geometry) => {
+                    println!("Reloaded geometry '{}'", name);
+                    // images inside a glTF file are addressed as `<file>#image<index>` and reload with the file
+                    self.texture_bind_groups.retain(|albedo_texture, _| {
+                        albedo_texture.split_once('#').is_none_or(|(file, _)| {
+                            let file = Path::new(ASSETS_ROOT).join(file);
+                            !geometry.source_files.iter().any(|source| watcher::is_same_file(source, &file))
+                        })
+                    });
+                    // instances without a `material:` of their own use the one that came with the mesh file
+                    if let Some(material) = &geometry.material {
+                        for instance in self.scene.instances.iter_mut()
+                            .filter(|instance| instance.geometry_name == name && instance.material.source_file.is_none())
+                        {
+                            instance.material = material.clone();
+                        }
+                    }
+                    self.scene.geometries.insert(name.clone(), geometry);
+                    self.geometry_buffers.remove(&name);
+                    self.batches_dirty = true;
+                }
+                Err(e) => eprintln!("Failed to reload geometry '{}': {}. Keeping the old one.", name, e),
+            }
+        }
+    }
+
+    fn reload_materials(&mut self, changed: &Path) {
+        let is_affected = |material: &Material| material.source_file.as_ref()
+            .is_some_and(|source| watcher::is_same_file(Path::new(source), changed));
+
+        let Some(source_file) = self.scene.instances.iter()
+            .find(|instance| is_affected(&instance.material))
+            .and_then(|instance| instance.material.source_file.clone()) else {
+            return;
+        };
+
+        let material = match Material::from_file(&source_file) {
+            Ok(material) => material,
+            Err(e) => {
+                eprintln!("Failed to reload material '{}': {:#}. Keeping the old one.", source_file, e);
+                return;
+            }
+        };
+        println!("Reloaded material '{}'", material.name);
+
+        for instance in self.scene.instances.iter_mut().filter(|instance| is_affected(&instance.material)) {
+            instance.material = material.clone();
+        }
+        self.batches_dirty = true;
+    }
+
+    fn reload_textures(&mut self, changed: &Path) {
+        // dropping the cached bind group makes sync_scene load the texture again
+        let before = self.texture_bind_groups.len();
+        self.texture_bind_groups.retain(|albedo_texture, _| {
+            !watcher::is_same_file(&Path::new(ASSETS_ROOT).join(albedo_texture), changed)
+        });
+        if self.texture_bind_groups.len() != before {
+            println!("Reloaded texture '{}'", changed.display());
+            self.batches_dirty = true;
+        }
+    }
+
+    // one-shot commands, bound in assets/config/input.arbind (defaults: O toggles orbit/fly,
+    // Tab selects the next instance, F frames it, 1-9 jump to scene cameras, B saves a bookmark,
+    // P / numpad 5 toggles orthographic, numpad 7/1/3 switch to top/front/side views)
+    fn handle_commands(&mut self) {
+        for action in self.input.take_just_pressed() {
+            match action {
+                Action::JumpToCamera(index) => self.jump_to_camera(index),
+                Action::ToggleCameraMode => {
+                    self.camera_controller.toggle_mode(&self.camera);
+                    println!("Camera mode: {:?}", self.camera_controller.mode);
+                }
+                Action::SelectNext if !self.scene.instances.is_empty() => {
+                    self.selected_instance = (self.selected_instance + 1) % self.scene.instances.len();
+                    println!("Selected '{}'", self.scene.instances[self.selected_instance].name);
+                }
+                Action::FrameSelected => self.frame_selected_instance(),
+                Action::SaveBookmark => self.save_camera_bookmark(),
+                Action::ToggleProjection => {
+                    self.camera.toggle_projection(self.camera_controller.orbit_distance);
+                    println!("Projection: {}", self.camera.projection.name());
+                }
+                Action::ViewTop => self.apply_view_preset(ViewPreset::Top),
+                Action::ViewFront => self.apply_view_preset(ViewPreset::Front),
+                Action::ViewSide => self.apply_view_preset(ViewPreset::Side),
+                Action::PlayCameraPath => self.toggle_camera_path(),
+                _ => {}
+            }
+        }
+    }
+
+    fn apply_view_preset(&mut self, preset: ViewPreset) {
+        self.camera.apply_preset(preset, self.camera_controller.orbit_distance);
+        self.camera_controller.sync_to_camera(&self.camera);
+        println!("View: {:?}", preset);
+    }
+
+    // each press while stopped plays the scene's next camera path
+    fn toggle_camera_path(&mut self) {
+        if self.path_playback.is_some() {
+            self.stop_camera_path();
+            return;
+        }
+        if self.scene.camera_paths.is_empty() {
+            println!("The scene has no camera paths");
+            return;
+        }
+        let index = self.next_camera_path % self.scene.camera_paths.len();
+        self.next_camera_path = index + 1;
+        self.path_playback = Some(PathPlayback { index, elapsed: 0.0, play_once: false });
+        println!("Playing camera path '{}'", self.scene.camera_paths[index].name);
+    }
+
+    fn stop_camera_path(&mut self) {
+        if let Some(playback) = self.path_playback.take()
+            && let Some(path) = self.scene.camera_paths.get(playback.index) {
+            println!("Stopped camera path '{}'", path.name);
+        }
+        self.camera_controller.sync_to_camera(&self.camera);
+    }
+
+    // show the path at the current time, then step it forward
+    fn advance_camera_path(&mut self, dt: f32) {
+        let Some(playback) = &mut self.path_playback else {
+            return;
+        };
+        // a scene reload may have removed the path
+        let Some(path) = self.scene.camera_paths.get(playback.index) else {
+            self.stop_camera_path();
+            return;
+        };
+
+        let sample = path.sample(playback.elapsed);
+        self.camera.look_at(sample.position.into(), sample.target.into());
+        self.camera.fovy = sample.fovy;
+
+        playback.elapsed += dt;
+        let finished = if playback.play_once { path.is_past_end(playback.elapsed) } else { path.is_finished(playback.elapsed) };
+        if finished {
+            self.stop_camera_path();
+        }
+    }
+
+    pub fn is_playing_camera_path(&self) -> bool {
+        self.path_playback.is_some()
+    }
+
+    // play a path at a fixed frame rate, one rendered frame per step
+    pub fn render_camera_path(&mut self, name: &str, frames_per_second: f32) -> Result<usize, String> {
+        let index = self.scene.camera_paths.iter()
+            .position(|path| path.name == name)
+            .ok_or_else(|| format!("The scene has no camera path named '{}'", name))?;
+        let frame_time = 1.0 / frames_per_second;
+        let frame_count = (self.scene.camera_paths[index].duration() / frame_time).floor() as usize + 1;
+
+        self.fixed_frame_time = Some(frame_time);
+        self.path_playback = Some(PathPlayback { index, elapsed: 0.0, play_once: true });
+        Ok(frame_count)
+    }
+
+    fn jump_to_camera(&mut self, index: usize) {
+        let Some(scene_camera) = self.scene.cameras.get(index) else {
+            return;
+        };
+        self.camera.apply(scene_camera);
+        self.camera_controller.sync_to_camera(&self.camera);
+        println!("Camera '{}'", scene_camera.name);
+    }
+
+    fn save_camera_bookmark(&mut self) {
+        let bookmark = self.camera.to_scene_camera(format!("Bookmark{}", self.scene.cameras.len() + 1));
+        match Scene::save_camera_bookmark(&self.scene_path, &bookmark) {
+            Ok(()) => {
+                println!("Saved camera '{}' to '{}' (key {})", bookmark.name, self.scene_path, self.scene.cameras.len() + 1);
+                self.scene.cameras.push(bookmark);
             }
+            Err(e) => eprintln!("Failed to save camera bookmark to '{}': {}", self.scene_path, e),
+        }
+    }
+
+    fn frame_selected_instance(&mut self) {
+        let Some(instance) = self.scene.instances.get(self.selected_instance) else {
+            return;
+        };
+        let Some(geometry) = self.scene.geometries.get(&instance.geometry_name) else {
+            return;
         };
+        let (center, radius) = instance.bounding_sphere(geometry, self.scene.world_matrix(self.selected_instance));
+        self.camera_controller.frame(&mut self.camera, center, radius);
+        println!("Framing '{}'", instance.name);
+    }
 
-        let texture_view = surface_texture.texture.create_view(&Default::default());
+    // bring GPU buffers in line with scene changes made since the last frame
+    fn sync_scene(&mut self) {
+        if self.batches_dirty {
+            for (geom_name, geometry) in &self.scene.geometries {
+                if !self.geometry_buffers.contains_key(geom_name) {
+                    let buffers = Self::create_geometry_buffers(&self.device, geom_name, geometry);
+                    self.geometry_buffers.insert(geom_name.clone(), buffers);
+                }
+            }
+            for instance in &self.scene.instances {
+                let albedo_texture = &instance.material.albedo_texture;
+                if !self.texture_bind_groups.contains_key(albedo_texture) {
+                    let bind_group = Self::create_texture_bind_group(&self.device, &self.queue, &self.texture_bind_group_layout, albedo_texture);
+                    self.texture_bind_groups.insert(albedo_texture.clone(), bind_group);
+                }
+            }
+            self.instance_batches = Self::build_instance_batches(&self.device, &self.scene, &self.texture_bind_groups);
+        } else if !self.moved_instances.is_empty() {
+            let mut moved = vec![false; self.scene.instances.len()];
+            for &index in &self.moved_instances {
+                moved[index] = true;
+                for descendant in self.scene.descendants(index) {
+                    moved[descendant] = true;
+                }
+            }
+            let world_matrices = self.scene.world_matrices();
+            let moved_batches = self.instance_batches.iter()
+                .filter(|batch| batch.instance_indices.iter().any(|&idx| moved[idx]));
+            for batch in moved_batches {
+                let instances: Vec<InstanceRaw> = batch.instance_indices.iter()
+                    .map(|&idx| InstanceRaw::from_instance(&self.scene.instances[idx], world_matrices[idx]))
+                    .collect();
+                self.queue.write_buffer(&batch.instance_buffer, 0, bytemuck::cast_slice(&instances));
+            }
+        }
+
+        if self.batches_dirty || !self.moved_instances.is_empty() {
+            let point_lights_uniform = Self::collect_point_lights(&self.scene);
+            self.queue.write_buffer(&self.point_lights_buffer, 0, bytemuck::cast_slice(&[point_lights_uniform]));
+            self.batches_dirty = false;
+            self.moved_instances.clear();
+        }
+    }
 
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    pub fn render(&mut self) {
+        if self.size.width == 0 || self.size.height == 0 {
+            return;
+        }
+
+        self.update();
+
+        match &self.target {
+            RenderTarget::Window { window, surface } => {
+                let surface_texture = match surface.get_current_texture() {
+                    Ok(texture) => texture,
+                    Err(wgpu::SurfaceError::Outdated) => {
+                        self.configure_surface();
+                        return;
+                    }
+                    Err(e) => {
+                        panic!("failed to acquire next swapchain texture: {:?}", e);
+                    }
+                };
+                self.draw(&surface_texture.texture.create_view(&Default::default()));
+                window.pre_present_notify();
+                surface_texture.present();
+                window.request_redraw();
+            }
+            RenderTarget::Offscreen { texture } => {
+                self.draw(&texture.create_view(&Default::default()));
+            }
+        }
+        self.frame_count += 1;
+    }
+
+    // everything before drawing: hot reload, scene sync, input and camera
+    fn update(&mut self) {
+        self.reload_changed_shaders();
+        self.reload_changed_assets();
+        self.sync_scene();
+        // clamp so a stall (window drag, breakpoint) doesn't teleport the camera
+        let now = Instant::now();
+        let measured_dt = self.fixed_frame_time
+            .unwrap_or_else(|| now.duration_since(self.last_frame).as_secs_f32().min(MAX_FRAME_TIME));
+        self.last_frame = now;
+
+        // replays supply recorded input and frame times, live sessions poll the devices
+        let mut expected_pose = None;
+        let dt = match self.replay.as_mut().and_then(Replay::next_frame) {
+            Some(frame) => {
+                for &event in &frame.events {
+                    self.input.replay_event(event);
+                }
+                expected_pose = frame.camera;
+                frame.dt
+            }
+            None => {
+                if let Some(gamepads) = &mut self.gamepads {
+                    gamepads.poll(&mut self.input);
+                }
+                measured_dt
+            }
+        };
+
+        self.handle_commands();
+        if self.path_playback.is_some() {
+            self.advance_camera_path(dt);
+        } else {
+            self.camera_controller.update_camera(&mut self.camera, &mut self.input, dt);
+        }
+        self.input.end_frame();
+        self.camera.update(dt);
+
+        let pose = CameraPose::from_camera(&self.camera);
+        if let Some(replay) = &mut self.replay {
+            replay.check_camera(expected_pose, pose);
+            if replay.is_finished() {
+                self.finish_replay();
+            }
+        }
+        if let Some(recorder) = &mut self.recorder {
+            let events = self.input.take_recorded_events();
+            if let Err(e) = recorder.record_frame(dt, &events, pose) {
+                eprintln!("Failed to write recording: {:#}. Recording stopped after {} frames.", e, recorder.frames());
+                self.recorder = None;
+            }
+        }
+
+        self.camera_uniform.update_view_proj(&self.camera);
+        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[self.camera_uniform]));
+    }
+
+    fn finish_replay(&mut self) {
+        let Some(replay) = self.replay.take() else {
+            return;
+        };
+        match replay.diverged_at {
+            Some(frame) => println!("Replay finished after {} frames, diverging from frame {}", replay.frame_index, frame),
+            None => println!("Replay finished after {} frames, camera matched the recording", replay.frame_index),
+        }
+        self.input.set_ignore_live(false);
+    }
+
+    fn draw(&self, view: &wgpu::TextureView) {
         let mut encoder = self.device.create_command_encoder(&Default::default());
         let mut renderpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: None,
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: &texture_view,
+                view,
                 depth_slice: None,
                 resolve_target: None,
                 ops: wgpu::Operations {
@@ -564,7 +1350,7 @@ impl State {
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                 view: &self.depth_texture_view,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0), // clear to far plane
+                    load: wgpu::LoadOp::Clear(self.depth_config.clear), // clear to far plane
                     store: wgpu::StoreOp::Store,
                 }),
                 stencil_ops: None,
@@ -575,34 +1361,81 @@ impl State {
 
         renderpass.set_pipeline(&self.render_pipeline);
         renderpass.set_bind_group(0, &self.camera_bind_group, &[]);
-        renderpass.set_bind_group(2, &self.light_bind_group, &[]);
+        renderpass.set_bind_group(1, &self.light_bind_group, &[]);
 
-        // render each object instance
+        // one instanced draw per (geometry, texture) batch
         let mut rendered_count = 0;
-        for (idx, instance) in self.scene.instances.iter().enumerate() {
-            if let Some(buffers) = self.geometry_buffers.get(&instance.geometry_name) {
-                if let Some(instance_data) = self.instance_data.get(idx) {
-                    renderpass.set_bind_group(1, &instance_data.model_bind_group, &[]);
-                    renderpass.set_bind_group(3, &instance_data.texture_bind_group, &[]);
-                    renderpass.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
-                    renderpass.set_index_buffer(buffers.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-                    renderpass.draw_indexed(0..buffers.num_indices, 0, 0..1);
-                    rendered_count += 1;
-                }
+        for batch in &self.instance_batches {
+            if let Some(buffers) = self.geometry_buffers.get(&batch.geometry_name) {
+                renderpass.set_bind_group(2, &batch.texture_bind_group, &[]);
+                renderpass.set_vertex_buffer(0, buffers.vertex_buffer.slice(..));
+                renderpass.set_vertex_buffer(1, batch.instance_buffer.slice(..));
+                renderpass.set_index_buffer(buffers.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                renderpass.draw_indexed(0..buffers.num_indices, 0, 0..batch.instance_count);
+                rendered_count += batch.instance_count;
             }
         }
         
         if self.frame_count == 0 {
-            println!("First frame: rendered {} instances out of {} total", rendered_count, self.scene.instances.len());
+            println!("First frame: rendered {} instances out of {} total in {} draw calls",
+                rendered_count, self.scene.instances.len(), self.instance_batches.len());
         }
-        self.frame_count += 1;
 
         drop(renderpass);
 
         self.queue.submit([encoder.finish()]);
-        self.window.pre_present_notify();
-        surface_texture.present();
-     
-        self.window.request_redraw();
+    }
+
+    // read the last headless frame back from the GPU and write it as a PNG
+    pub fn save_frame(&self, path: &Path) -> Result<(), String> {
+        let RenderTarget::Offscreen { texture } = &self.target else {
+            return Err("Frames can only be saved from a headless renderer".to_string());
+        };
+        let (width, height) = (self.size.width, self.size.height);
+        // buffer rows must be padded to the copy alignment
+        let padded_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("frame_readback"),
+            size: (padded_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: None,
+                },
+            },
+            texture.size(),
+        );
+        self.queue.submit([encoder.finish()]);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer.slice(..).map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::PollType::wait_indefinitely()).map_err(|e| format!("Failed to read back frame: {}", e))?;
+        receiver.recv()
+            .map_err(|e| format!("Failed to read back frame: {}", e))?
+            .map_err(|e| format!("Failed to read back frame: {}", e))?;
+
+        let mapped = buffer.slice(..).get_mapped_range();
+        let pixels: Vec<u8> = mapped.chunks(padded_row as usize)
+            .flat_map(|row| &row[..(width * 4) as usize])
+            .copied()
+            .collect();
+        drop(mapped);
+        buffer.unmap();
+
+        image::RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| "Frame size doesn't match the image".to_string())?
+            .save(path)
+            .map_err(|e| format!("Failed to save frame '{}': {}", path.display(), e))
     }
 }
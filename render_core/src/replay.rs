@@ -0,0 +1,295 @@
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::time::Instant;
+
+use anyhow::{bail, Context, Result};
+
+use crate::camera::Camera;
+use crate::input::{self, Binding, GamepadEvent, InputEvent};
+
+// set to a file path to record the session's input and frame times
+pub const RECORD_ENV: &str = "ARBITRA_RECORD";
+// set to a recording to play it back instead of reading live input
+pub const REPLAY_ENV: &str = "ARBITRA_REPLAY";
+const FORMAT_VERSION: u32 = 2;
+
+/// Camera state written after every recorded frame, so replays can detect divergence
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraPose {
+    pub eye: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub ortho_height: f32,
+}
+
+impl CameraPose {
+    pub fn from_camera(camera: &Camera) -> Self {
+        CameraPose {
+            eye: camera.eye.into(),
+            yaw: camera.yaw,
+            pitch: camera.pitch,
+            ortho_height: camera.ortho_height,
+        }
+    }
+}
+
+/// One frame of a recording: the events that arrived before it and the time step it used
+pub struct RecordedFrame {
+    // seconds since the recording started, as measured on the recording machine
+    #[allow(dead_code)]
+    pub timestamp: f64,
+    pub dt: f32,
+    pub events: Vec<InputEvent>,
+    pub camera: Option<CameraPose>,
+}
+
+/// Writes an `.arrec` file frame by frame
+pub struct Recorder {
+    writer: BufWriter<File>,
+    start: Instant,
+    frames: usize,
+}
+
+impl Recorder {
+    pub fn create(path: &str, scene_path: &str, size: (u32, u32)) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Failed to create recording: {}", path))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "# Arbitra input recording")?;
+        writeln!(writer, "version {}", FORMAT_VERSION)?;
+        writeln!(writer, "scene {}", scene_path)?;
+        writeln!(writer, "size {} {}", size.0, size.1)?;
+        Ok(Recorder { writer, start: Instant::now(), frames: 0 })
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    // flushed every frame, so a crash still leaves a usable recording behind
+    pub fn record_frame(&mut self, dt: f32, events: &[InputEvent], camera: CameraPose) -> Result<()> {
+        let writer = &mut self.writer;
+        writeln!(writer, "frame {} {}", self.start.elapsed().as_secs_f64(), dt)?;
+        for event in events {
+            if let Some(line) = format_event(event) {
+                writeln!(writer, "{}", line)?;
+            }
+        }
+        writeln!(writer, "camera {} {} {} {} {} {}",
+            camera.eye[0], camera.eye[1], camera.eye[2], camera.yaw, camera.pitch, camera.ortho_height)?;
+        writer.flush()?;
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+/// A loaded `.arrec` file, consumed one frame at a time
+pub struct Replay {
+    pub scene_path: String,
+    pub size: (u32, u32),
+    frames: VecDeque<RecordedFrame>,
+    pub frame_index: usize,
+    // the first frame whose camera didn't match the recording
+    pub diverged_at: Option<usize>,
+}
+
+impl Replay {
+    pub fn load(path: &str) -> Result<Self> {
+        let content = fs::read_to_string(path).with_context(|| format!("Failed to read recording: {}", path))?;
+        Self::parse(&content).with_context(|| format!("Invalid recording {}", path))
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut scene_path = String::new();
+        let mut size = None;
+        let mut frames: VecDeque<RecordedFrame> = VecDeque::new();
+
+        for (line_index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_number = line_index + 1;
+            let parts: Vec<&str> = line.split_whitespace().collect();
+
+            match parts[0] {
+                "version" => {
+                    let version: u32 = parse_field(&parts, 1, line_number)?;
+                    if version != FORMAT_VERSION {
+                        bail!("line {}: unsupported recording version {}", line_number, version);
+                    }
+                }
+                "scene" => scene_path = parts[1..].join(" "),
+                "size" => size = Some((parse_field(&parts, 1, line_number)?, parse_field(&parts, 2, line_number)?)),
+                "frame" => frames.push_back(RecordedFrame {
+                    timestamp: parse_field(&parts, 1, line_number)?,
+                    dt: parse_field(&parts, 2, line_number)?,
+                    events: Vec::new(),
+                    camera: None,
+                }),
+                keyword => {
+                    let Some(frame) = frames.back_mut() else {
+                        bail!("line {}: '{}' before the first frame", line_number, keyword);
+                    };
+                    if keyword == "camera" {
+                        frame.camera = Some(CameraPose {
+                            eye: [
+                                parse_field(&parts, 1, line_number)?,
+                                parse_field(&parts, 2, line_number)?,
+                                parse_field(&parts, 3, line_number)?,
+                            ],
+                            yaw: parse_field(&parts, 4, line_number)?,
+                            pitch: parse_field(&parts, 5, line_number)?,
+                            ortho_height: parse_field(&parts, 6, line_number)?,
+                        });
+                    } else {
+                        frame.events.push(parse_event(&parts)
+                            .with_context(|| format!("line {}: invalid event '{}'", line_number, line))?);
+                    }
+                }
+            }
+        }
+
+        let Some(size) = size else {
+            bail!("missing 'size' line");
+        };
+        Ok(Replay { scene_path, size, frames, frame_index: 0, diverged_at: None })
+    }
+
+    pub fn next_frame(&mut self) -> Option<RecordedFrame> {
+        let frame = self.frames.pop_front()?;
+        self.frame_index += 1;
+        Some(frame)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    // remember the first mismatch; later frames usually differ as a consequence
+    pub fn check_camera(&mut self, expected: Option<CameraPose>, actual: CameraPose) {
+        if self.diverged_at.is_none() && expected.is_some_and(|expected| expected != actual) {
+            eprintln!("Replay diverged at frame {}: expected camera {:?}, got {:?}", self.frame_index, expected, actual);
+            self.diverged_at = Some(self.frame_index);
+        }
+    }
+}
+
+fn parse_field<T: std::str::FromStr>(parts: &[&str], index: usize, line_number: usize) -> Result<T> {
+    let Some(value) = parts.get(index) else {
+        bail!("line {}: missing field {}", line_number, index);
+    };
+    value.parse().ok().with_context(|| format!("line {}: invalid value '{}'", line_number, value))
+}
+
+fn state_name(is_pressed: bool) -> &'static str {
+    if is_pressed { "down" } else { "up" }
+}
+
+// f32 Display prints the shortest text that parses back to the same value, which keeps replays exact
+fn format_event(event: &InputEvent) -> Option<String> {
+    Some(match *event {
+        InputEvent::Key(code, is_pressed) => format!("key {} {}", input::binding_name(Binding::Key(code))?, state_name(is_pressed)),
+        InputEvent::MouseButton(button, is_pressed) => {
+            format!("mouse {} {}", input::binding_name(Binding::Mouse(button))?, state_name(is_pressed))
+        }
+        InputEvent::MouseMove(delta_x, delta_y) => format!("mouse_move {} {}", delta_x, delta_y),
+        InputEvent::Scroll(lines) => format!("scroll {}", lines),
+        InputEvent::Gamepad(pad, GamepadEvent::ButtonPressed(button)) => {
+            format!("pad_button {} {} down", pad, input::binding_name(Binding::PadButton(button))?)
+        }
+        InputEvent::Gamepad(pad, GamepadEvent::ButtonReleased(button)) => {
+            format!("pad_button {} {} up", pad, input::binding_name(Binding::PadButton(button))?)
+        }
+        InputEvent::Gamepad(pad, GamepadEvent::ButtonChanged(button, value)) => {
+            format!("pad_value {} {} {}", pad, input::binding_name(Binding::PadButton(button))?, value)
+        }
+        InputEvent::Gamepad(pad, GamepadEvent::AxisChanged(axis, value)) => {
+            format!("pad_axis {} {} {}", pad, input::pad_axis_name(axis)?, value)
+        }
+        InputEvent::Gamepad(pad, GamepadEvent::Disconnected) => format!("pad_disconnected {}", pad),
+        InputEvent::ReleaseAll => "release_all".to_string(),
+    })
+}
+
+fn parse_event(parts: &[&str]) -> Option<InputEvent> {
+    let is_pressed = |index: usize| match parts.get(index) {
+        Some(&"down") => Some(true),
+        Some(&"up") => Some(false),
+        _ => None,
+    };
+    let binding = || parts.get(1).and_then(|name| input::parse_binding(name));
+    let number = |index: usize| parts.get(index).and_then(|value| value.parse::<f32>().ok());
+    // gamepad events start with the index of the pad that sent them
+    let pad = || parts.get(1).and_then(|value| value.parse::<usize>().ok());
+
+    match parts[0] {
+        "key" => match binding()? {
+            Binding::Key(code) => Some(InputEvent::Key(code, is_pressed(2)?)),
+            _ => None,
+        },
+        "mouse" => match binding()? {
+            Binding::Mouse(button) => Some(InputEvent::MouseButton(button, is_pressed(2)?)),
+            _ => None,
+        },
+        "mouse_move" => Some(InputEvent::MouseMove(number(1)?, number(2)?)),
+        "scroll" => Some(InputEvent::Scroll(number(1)?)),
+        "pad_button" | "pad_value" => {
+            let Binding::PadButton(button) = parts.get(2).and_then(|name| input::parse_binding(name))? else {
+                return None;
+            };
+            let event = match parts[0] {
+                "pad_value" => GamepadEvent::ButtonChanged(button, number(3)?),
+                _ if is_pressed(3)? => GamepadEvent::ButtonPressed(button),
+                _ => GamepadEvent::ButtonReleased(button),
+            };
+            Some(InputEvent::Gamepad(pad()?, event))
+        }
+        "pad_axis" => {
+            let axis = input::pad_axis_from_name(parts.get(2)?)?;
+            Some(InputEvent::Gamepad(pad()?, GamepadEvent::AxisChanged(axis, number(3)?)))
+        }
+        "pad_disconnected" => Some(InputEvent::Gamepad(pad()?, GamepadEvent::Disconnected)),
+        "release_all" => Some(InputEvent::ReleaseAll),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gilrs::{Axis, Button};
+
+    fn round_trip(event: InputEvent) -> Option<InputEvent> {
+        let line = format_event(&event)?;
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        Some(parse_event(&parts).unwrap_or_else(|| panic!("'{}' doesn't parse back", line)))
+    }
+
+    #[test]
+    fn every_key_and_pad_input_parses_back() {
+        for &code in input::KEY_CODES {
+            for is_pressed in [true, false] {
+                let event = InputEvent::Key(code, is_pressed);
+                assert_eq!(round_trip(event), Some(event));
+            }
+        }
+        for &button in input::PAD_BUTTONS {
+            for event in [GamepadEvent::ButtonPressed(button), GamepadEvent::ButtonReleased(button), GamepadEvent::ButtonChanged(button, 0.25)] {
+                assert_eq!(round_trip(InputEvent::Gamepad(0, event)), Some(InputEvent::Gamepad(0, event)));
+            }
+        }
+        for &axis in input::PAD_AXES {
+            let event = InputEvent::Gamepad(1, GamepadEvent::AxisChanged(axis, -0.75));
+            assert_eq!(round_trip(event), Some(event));
+        }
+        let event = InputEvent::Gamepad(2, GamepadEvent::Disconnected);
+        assert_eq!(round_trip(event), Some(event));
+    }
+
+    #[test]
+    fn unnamed_inputs_are_left_out() {
+        assert_eq!(round_trip(InputEvent::Gamepad(0, GamepadEvent::ButtonPressed(Button::Unknown))), None);
+        assert_eq!(round_trip(InputEvent::Gamepad(0, GamepadEvent::AxisChanged(Axis::Unknown, 1.0))), None);
+    }
+}
@@ -1,9 +1,13 @@
 use crate::object::ObjectGeometry;
 use crate::material::Material;
-use crate::transform::Transform;
+use crate::transform::{RotationOrder, Transform};
+use crate::camera::Projection;
+use crate::camera_path::{CameraPath, Easing, Interpolation, PathKey};
+use crate::pattern::{self, Pattern, ScatterRegion, SurfaceSampler, Variation};
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 // global directional light (like the sun)
 #[derive(Debug, Clone, Copy)]
@@ -25,23 +29,402 @@ impl Default for Light {
     }
 }
 
+// named camera from a `camera` block; angles are in degrees like the file
+#[derive(Debug, Clone)]
+pub struct SceneCamera {
+    pub name: String,
+    pub position: [f32; 3],
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fovy: f32,
+    pub znear: f32,
+    pub zfar: f32,
+    pub projection: Projection,
+    pub ortho_height: f32,
+    pub is_default: bool,
+}
+
+impl Default for SceneCamera {
+    fn default() -> Self {
+        SceneCamera {
+            name: String::from("Default"),
+            position: [0.0, 1.0, 2.0],
+            yaw: -90.0,
+            pitch: -22.9,
+            fovy: 45.0,
+            znear: 0.1,
+            zfar: 100.0,
+            projection: Projection::Perspective,
+            ortho_height: 5.0,
+            is_default: false,
+        }
+    }
+}
+
+impl SceneCamera {
+    // camera block in .arsc syntax
+    pub fn to_arsc(&self) -> String {
+        format!(
+            "camera\n    name: {}\n    position: {} {} {}\n    yaw: {}\n    pitch: {}\n    fov: {}\n    near: {}\n    far: {}\n    projection: {}\n    ortho_height: {}\n",
+            self.name,
+            self.position[0], self.position[1], self.position[2],
+            self.yaw, self.pitch, self.fovy, self.znear, self.zfar,
+            self.projection.name(), self.ortho_height,
+        )
+    }
+}
+
+// emissive instances light the scene as point lights, the first this many of them; the shader has the same limit
+pub const MAX_POINT_LIGHTS: usize = 8;
+// point light intensity per unit of an instance's emissive strength
+pub const EMISSIVE_LIGHT_INTENSITY: f32 = 5.0;
+
 #[derive(Debug, Clone)]
 pub struct ObjectInstance {
     #[allow(dead_code)]
     pub name: String,
     pub geometry_name: String,
+    // relative to the parent, or to the world for root instances
     pub transform: Transform,
+    // index into Scene::instances
+    pub parent: Option<usize>,
     pub emissive: f32,
     pub emissive_color: [f32; 3],
     pub material: Material,
 }
 
+impl ObjectInstance {
+    // world-space bounding sphere (center, radius) around the geometry's bounds, given the world matrix
+    pub fn bounding_sphere(&self, geometry: &ObjectGeometry, model: cgmath::Matrix4<f32>) -> (cgmath::Point3<f32>, f32) {
+        use cgmath::{EuclideanSpace, InnerSpace, Transform as _};
+
+        let (min, max) = geometry.bounds();
+        let corners: Vec<cgmath::Point3<f32>> = (0..8)
+            .map(|i| cgmath::Point3::new(
+                if i & 1 == 0 { min[0] } else { max[0] },
+                if i & 2 == 0 { min[1] } else { max[1] },
+                if i & 4 == 0 { min[2] } else { max[2] },
+            ))
+            .map(|corner| model.transform_point(corner))
+            .collect();
+
+        let center = cgmath::Point3::centroid(&corners);
+        let radius = corners.iter()
+            .map(|corner| (corner - center).magnitude())
+            .fold(0.0, f32::max);
+        (center, radius)
+    }
+}
+
 #[derive(Debug)]
 pub struct Scene {
     pub name: String,
     pub instances: Vec<ObjectInstance>,
     pub geometries: HashMap<String, ObjectGeometry>,
     pub light: Light,
+    // whether the file sets the directional light itself rather than keeping the default
+    pub has_light: bool,
+    pub cameras: Vec<SceneCamera>,
+    pub camera_paths: Vec<CameraPath>,
+    // every file pulled in through `include:`, at any depth
+    pub included_files: Vec<PathBuf>,
+}
+
+// camera block being parsed; look_at is resolved against the final position
+struct PendingCamera {
+    camera: SceneCamera,
+    look_at: Option<[f32; 3]>,
+}
+
+impl PendingCamera {
+    fn finalize(mut self, scene: &mut Scene) {
+        if let Some(look_at) = self.look_at {
+            let direction: [f32; 3] = std::array::from_fn(|i| look_at[i] - self.camera.position[i]);
+            let length = direction.iter().map(|d| d * d).sum::<f32>().sqrt();
+            if length > 0.0 {
+                self.camera.yaw = direction[2].atan2(direction[0]).to_degrees();
+                self.camera.pitch = (direction[1] / length).asin().to_degrees();
+            }
+        }
+        scene.cameras.push(self.camera);
+    }
+}
+
+// object block being parsed, turned into an ObjectInstance when the block ends; prefabs are kept as one
+#[derive(Debug, Clone)]
+struct PendingObject {
+    geometry_path: String,
+    // built-in geometry spec such as `sphere segments=32`, used instead of geometry_path
+    primitive: Option<String>,
+    name: String,
+    position: [f32; 3],
+    // Euler degrees, applied in rotation_order unless a quaternion is given
+    rotation: [f32; 3],
+    rotation_order: RotationOrder,
+    quaternion: Option<cgmath::Quaternion<f32>>,
+    scale: [f32; 3],
+    emissive: f32,
+    emissive_color: [f32; 3],
+    material_path: Option<String>,
+    // name of another object, resolved once the whole file is read
+    parent: Option<String>,
+    // repeats the object once the whole file is read
+    pattern: Option<Pattern>,
+    variation: Variation,
+    // for prefabs: objects made from it so far, which numbers the names of those without their own `name:`
+    instances_made: usize,
+}
+
+impl PendingObject {
+    fn new() -> Self {
+        PendingObject {
+            geometry_path: String::new(),
+            primitive: None,
+            name: String::new(),
+            position: [0.0, 0.0, 0.0],
+            rotation: [0.0, 0.0, 0.0],
+            rotation_order: RotationOrder::default(),
+            quaternion: None,
+            scale: [1.0, 1.0, 1.0],
+            emissive: 0.0,
+            emissive_color: [1.0, 1.0, 1.0],
+            material_path: None,
+            parent: None,
+            pattern: None,
+            variation: Variation::default(),
+            instances_made: 0,
+        }
+    }
+
+    fn apply_key(&mut self, parts: &[&str]) -> Result<(), Box<dyn std::error::Error>> {
+        match parts[0] {
+            "geometry:" if parts.len() > 1 => {
+                self.geometry_path = parts[1].to_string();
+                self.primitive = None;
+            }
+            "primitive:" if parts.len() > 1 => {
+                self.primitive = Some(parts[1..].join(" "));
+            }
+            "name:" if parts.len() > 1 => {
+                self.name = parts[1].to_string();
+            }
+            "position:" if parts.len() >= 4 => {
+                self.position = parse_vec3(parts)?;
+            }
+            "rotation:" if parts.len() >= 4 => {
+                self.rotation = parse_vec3(parts)?;
+            }
+            "rotation_order:" if parts.len() >= 2 => {
+                self.rotation_order = RotationOrder::from_name(parts[1])
+                    .ok_or_else(|| format!("Unknown rotation order '{}' in object '{}'", parts[1], self.name))?;
+            }
+            "quaternion:" if parts.len() >= 5 => {
+                // x y z w, like glTF
+                use cgmath::InnerSpace;
+                let [x, y, z] = parse_vec3(parts)?;
+                let quaternion = cgmath::Quaternion::new(parts[4].parse()?, x, y, z);
+                if quaternion.magnitude2() == 0.0 {
+                    return Err(format!("Zero quaternion in object '{}'", self.name).into());
+                }
+                self.quaternion = Some(quaternion.normalize());
+            }
+            "scale:" if parts.len() >= 4 => {
+                self.scale = parse_vec3(parts)?;
+            }
+            "emissive:" if parts.len() >= 2 => {
+                self.emissive = parts[1].parse()?;
+            }
+            "emissive_color:" if parts.len() >= 4 => {
+                self.emissive_color = parse_vec3(parts)?;
+            }
+            "material:" if parts.len() >= 2 => {
+                self.material_path = Some(parts[1].to_string());
+            }
+            "parent:" if parts.len() >= 2 => {
+                self.parent = Some(parts[1].to_string());
+            }
+            "array:" | "radial:" | "scatter:" => {
+                self.pattern = Some(Pattern::parse(parts).map_err(|e| format!("In object '{}': {}", self.name, e))?);
+            }
+            "seed:" if parts.len() >= 2 => {
+                self.variation.seed = parts[1].parse()?;
+            }
+            "random_rotation:" if parts.len() >= 4 => {
+                self.variation.rotation = parse_vec3(parts)?;
+            }
+            "random_scale:" if parts.len() >= 3 => {
+                self.variation.scale = [parts[1].parse()?, parts[2].parse()?];
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    // load the referenced geometry and material and add the instance to the scene;
+    // parent names and patterns are queued in `deferred` with the new instance's index
+    fn finalize(
+        self,
+        scene: &mut Scene,
+        assets_root: &str,
+        deferred: &mut Deferred,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let geometry = match &self.primitive {
+            Some(spec) => ObjectGeometry::from_primitive(spec).map_err(|e| format!("In object '{}': {}", self.name, e))?,
+            None => ObjectGeometry::load_from_arobj(format!("{}/{}", assets_root, self.geometry_path))?,
+        };
+        let geometry_name = geometry.name.clone();
+        let mesh_material = geometry.material.clone();
+        scene.geometries.entry(geometry_name.clone()).or_insert(geometry);
+
+        let material = if let Some(mat) = self.material_path {
+            let material_path = format!("{}/{}", assets_root, mat);
+            Material::from_file(&material_path).unwrap_or_else(|e| {
+                eprintln!("Failed to load material: {}. Using default.", e);
+                Material::default()
+            })
+        } else {
+            // the mesh file's own material, e.g. from a glTF
+            mesh_material.unwrap_or_else(Material::default)
+        };
+
+        println!("Loaded instance '{}' referencing geometry '{}' at position {:?}", 
+            self.name, geometry_name, self.position);
+        if let Some(parent) = self.parent {
+            deferred.parent_links.push((scene.instances.len(), parent));
+        }
+        if let Some(pattern) = self.pattern {
+            deferred.patterns.push((scene.instances.len(), pattern, self.variation));
+        }
+        scene.instances.push(ObjectInstance {
+            name: self.name,
+            geometry_name,
+            transform: Transform {
+                position: self.position,
+                rotation: self.quaternion
+                    .unwrap_or_else(|| Transform::rotation_from_euler(self.rotation, self.rotation_order)),
+                scale: self.scale,
+            },
+            parent: None,
+            emissive: self.emissive,
+            emissive_color: self.emissive_color,
+            material,
+        });
+        Ok(())
+    }
+}
+
+// object links that can only be made once the whole file is read, by instance index
+#[derive(Default)]
+struct Deferred {
+    parent_links: Vec<(usize, String)>,
+    patterns: Vec<(usize, Pattern, Variation)>,
+    // sun and cameras of glTF includes, already moved by the include offset
+    included_sun: Option<Light>,
+    included_cameras: Vec<SceneCamera>,
+}
+
+impl Deferred {
+    // keep a glTF include's cameras, and its sun if it has one and no earlier include gave one
+    fn add_included_views(&mut self, included: &Scene, offset: &Transform) {
+        use cgmath::{InnerSpace, Point3, Rotation, Transform as _, Vector3};
+
+        let matrix = offset.to_matrix();
+        let rotate = |direction: [f32; 3]| -> Vector3<f32> { offset.rotation.rotate_vector(direction.into()) };
+        if included.has_light && self.included_sun.is_none() {
+            self.included_sun = Some(Light { direction: rotate(included.light.direction).into(), ..included.light });
+        }
+        for camera in &included.cameras {
+            let (yaw, pitch) = (camera.yaw.to_radians(), camera.pitch.to_radians());
+            let forward = rotate([yaw.cos() * pitch.cos(), pitch.sin(), yaw.sin() * pitch.cos()]).normalize();
+            self.included_cameras.push(SceneCamera {
+                position: matrix.transform_point(Point3::from(camera.position)).into(),
+                yaw: forward.z.atan2(forward.x).to_degrees(),
+                pitch: forward.y.clamp(-1.0, 1.0).asin().to_degrees(),
+                // the include scale is uniform
+                ortho_height: camera.ortho_height * offset.scale[0],
+                ..camera.clone()
+            });
+        }
+    }
+}
+
+// object or prefab block; its lines are kept until the block ends, so `prefab:` may appear anywhere in it
+struct ObjectBlock {
+    is_prefab: bool,
+    lines: Vec<String>,
+}
+
+impl ObjectBlock {
+    // start from the named prefab (if any), then apply the block's own keys on top
+    fn finish(
+        self,
+        scene: &mut Scene,
+        assets_root: &str,
+        prefabs: &mut HashMap<String, PendingObject>,
+        deferred: &mut Deferred,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let prefab_name = self.lines.iter()
+            .filter_map(|line| line.strip_prefix("prefab:"))
+            .map(str::trim)
+            .next_back();
+        let mut object = match prefab_name {
+            Some(name) => prefabs.get(name).cloned().ok_or_else(|| format!("Unknown prefab '{}'", name))?,
+            None => PendingObject::new(),
+        };
+        // an Euler rotation on the object overrides a quaternion from the prefab
+        if self.lines.iter().any(|line| line.starts_with("rotation:")) {
+            object.quaternion = None;
+        }
+        for line in &self.lines {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            object.apply_key(&parts)?;
+        }
+
+        // the prefab's name would be shared by every object made from it, and `parent:` couldn't tell them apart
+        let named = self.lines.iter().any(|line| line.starts_with("name:"));
+        if self.is_prefab {
+            if !named {
+                return Err("prefab block needs a name:".into());
+            }
+            object.instances_made = prefabs.get(&object.name).map_or(0, |earlier| earlier.instances_made);
+            prefabs.insert(object.name.clone(), object);
+            Ok(())
+        } else {
+            if !named && let Some(prefab) = prefab_name.and_then(|name| prefabs.get_mut(name)) {
+                prefab.instances_made += 1;
+                object.name = format!("{}{}", prefab.name, prefab.instances_made);
+            }
+            object.finalize(scene, assets_root, deferred)
+        }
+    }
+}
+
+// `include: <file> [position x y z] [rotation x y z] [scale s]`; the offset moves the file's root objects
+fn parse_include(parts: &[&str]) -> Result<(String, Transform), String> {
+    let file = parts.get(1).ok_or("include: needs a file")?;
+    let number = |index: usize| -> Result<f32, String> {
+        let value = parts.get(index).ok_or_else(|| format!("include is missing a value after '{}'", parts[index - 1]))?;
+        value.parse().map_err(|_| format!("invalid number '{}' in include", value))
+    };
+    let vec3 = |index: usize| -> Result<[f32; 3], String> { Ok([number(index)?, number(index + 1)?, number(index + 2)?]) };
+
+    let mut offset = Transform::new();
+    let mut index = 2;
+    while index < parts.len() {
+        match parts[index] {
+            "position" => { offset.position = vec3(index + 1)?; index += 4; }
+            "rotation" => { offset.rotation = Transform::rotation_from_euler(vec3(index + 1)?, RotationOrder::Xyz); index += 4; }
+            // uniform only, so the offset combines exactly with any object transform
+            "scale" => { offset.scale = [number(index + 1)?; 3]; index += 2; }
+            field => return Err(format!("unknown include field '{}'", field)),
+        }
+    }
+    Ok((file.to_string(), offset))
+}
+
+// parse three whitespace-separated floats following the key
+fn parse_vec3(parts: &[&str]) -> Result<[f32; 3], Box<dyn std::error::Error>> {
+    Ok([parts[1].parse()?, parts[2].parse()?, parts[3].parse()?])
 }
 
 impl Scene {
@@ -52,19 +435,60 @@ impl Scene {
             instances: Vec::new(),
             geometries: HashMap::new(),
             light: Light::default(),
+            has_light: false,
+            cameras: Vec::new(),
+            camera_paths: Vec::new(),
+            included_files: Vec::new(),
         }
     }
 
+    // camera marked `default: true`, else the first one defined
+    pub fn default_camera(&self) -> Option<&SceneCamera> {
+        self.cameras.iter().find(|camera| camera.is_default).or(self.cameras.first())
+    }
+
+    // append a camera block to a scene file so the bookmark survives restarts
+    pub fn save_camera_bookmark<P: AsRef<Path>>(path: P, camera: &SceneCamera) -> std::io::Result<()> {
+        let mut file = fs::OpenOptions::new().append(true).open(path)?;
+        write!(file, "\n{}", camera.to_arsc())
+    }
+
     // load scene from .arsc file format
     pub fn load_from_arsc<P: AsRef<Path>>(
         path: P,
         assets_root: &str,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = fs::read_to_string(path.as_ref())?;
+        Self::load_arsc_file(path.as_ref(), assets_root, &mut Vec::new(), &mut HashMap::new())
+    }
+
+    // include_chain lists the files being loaded, outermost first;
+    // prefabs are shared, so an included file can use earlier prefabs and define new ones
+    fn load_arsc_file(
+        path: &Path,
+        assets_root: &str,
+        include_chain: &mut Vec<PathBuf>,
+        prefabs: &mut HashMap<String, PendingObject>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let same_file = |a: &Path, b: &Path| match (fs::canonicalize(a), fs::canonicalize(b)) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => a == b,
+        };
+        if include_chain.iter().any(|loading| same_file(loading, path)) {
+            let chain: Vec<String> = include_chain.iter()
+                .chain(std::iter::once(&path.to_path_buf()))
+                .map(|file| file.display().to_string())
+                .collect();
+            return Err(format!("Include cycle: {}", chain.join(" -> ")).into());
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read scene '{}': {}", path.display(), e))?;
+        include_chain.push(path.to_path_buf());
         let mut scene = Scene::new(String::from("Unnamed Scene"));
-        
-        // (geometry_path, instance_name, position, rotation, scale, emissive, emissive_color, material_path)
-        let mut current_object: Option<(String, String, [f32; 3], [f32; 3], [f32; 3], f32, [f32; 3], Option<String>)> = None;
+        let mut current_object: Option<ObjectBlock> = None;
+        let mut current_camera: Option<PendingCamera> = None;
+        let mut current_path: Option<CameraPath> = None;
+        let mut deferred = Deferred::default();
 
         for line in content.lines() {
             let line = line.trim();
@@ -79,197 +503,375 @@ impl Scene {
             }
 
             match parts[0] {
-                "scene_name" => {
-                    if parts.len() > 1 {
-                        scene.name = parts[1..].join(" ");
-                    }
+                "scene_name" if parts.len() > 1 => {
+                    scene.name = parts[1..].join(" ");
                 }
                 "light" => {
                     // parse light definition (stays in light block until next keyword)
+                    scene.has_light = true;
                 }
-                "light_direction:" => {
-                    if parts.len() >= 4 {
-                        scene.light.direction = [
-                            parts[1].parse()?,
-                            parts[2].parse()?,
-                            parts[3].parse()?,
-                        ];
-                    }
-                }
-                "light_color:" => {
-                    if parts.len() >= 4 {
-                        scene.light.color = [
-                            parts[1].parse()?,
-                            parts[2].parse()?,
-                            parts[3].parse()?,
-                        ];
-                    }
+                "light_direction:" if parts.len() >= 4 => {
+                    scene.light.direction = parse_vec3(&parts)?;
+                    scene.has_light = true;
                 }
-                "light_intensity:" => {
-                    if parts.len() >= 2 {
-                        scene.light.intensity = parts[1].parse()?;
-                    }
+                "light_color:" if parts.len() >= 4 => {
+                    scene.light.color = parse_vec3(&parts)?;
+                    scene.has_light = true;
                 }
-                "ambient_strength:" => {
-                    if parts.len() >= 2 {
-                        scene.light.ambient_strength = parts[1].parse()?;
-                    }
+                "light_intensity:" if parts.len() >= 2 => {
+                    scene.light.intensity = parts[1].parse()?;
+                    scene.has_light = true;
                 }
-                "object" => {
-                    // finalize previous object before starting new one
-                    if let Some((geom_path, inst_name, pos, rot, scl, emis, emis_color, mat_path)) = current_object.take() {
-                        let arobj_path = format!("{}/{}", assets_root, geom_path);
-                        let geometry = ObjectGeometry::load_from_arobj(&arobj_path)?;
-                        let geometry_name = geometry.name.clone();
-                        scene.geometries.entry(geometry_name.clone()).or_insert(geometry);
-                        
-                        let material = if let Some(mat) = mat_path {
-                            let material_path = format!("{}/{}", assets_root, mat);
-                            Material::from_file(&material_path).unwrap_or_else(|e| {
-                                eprintln!("Failed to load material: {}. Using default.", e);
-                                Material::default()
-                            })
-                        } else {
-                            Material::default()
-                        };
-                        
-                        let instance = ObjectInstance {
-                            name: inst_name.clone(),
-                            geometry_name: geometry_name.clone(),
-                            transform: Transform {
-                                position: pos,
-                                rotation: rot,
-                                scale: scl,
-                            },
-                            emissive: emis,
-                            emissive_color: emis_color,
-                            material,
-                        };
-                        println!("Loaded instance '{}' referencing geometry '{}' at position {:?}", 
-                            inst_name, geometry_name, pos);
-                        scene.instances.push(instance);
-                    }
-                    current_object = Some((
-                        String::new(),
-                        String::new(),
-                        [0.0, 0.0, 0.0],
-                        [0.0, 0.0, 0.0],
-                        [1.0, 1.0, 1.0],
-                        0.0,
-                        [1.0, 1.0, 1.0],
-                        None,
-                    ));
+                "ambient_strength:" if parts.len() >= 2 => {
+                    scene.light.ambient_strength = parts[1].parse()?;
+                    scene.has_light = true;
                 }
-                "geometry:" => {
-                    if let Some(ref mut obj) = current_object {
-                        if parts.len() > 1 {
-                            obj.0 = parts[1].to_string();
-                        }
+                "object" | "prefab" | "camera" | "camera_path" | "include:" => {
+                    // finalize previous block before starting new one
+                    if let Some(block) = current_object.take() {
+                        block.finish(&mut scene, assets_root, prefabs, &mut deferred)?;
                     }
-                }
-                "name:" => {
-                    if let Some(ref mut obj) = current_object {
-                        if parts.len() > 1 {
-                            obj.1 = parts[1].to_string();
-                        }
+                    if let Some(cam) = current_camera.take() {
+                        cam.finalize(&mut scene);
                     }
-                }
-                "position:" => {
-                    if let Some(ref mut obj) = current_object {
-                        if parts.len() >= 4 {
-                            obj.2 = [
-                                parts[1].parse()?,
-                                parts[2].parse()?,
-                                parts[3].parse()?,
-                            ];
-                        }
+                    if let Some(path) = current_path.take() {
+                        path.validate()?;
+                        scene.camera_paths.push(path);
                     }
-                }
-                "rotation:" => {
-                    if let Some(ref mut obj) = current_object {
-                        if parts.len() >= 4 {
-                            obj.3 = [
-                                parts[1].parse()?,
-                                parts[2].parse()?,
-                                parts[3].parse()?,
-                            ];
+                    match parts[0] {
+                        "object" | "prefab" => {
+                            current_object = Some(ObjectBlock { is_prefab: parts[0] == "prefab", lines: Vec::new() });
                         }
-                    }
-                }
-                "scale:" => {
-                    if let Some(ref mut obj) = current_object {
-                        if parts.len() >= 4 {
-                            obj.4 = [
-                                parts[1].parse()?,
-                                parts[2].parse()?,
-                                parts[3].parse()?,
-                            ];
+                        "include:" => {
+                            let (file, offset) = parse_include(&parts)
+                                .map_err(|e| format!("In '{}': {}", path.display(), e))?;
+                            let include_path = PathBuf::from(format!("{}/{}", assets_root, file));
+                            let included = match include_path.extension().and_then(|extension| extension.to_str()) {
+                                Some("gltf" | "glb") => {
+                                    let included = Self::load_from_gltf(&include_path, assets_root)?;
+                                    deferred.add_included_views(&included, &offset);
+                                    included
+                                }
+                                _ => Self::load_arsc_file(&include_path, assets_root, include_chain, prefabs)?,
+                            };
+                            scene.merge_included(included, include_path, &offset);
                         }
-                    }
-                }
-                "emissive:" => {
-                    if let Some(ref mut obj) = current_object {
-                        if parts.len() >= 2 {
-                            obj.5 = parts[1].parse()?;
+                        "camera" => {
+                            current_camera = Some(PendingCamera {
+                                camera: SceneCamera {
+                                    name: format!("Camera{}", scene.cameras.len() + 1),
+                                    ..SceneCamera::default()
+                                },
+                                look_at: None,
+                            });
                         }
+                        _ => current_path = Some(CameraPath::new(format!("Path{}", scene.camera_paths.len() + 1))),
                     }
                 }
-                "emissive_color:" => {
-                    if let Some(ref mut obj) = current_object {
-                        if parts.len() >= 4 {
-                            obj.6 = [
-                                parts[1].parse()?,
-                                parts[2].parse()?,
-                                parts[3].parse()?,
-                            ];
+                key => {
+                    if let Some(ref mut path) = current_path {
+                        match key {
+                            "name:" if parts.len() > 1 => {
+                                path.name = parts[1].to_string();
+                            }
+                            "interpolation:" if parts.len() >= 2 => {
+                                path.interpolation = Interpolation::from_name(parts[1])
+                                    .ok_or_else(|| format!("Unknown interpolation '{}' in camera path '{}'", parts[1], path.name))?;
+                            }
+                            "easing:" if parts.len() >= 2 => {
+                                path.easing = Easing::from_name(parts[1])
+                                    .ok_or_else(|| format!("Unknown easing '{}' in camera path '{}'", parts[1], path.name))?;
+                            }
+                            "loop:" if parts.len() >= 2 => {
+                                path.looping = parts[1].parse()?;
+                            }
+                            "key:" => {
+                                let key = PathKey::parse(&parts[1..], path.keys.last())
+                                    .map_err(|e| format!("In camera path '{}': {}", path.name, e))?;
+                                path.keys.push(key);
+                            }
+                            _ => {}
                         }
-                    }
-                }
-                "material:" => {
-                    if let Some(ref mut obj) = current_object {
-                        if parts.len() >= 2 {
-                            obj.7 = Some(parts[1].to_string());
+                    } else if let Some(ref mut pending) = current_camera {
+                        let cam = &mut pending.camera;
+                        match key {
+                            "name:" if parts.len() > 1 => {
+                                cam.name = parts[1].to_string();
+                            }
+                            "position:" if parts.len() >= 4 => {
+                                cam.position = parse_vec3(&parts)?;
+                            }
+                            "look_at:" if parts.len() >= 4 => {
+                                pending.look_at = Some(parse_vec3(&parts)?);
+                            }
+                            "yaw:" if parts.len() >= 2 => {
+                                cam.yaw = parts[1].parse()?;
+                            }
+                            "pitch:" if parts.len() >= 2 => {
+                                cam.pitch = parts[1].parse()?;
+                            }
+                            "fov:" if parts.len() >= 2 => {
+                                cam.fovy = parts[1].parse()?;
+                            }
+                            "near:" if parts.len() >= 2 => {
+                                cam.znear = parts[1].parse()?;
+                            }
+                            "far:" if parts.len() >= 2 => {
+                                cam.zfar = parts[1].parse()?;
+                            }
+                            "projection:" if parts.len() >= 2 => {
+                                cam.projection = Projection::from_name(parts[1])
+                                    .ok_or_else(|| format!("Unknown projection '{}' in camera '{}'", parts[1], cam.name))?;
+                            }
+                            "ortho_height:" if parts.len() >= 2 => {
+                                cam.ortho_height = parts[1].parse()?;
+                            }
+                            "default:" if parts.len() >= 2 => {
+                                cam.is_default = parts[1].parse()?;
+                            }
+                            _ => {}
                         }
+                    } else if let Some(ref mut block) = current_object {
+                        block.lines.push(line.to_string());
                     }
                 }
-                _ => {}
             }
         }
         
-        if let Some((geom_path, inst_name, pos, rot, scl, emis, emis_color, mat_path)) = current_object.take() {
-            let arobj_path = format!("{}/{}", assets_root, geom_path);
-            let geometry = ObjectGeometry::load_from_arobj(&arobj_path)?;
-            let geometry_name = geometry.name.clone();
-            scene.geometries.entry(geometry_name.clone()).or_insert(geometry);
-            
-            let material = if let Some(mat) = mat_path {
-                let material_path = format!("{}/{}", assets_root, mat);
-                Material::from_file(&material_path).unwrap_or_else(|e| {
-                    eprintln!("Failed to load material: {}. Using default.", e);
-                    Material::default()
-                })
-            } else {
-                Material::default()
+        if let Some(block) = current_object.take() {
+            block.finish(&mut scene, assets_root, prefabs, &mut deferred)?;
+        }
+        if let Some(cam) = current_camera.take() {
+            cam.finalize(&mut scene);
+        }
+        if let Some(path) = current_path.take() {
+            path.validate()?;
+            scene.camera_paths.push(path);
+        }
+        // a glTF include's sun stands in only when this file sets no light, and its cameras come after
+        // this file's own so they don't change which camera is the default
+        if !scene.has_light && let Some(sun) = deferred.included_sun {
+            scene.light = sun;
+            scene.has_light = true;
+        }
+        if !scene.cameras.is_empty() {
+            deferred.included_cameras.iter_mut().for_each(|camera| camera.is_default = false);
+        }
+        scene.cameras.extend(deferred.included_cameras);
+        scene.resolve_parents(deferred.parent_links)?;
+        scene.expand_patterns(deferred.patterns)?;
+        include_chain.pop();
+
+        Ok(scene)
+    }
+
+    // append an included file's objects; only its root objects are moved by the offset, children follow them.
+    // The included file's name is left out, and so are its light and cameras unless it is a glTF file
+    // (see Deferred::add_included_views)
+    fn merge_included(&mut self, included: Scene, path: PathBuf, offset: &Transform) {
+        let first_index = self.instances.len();
+        for mut instance in included.instances {
+            match instance.parent {
+                Some(parent) => instance.parent = Some(first_index + parent),
+                None => instance.transform = offset.combine(&instance.transform),
+            }
+            self.instances.push(instance);
+        }
+        for (name, geometry) in included.geometries {
+            self.geometries.entry(name).or_insert(geometry);
+        }
+        self.included_files.push(path);
+        self.included_files.extend(included.included_files);
+    }
+
+    // turn `parent:` names into indices; names must be unique among objects used as parents
+    fn resolve_parents(&mut self, parent_links: Vec<(usize, String)>) -> Result<(), String> {
+        for (child, parent_name) in parent_links {
+            let parent = self.find_unique(&parent_name)
+                .map_err(|e| format!("Object '{}' has parent '{}': {}", self.instances[child].name, parent_name, e))?;
+            self.set_parent(child, Some(parent))?;
+        }
+        Ok(())
+    }
+
+    // index of the only instance with this name
+    fn find_unique(&self, name: &str) -> Result<usize, String> {
+        let mut candidates = self.instances.iter()
+            .enumerate()
+            .filter(|(_, instance)| instance.name == name)
+            .map(|(index, _)| index);
+        match (candidates.next(), candidates.next()) {
+            (Some(index), None) => Ok(index),
+            (None, _) => Err("no object has that name".to_string()),
+            (Some(_), Some(_)) => Err("several objects have that name".to_string()),
+        }
+    }
+
+    // replace each patterned instance by its copies, named `<name>_0`, `<name>_1`, ...;
+    // the first copy keeps the instance's index and the rest go to the end, so no other index moves
+    fn expand_patterns(&mut self, patterns: Vec<(usize, Pattern, Variation)>) -> Result<(), String> {
+        for (index, pattern, variation) in patterns {
+            let template = self.instances[index].clone();
+            if self.instances.iter().any(|instance| instance.parent == Some(index)) {
+                return Err(format!("Object '{}' is repeated by a pattern, so it can't be a parent", template.name));
+            }
+            let surface = match &pattern {
+                Pattern::Scatter { region: ScatterRegion::Surface(target), .. } => Some(self.surface_sampler(target, template.parent)
+                    .map_err(|e| format!("Object '{}' scatters on '{}': {}", template.name, target, e))?),
+                _ => None,
             };
-            
-            let instance = ObjectInstance {
-                name: inst_name.clone(),
-                geometry_name: geometry_name.clone(),
-                transform: Transform {
-                    position: pos,
-                    rotation: rot,
-                    scale: scl,
-                },
-                emissive: emis,
-                emissive_color: emis_color,
-                material,
+
+            let transforms = pattern::expand(&pattern, &template.transform, &variation, surface.as_ref());
+            for (copy, transform) in transforms.into_iter().enumerate() {
+                let instance = ObjectInstance {
+                    name: format!("{}_{}", template.name, copy),
+                    transform,
+                    ..template.clone()
+                };
+                if copy == 0 {
+                    self.instances[index] = instance;
+                } else {
+                    self.instances.push(instance);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // sampler over a named instance's triangles, in the space of `parent` (the world if None)
+    fn surface_sampler(&self, target: &str, parent: Option<usize>) -> Result<SurfaceSampler, String> {
+        use cgmath::SquareMatrix;
+
+        let index = self.find_unique(target)?;
+        let geometry = self.geometries.get(&self.instances[index].geometry_name)
+            .ok_or("its geometry isn't loaded")?;
+        let to_parent = match parent {
+            Some(parent) => self.world_matrix(parent).invert().ok_or("the parent's transform can't be inverted")?,
+            None => cgmath::Matrix4::identity(),
+        };
+        SurfaceSampler::new(geometry, to_parent * self.world_matrix(index)).ok_or_else(|| "it has no surface area".to_string())
+    }
+
+    // attach an instance to a parent (None detaches it); its local transform is kept, so it moves with the parent
+    pub fn set_parent(&mut self, child: usize, parent: Option<usize>) -> Result<(), String> {
+        if child >= self.instances.len() || parent.is_some_and(|parent| parent >= self.instances.len()) {
+            return Err(format!("Instance index out of range ({} instances)", self.instances.len()));
+        }
+        // walk up from the new parent; reaching the child means the link would close a loop
+        let mut chain = vec![child];
+        let mut ancestor = parent;
+        while let Some(index) = ancestor {
+            chain.push(index);
+            if index == child {
+                let names: Vec<&str> = chain.iter().rev().map(|&index| self.instances[index].name.as_str()).collect();
+                return Err(format!("Parent cycle: {}", names.join(" -> ")));
+            }
+            ancestor = self.instances[index].parent;
+        }
+        self.instances[child].parent = parent;
+        Ok(())
+    }
+
+    // local-to-world matrix: the parents' matrices applied on top of the instance's own
+    pub fn world_matrix(&self, index: usize) -> cgmath::Matrix4<f32> {
+        let instance = &self.instances[index];
+        let local = instance.transform.to_matrix();
+        match instance.parent {
+            Some(parent) => self.world_matrix(parent) * local,
+            None => local,
+        }
+    }
+
+    // world matrices of all instances, each parent computed once
+    pub fn world_matrices(&self) -> Vec<cgmath::Matrix4<f32>> {
+        let mut matrices: Vec<Option<cgmath::Matrix4<f32>>> = vec![None; self.instances.len()];
+        for index in 0..self.instances.len() {
+            // climb to the nearest ancestor that's already done, then fill in on the way back down
+            let mut pending = vec![index];
+            while let Some(parent) = self.instances[*pending.last().unwrap()].parent {
+                if matrices[parent].is_some() {
+                    break;
+                }
+                pending.push(parent);
+            }
+            while let Some(current) = pending.pop() {
+                if matrices[current].is_some() {
+                    continue;
+                }
+                let local = self.instances[current].transform.to_matrix();
+                matrices[current] = Some(match self.instances[current].parent {
+                    Some(parent) => matrices[parent].unwrap() * local,
+                    None => local,
+                });
+            }
+        }
+        matrices.into_iter().map(Option::unwrap).collect()
+    }
+
+    pub fn world_position(&self, index: usize) -> [f32; 3] {
+        let matrix = self.world_matrix(index);
+        [matrix.w.x, matrix.w.y, matrix.w.z]
+    }
+
+    #[allow(dead_code)]
+    pub fn children(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.instances.iter()
+            .enumerate()
+            .filter(move |(_, instance)| instance.parent == Some(index))
+            .map(|(child, _)| child)
+    }
+
+    // every instance below this one, children before grandchildren
+    pub fn descendants(&self, index: usize) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut next = 0;
+        let mut current = index;
+        loop {
+            found.extend(self.instances.iter()
+                .enumerate()
+                .filter(|(_, instance)| instance.parent == Some(current))
+                .map(|(child, _)| child));
+            let Some(&descendant) = found.get(next) else {
+                return found;
             };
-            println!("Loaded instance '{}' referencing geometry '{}' at position {:?}", 
-                inst_name, geometry_name, pos);
-            scene.instances.push(instance);
+            current = descendant;
+            next += 1;
         }
+    }
 
-        Ok(scene)
+    // remove an instance together with its descendants; remaining parent indices are renumbered
+    pub fn remove_instance(&mut self, index: usize) -> Option<ObjectInstance> {
+        if index >= self.instances.len() {
+            return None;
+        }
+        let mut removed = vec![false; self.instances.len()];
+        removed[index] = true;
+        for descendant in self.descendants(index) {
+            removed[descendant] = true;
+        }
+
+        // new index of every kept instance
+        let mut new_indices = vec![None; self.instances.len()];
+        let mut next = 0;
+        for (old, is_removed) in removed.iter().enumerate() {
+            if !is_removed {
+                new_indices[old] = Some(next);
+                next += 1;
+            }
+        }
+
+        let mut root = None;
+        let instances = std::mem::take(&mut self.instances);
+        for (old, mut instance) in instances.into_iter().enumerate() {
+            if old == index {
+                instance.parent = None;
+                root = Some(instance);
+            } else if !removed[old] {
+                instance.parent = instance.parent.and_then(|parent| new_indices[parent]);
+                self.instances.push(instance);
+            }
+        }
+        root
     }
 
     #[allow(dead_code)]
@@ -282,3 +884,199 @@ impl Scene {
         self.geometries.get(name)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUN_AND_CAMERA_GLTF: &str = r#"{
+        "asset": {"version": "2.0"},
+        "extensionsUsed": ["KHR_lights_punctual"],
+        "extensions": {"KHR_lights_punctual": {"lights": [{"type": "directional", "intensity": 3.0}]}},
+        "cameras": [{"type": "perspective", "perspective": {"yfov": 0.8, "znear": 0.1, "zfar": 50.0}}],
+        "scenes": [{"nodes": [0, 1]}],
+        "nodes": [
+            {"name": "Sun", "extensions": {"KHR_lights_punctual": {"light": 0}}},
+            {"name": "View", "camera": 0, "translation": [0.0, 0.0, 5.0]}
+        ]
+    }"#;
+
+    fn assert_near(actual: [f32; 3], expected: [f32; 3]) {
+        assert!(actual.iter().zip(expected).all(|(a, e)| (a - e).abs() < 1e-4), "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn gltf_includes_bring_their_sun_and_cameras() {
+        let root = std::env::temp_dir().join(format!("arbitra_include_{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("views.gltf"), SUN_AND_CAMERA_GLTF).unwrap();
+        let include = "include: views.gltf position 1.0 0.0 0.0 rotation 0.0 90.0 0.0\n";
+        fs::write(root.join("plain.arsc"), include).unwrap();
+        fs::write(root.join("lit.arsc"), format!("camera\nname: Own\nposition: 0.0 1.0 0.0\n{}light\nlight_intensity: 0.5\n", include)).unwrap();
+        let assets_root = root.to_str().unwrap();
+
+        let plain = Scene::load_from_arsc(root.join("plain.arsc"), assets_root).unwrap();
+        assert!(plain.has_light);
+        assert_eq!(plain.light.intensity, 3.0);
+        assert_near(plain.light.direction, [-1.0, 0.0, 0.0]);
+        assert_eq!(plain.cameras.len(), 1);
+        let camera = &plain.cameras[0];
+        assert_near(camera.position, [6.0, 0.0, 0.0]);
+        assert!((camera.yaw.abs() - 180.0).abs() < 1e-3 && camera.pitch.abs() < 1e-3, "yaw {} pitch {}", camera.yaw, camera.pitch);
+        assert!(camera.is_default);
+
+        // the including file's own light and default camera win
+        let lit = Scene::load_from_arsc(root.join("lit.arsc"), assets_root).unwrap();
+        assert_eq!(lit.light.intensity, 0.5);
+        assert_eq!(lit.cameras.len(), 2);
+        assert_eq!(lit.default_camera().unwrap().name, "Own");
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    // instances named after their index, each with `parents[i]` as its parent
+    fn hierarchy(transforms: &[Transform], parents: &[Option<usize>]) -> Scene {
+        let mut scene = Scene::new("hierarchy".to_string());
+        for (index, (transform, &parent)) in transforms.iter().zip(parents).enumerate() {
+            scene.add_instance(ObjectInstance {
+                name: index.to_string(),
+                geometry_name: "cube".to_string(),
+                transform: *transform,
+                parent,
+                emissive: 0.0,
+                emissive_color: [1.0; 3],
+                material: Material::default(),
+            });
+        }
+        scene
+    }
+
+    #[test]
+    fn parent_cycles_are_rejected() {
+        let mut scene = hierarchy(&[Transform::new(); 3], &[None, Some(0), Some(1)]);
+        assert_eq!(scene.set_parent(0, Some(2)).unwrap_err(), "Parent cycle: 0 -> 1 -> 2 -> 0");
+        assert_eq!(scene.set_parent(1, Some(1)).unwrap_err(), "Parent cycle: 1 -> 1");
+        assert_eq!(scene.instances[0].parent, None);
+        assert!(scene.set_parent(2, Some(3)).is_err());
+
+        // moving a branch under a sibling is fine
+        scene.set_parent(2, None).unwrap();
+        scene.set_parent(1, Some(2)).unwrap();
+        assert_eq!(scene.instances[1].parent, Some(2));
+        assert_eq!(scene.descendants(0), Vec::<usize>::new());
+        assert_eq!(scene.descendants(2), [1]);
+    }
+
+    #[test]
+    fn removing_an_instance_takes_its_descendants_and_renumbers_the_rest() {
+        // 0 <- 1 <- 2, 0 <- 3, 4 <- 5
+        let parents = [None, Some(0), Some(1), Some(0), None, Some(4)];
+        let mut scene = hierarchy(&[Transform::new(); 6], &parents);
+        let removed = scene.remove_instance(1).unwrap();
+        assert_eq!(removed.name, "1");
+        assert_eq!(removed.parent, None);
+
+        let names: Vec<&str> = scene.instances.iter().map(|instance| instance.name.as_str()).collect();
+        assert_eq!(names, ["0", "3", "4", "5"]);
+        let parents: Vec<Option<usize>> = scene.instances.iter().map(|instance| instance.parent).collect();
+        assert_eq!(parents, [None, Some(0), None, Some(2)]);
+        assert!(scene.remove_instance(4).is_none());
+    }
+
+    #[test]
+    fn world_matrices_apply_the_parents_first() {
+        let transforms = [
+            Transform::new().with_position(10.0, 0.0, 0.0).with_rotation(0.0, 90.0, 0.0),
+            Transform::new().with_position(0.0, 0.0, 1.0).with_scale(2.0, 2.0, 2.0),
+            Transform::new().with_position(1.0, 0.0, 0.0),
+        ];
+        let scene = hierarchy(&transforms, &[None, Some(0), Some(1)]);
+        // rotating +z by 90 degrees about y gives +x; the child's scale doubles the grandchild's offset
+        assert_near(scene.world_position(1), [11.0, 0.0, 0.0]);
+        assert_near(scene.world_position(2), [11.0, 0.0, -2.0]);
+
+        let matrices = scene.world_matrices();
+        for (index, matrix) in matrices.iter().enumerate() {
+            let expected = scene.world_matrix(index);
+            for column in 0..4 {
+                assert_near(matrix[column].truncate().into(), expected[column].truncate().into());
+            }
+        }
+        let expected = transforms[0].to_matrix() * transforms[1].to_matrix() * transforms[2].to_matrix();
+        assert_near(matrices[2].w.truncate().into(), expected.w.truncate().into());
+    }
+
+    #[test]
+    fn includes_nest_and_report_cycles() {
+        let root = std::env::temp_dir().join(format!("arbitra_nested_include_{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let assets_root = root.to_str().unwrap();
+        fs::write(root.join("leaf.arsc"), "object\nname: Leaf\nprimitive: cylinder\nposition: 1.0 0.0 0.0\n").unwrap();
+        fs::write(root.join("middle.arsc"), "include: leaf.arsc position 0.0 2.0 0.0 scale 2.0\nobject\nname: Middle\nprimitive: cone\n").unwrap();
+        fs::write(root.join("top.arsc"), "include: middle.arsc position 0.0 0.0 3.0\n").unwrap();
+
+        let scene = Scene::load_from_arsc(root.join("top.arsc"), assets_root).unwrap();
+        let names: Vec<&str> = scene.instances.iter().map(|instance| instance.name.as_str()).collect();
+        assert_eq!(names, ["Leaf", "Middle"]);
+        // offsets compose: the leaf's position is scaled by the middle include, then both are moved
+        assert_near(scene.world_position(0), [2.0, 2.0, 3.0]);
+        assert_near(scene.world_position(1), [0.0, 0.0, 3.0]);
+        assert_eq!(scene.included_files.len(), 2);
+
+        fs::write(root.join("a.arsc"), "include: b.arsc\n").unwrap();
+        fs::write(root.join("b.arsc"), "object\nname: B\nprimitive: cone\ninclude: a.arsc\n").unwrap();
+        let error = Scene::load_from_arsc(root.join("a.arsc"), assets_root).unwrap_err().to_string();
+        let (a, b) = (root.join("a.arsc"), format!("{}/b.arsc", assets_root));
+        assert_eq!(error, format!("Include cycle: {} -> {} -> {}/a.arsc", a.display(), b, assets_root));
+
+        fs::write(root.join("self.arsc"), "include: self.arsc\n").unwrap();
+        assert!(Scene::load_from_arsc(root.join("self.arsc"), assets_root).unwrap_err().to_string().starts_with("Include cycle"));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn prefab_instances_get_their_own_names() {
+        let root = std::env::temp_dir().join(format!("arbitra_prefab_{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        let assets_root = root.to_str().unwrap();
+        fs::write(root.join("prefabs.arsc"), "prefab\nname: Post\nprimitive: cylinder\nscale: 0.5 2.0 0.5\n").unwrap();
+        fs::write(root.join("fence.arsc"), "\
+include: prefabs.arsc
+prefab
+prefab: Post
+name: TallPost
+scale: 0.5 4.0 0.5
+object
+prefab: Post
+position: 1.0 0.0 0.0
+object
+prefab: Post
+name: Corner
+object
+prefab: Post
+position: 2.0 0.0 0.0
+object
+name: Cap
+primitive: cone
+parent: Post2
+position: 0.0 1.0 0.0
+object
+prefab: TallPost
+").unwrap();
+
+        let scene = Scene::load_from_arsc(root.join("fence.arsc"), assets_root).unwrap();
+        let names: Vec<&str> = scene.instances.iter().map(|instance| instance.name.as_str()).collect();
+        assert_eq!(names, ["Post1", "Corner", "Post2", "Cap", "TallPost1"]);
+        // values come from the prefab, and from the prefab it starts from
+        assert_eq!(scene.instances[1].transform.scale, [0.5, 2.0, 0.5]);
+        assert_eq!(scene.instances[4].transform.scale, [0.5, 4.0, 0.5]);
+        assert_eq!(scene.instances[3].parent, Some(2));
+        assert_near(scene.world_position(3), [2.0, 2.0, 0.0]);
+
+        fs::write(root.join("unnamed.arsc"), "prefab\nprimitive: cone\n").unwrap();
+        assert!(Scene::load_from_arsc(root.join("unnamed.arsc"), assets_root).is_err());
+        fs::write(root.join("unnamed.arsc"), "include: prefabs.arsc\nprefab\nprefab: Post\nscale: 1.0 1.0 1.0\n").unwrap();
+        assert!(Scene::load_from_arsc(root.join("unnamed.arsc"), assets_root).is_err());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::preprocessor::Preprocessor;
+
+// set to a directory containing shader.wgsl to load shaders from disk and hot reload them
+pub const SHADER_DIR_ENV: &str = "ARBITRA_SHADER_DIR";
+// comma-separated defines for shader variants, e.g. "ALPHA_MASK,ALPHA_CUTOFF=0.3"
+pub const SHADER_DEFINES_ENV: &str = "ARBITRA_SHADER_DEFINES";
+const SHADER_FILE: &str = "shader.wgsl";
+// every shader module baked into the binary, looked up by #include name
+const EMBEDDED_SHADERS: [(&str, &str); 3] = [
+    ("shader.wgsl", include_str!("shaders/shader.wgsl")),
+    ("structs.wgsl", include_str!("shaders/structs.wgsl")),
+    ("lighting.wgsl", include_str!("shaders/lighting.wgsl")),
+];
+
+/// Size and member offsets of a Rust struct that mirrors a WGSL struct
+pub struct StructLayout {
+    pub name: &'static str,
+    pub size: usize,
+    // (member name, byte offset); padding members can be left out
+    pub members: Vec<(&'static str, usize)>,
+}
+
+/// Provides the main WGSL shader, either embedded in the binary or read from disk
+pub struct ShaderLoader {
+    dir: Option<PathBuf>,
+    defines: HashMap<String, String>,
+    struct_layouts: Vec<StructLayout>,
+}
+
+impl ShaderLoader {
+    pub fn from_env(struct_layouts: Vec<StructLayout>) -> Self {
+        let defines = std::env::var(SHADER_DEFINES_ENV)
+            .map(|defines| parse_defines(&defines))
+            .unwrap_or_default();
+
+        ShaderLoader {
+            dir: std::env::var_os(SHADER_DIR_ENV).map(PathBuf::from),
+            defines,
+            struct_layouts,
+        }
+    }
+
+    // directory to watch for changes, None when using the embedded shader
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    // preprocess and validate the shader; errors are formatted with source spans
+    pub fn load(&self) -> Result<String, String> {
+        match &self.dir {
+            Some(dir) => self.build(|name| {
+                let path = dir.join(name);
+                fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read shader '{}': {}", path.display(), e))
+            }),
+            None => self.load_embedded(),
+        }
+    }
+
+    // the embedded shader, also used when the disk version fails at startup
+    pub fn load_embedded(&self) -> Result<String, String> {
+        self.build(|name| {
+            EMBEDDED_SHADERS.iter()
+                .find(|(file, _)| *file == name)
+                .map(|(_, source)| source.to_string())
+                .ok_or_else(|| format!("No embedded shader named '{}'", name))
+        })
+    }
+
+    fn build<F: Fn(&str) -> Result<String, String>>(&self, resolve_include: F) -> Result<String, String> {
+        let source = Preprocessor::new(self.defines.clone(), resolve_include).process(SHADER_FILE)?;
+        let module = validate_wgsl(&source, &format!("{} (preprocessed)", SHADER_FILE))?;
+        check_struct_layouts(&module, &self.struct_layouts)?;
+        Ok(source)
+    }
+}
+
+fn parse_defines(defines: &str) -> HashMap<String, String> {
+    defines.split(',')
+        .map(str::trim)
+        .filter(|define| !define.is_empty())
+        .map(|define| match define.split_once('=') {
+            Some((name, value)) => (name.trim().to_string(), value.trim().to_string()),
+            None => (define.to_string(), String::new()),
+        })
+        .collect()
+}
+
+// run naga's parser and validator, so broken WGSL is reported instead of panicking in wgpu
+pub fn validate_wgsl(source: &str, path: &str) -> Result<naga::Module, String> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|e| e.emit_to_string_with_path(source, path))?;
+    naga::valid::Validator::new(naga::valid::ValidationFlags::all(), naga::valid::Capabilities::default())
+        .validate(&module)
+        .map_err(|e| e.emit_to_string_with_path(source, path))?;
+    Ok(module)
+}
+
+// compare WGSL struct layouts against their Rust counterparts
+pub fn check_struct_layouts(module: &naga::Module, layouts: &[StructLayout]) -> Result<(), String> {
+    let mut errors = Vec::new();
+
+    for layout in layouts {
+        let wgsl_struct = module.types.iter().find_map(|(_, ty)| match &ty.inner {
+            naga::TypeInner::Struct { members, span } if ty.name.as_deref() == Some(layout.name) => Some((members, *span)),
+            _ => None,
+        });
+        let Some((members, span)) = wgsl_struct else {
+            errors.push(format!("struct {} is missing from the shader", layout.name));
+            continue;
+        };
+
+        if span as usize != layout.size {
+            errors.push(format!("struct {} is {} bytes in WGSL but {} bytes in Rust", layout.name, span, layout.size));
+        }
+        for &(member_name, offset) in &layout.members {
+            match members.iter().find(|member| member.name.as_deref() == Some(member_name)) {
+                Some(member) if member.offset as usize == offset => {}
+                Some(member) => errors.push(format!("{}.{} is at offset {} in WGSL but {} in Rust",
+                    layout.name, member_name, member.offset, offset)),
+                None => errors.push(format!("{}.{} is missing from the shader", layout.name, member_name)),
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Shader struct layouts don't match the Rust side:\n  {}", errors.join("\n  ")))
+    }
+}
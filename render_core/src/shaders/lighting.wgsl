@@ -0,0 +1,30 @@
+#include "structs.wgsl"
+
+fn ambient_lighting(light: Light) -> vec3<f32> {
+    return light.ambient_strength * vec3<f32>(1.0, 1.0, 1.0);
+}
+
+fn directional_lighting(light: Light, world_normal: vec3<f32>) -> vec3<f32> {
+    let light_dir = normalize(-light.direction);
+    let diff = max(dot(world_normal, light_dir), 0.0);
+    return diff * light.color * light.intensity;
+}
+
+fn point_lighting(point_lights: PointLights, world_position: vec3<f32>, world_normal: vec3<f32>) -> vec3<f32> {
+    var lighting = vec3<f32>(0.0, 0.0, 0.0);
+    for (var i = 0u; i < point_lights.count; i = i + 1u) {
+        let light_pos = point_lights.lights[i].position;
+        let light_color = point_lights.lights[i].color;
+        let light_intensity = point_lights.lights[i].intensity;
+        
+        let to_light = light_pos - world_position;
+        let distance = length(to_light);
+        let light_direction = normalize(to_light);
+        
+        let attenuation = light_intensity / max(distance * distance, 0.1);
+        let diffuse_strength = max(dot(world_normal, light_direction), 0.0);
+        
+        lighting = lighting + (light_color * diffuse_strength * attenuation);
+    }
+    return lighting;
+}
@@ -1,115 +1,64 @@
-struct CameraUniform {
-    view_proj: mat4x4<f32>,
-};
+#include "structs.wgsl"
+#include "lighting.wgsl"
+
+// variant switches, set through ARBITRA_SHADER_DEFINES (e.g. "ALPHA_MASK,ALPHA_CUTOFF=0.3")
+#ifndef ALPHA_CUTOFF
+#define ALPHA_CUTOFF 0.5
+#endif
+
 @group(0) @binding(0)
 var<uniform> camera: CameraUniform;
 
-struct ModelUniform {
-    model: mat4x4<f32>,
-    emissive: f32,
-    _padding1: f32,
-    _padding2: f32,
-    _padding3: f32,
-};
 @group(1) @binding(0)
-var<uniform> model_uniform: ModelUniform;
-
-struct Light {
-    direction: vec3<f32>,
-    _padding1: f32,
-    color: vec3<f32>,
-    _padding2: f32,
-    intensity: f32,
-    ambient_strength: f32,
-    _padding3: f32,
-    _padding4: f32,
-};
-@group(2) @binding(0)
 var<uniform> light: Light;
-
-const MAX_POINT_LIGHTS: u32 = 8u;
-
-struct PointLight {
-    position: vec3<f32>,
-    intensity: f32,
-    color: vec3<f32>,
-    _padding: f32,
-};
-
-struct PointLights {
-    lights: array<PointLight, MAX_POINT_LIGHTS>,
-    count: u32,
-    _padding1: f32,
-    _padding2: f32,
-    _padding3: f32,
-};
-@group(2) @binding(1)
+@group(1) @binding(1)
 var<uniform> point_lights: PointLights;
 
-@group(3) @binding(0)
+@group(2) @binding(0)
 var t_albedo: texture_2d<f32>;
-@group(3) @binding(1)
+@group(2) @binding(1)
 var s_albedo: sampler;
 
-struct VertexInput {
-    @location(0) position: vec3<f32>, 
-    @location(1) color: vec3<f32>,
-    @location(2) normal: vec3<f32>,
-    @location(3) uv: vec2<f32>,
-}
-
-struct VertexOutput {
-    @builtin(position) clip_position: vec4<f32>,  
-    @location(0) color: vec3<f32>,
-    @location(1) world_normal: vec3<f32>,
-    @location(2) world_position: vec3<f32>,
-    @location(3) uv: vec2<f32>,
-}
 @vertex
-fn vs_main(input: VertexInput) -> VertexOutput {
+fn vs_main(input: VertexInput, instance: InstanceInput) -> VertexOutput {
     var output: VertexOutput;
-    let world_position = model_uniform.model * vec4<f32>(input.position, 1.0);
+    let model = mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let world_position = model * vec4<f32>(input.position, 1.0);
     output.clip_position = camera.view_proj * world_position;
     output.world_position = world_position.xyz;
 
-    let world_normal = (model_uniform.model * vec4<f32>(input.normal, 0.0)).xyz;
+    let world_normal = (model * vec4<f32>(input.normal, 0.0)).xyz;
     output.world_normal = normalize(world_normal);
 
     output.color = input.color;
     output.uv = input.uv;
+    output.emissive = instance.emissive;
+    output.vertex_color_mode = instance.vertex_color_mode;
+    output.base_color = instance.base_color;
     return output;
 }
 
 @fragment
 fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    let albedo = textureSample(t_albedo, s_albedo, input.uv).rgb;
-
-    let ambient = light.ambient_strength * vec3<f32>(1.0, 1.0, 1.0);
-
-    let light_dir = normalize(-light.direction);
-    let diff = max(dot(input.world_normal, light_dir), 0.0);
-    let directional_lighting = diff * light.color * light.intensity;
-
-    var point_lighting = vec3<f32>(0.0, 0.0, 0.0);
-    for (var i = 0u; i < point_lights.count; i = i + 1u) {
-        let light_pos = point_lights.lights[i].position;
-        let light_color = point_lights.lights[i].color;
-        let light_intensity = point_lights.lights[i].intensity;
-        
-        let to_light = light_pos - input.world_position;
-        let distance = length(to_light);
-        let light_direction = normalize(to_light);
-        
-        let attenuation = light_intensity / max(distance * distance, 0.1);
-        let diffuse_strength = max(dot(input.world_normal, light_direction), 0.0);
-        
-        point_lighting = point_lighting + (light_color * diffuse_strength * attenuation);
+    let albedo_sample = textureSample(t_albedo, s_albedo, input.uv) * input.base_color;
+#ifdef ALPHA_MASK
+    if albedo_sample.a < ALPHA_CUTOFF {
+        discard;
+    }
+#endif
+    var albedo = albedo_sample.rgb;
+    if input.vertex_color_mode == VERTEX_COLOR_MULTIPLY {
+        albedo *= input.color;
+    } else if input.vertex_color_mode == VERTEX_COLOR_REPLACE {
+        albedo = input.color;
     }
 
-    let lighting = ambient + directional_lighting + point_lighting;
+    let lighting = ambient_lighting(light)
+        + directional_lighting(light, input.world_normal)
+        + point_lighting(point_lights, input.world_position, input.world_normal);
     let lit_color = albedo * lighting;
 
-    let final_color = lit_color + (albedo * model_uniform.emissive);
+    let final_color = lit_color + (albedo * input.emissive);
 
     return vec4<f32>(final_color, 1.0);
-}
\ No newline at end of file
+}
@@ -0,0 +1,68 @@
+// struct layouts shared with the Rust side (renderer.rs); checked against the
+// Rust Pod structs at startup, so keep member names and order in sync
+
+const MAX_POINT_LIGHTS: u32 = 8u;
+
+// Material vertex_color modes (VertexColorMode::shader_value)
+const VERTEX_COLOR_IGNORE: u32 = 0u;
+const VERTEX_COLOR_MULTIPLY: u32 = 1u;
+const VERTEX_COLOR_REPLACE: u32 = 2u;
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+};
+
+struct Light {
+    direction: vec3<f32>,
+    _padding1: f32,
+    color: vec3<f32>,
+    _padding2: f32,
+    intensity: f32,
+    ambient_strength: f32,
+    _padding3: f32,
+    _padding4: f32,
+};
+
+struct PointLight {
+    position: vec3<f32>,
+    intensity: f32,
+    color: vec3<f32>,
+    _padding: f32,
+};
+
+struct PointLights {
+    lights: array<PointLight, MAX_POINT_LIGHTS>,
+    count: u32,
+    _padding1: f32,
+    _padding2: f32,
+    _padding3: f32,
+};
+
+struct VertexInput {
+    @location(0) position: vec3<f32>, 
+    @location(1) color: vec3<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) uv: vec2<f32>,
+}
+
+// per-instance data from the instance-rate vertex buffer
+struct InstanceInput {
+    @location(4) model_0: vec4<f32>,
+    @location(5) model_1: vec4<f32>,
+    @location(6) model_2: vec4<f32>,
+    @location(7) model_3: vec4<f32>,
+    @location(8) emissive: f32,
+    @location(9) vertex_color_mode: u32,
+    @location(10) base_color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,  
+    @location(0) color: vec3<f32>,
+    @location(1) world_normal: vec3<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) uv: vec2<f32>,
+    @location(4) emissive: f32,
+    @location(5) @interpolate(flat) vertex_color_mode: u32,
+    @location(6) base_color: vec4<f32>,
+}
@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::object::MeshData;
+use crate::vertex::Vertex;
+
+// 80-byte header, then the triangle count
+const BINARY_HEADER: usize = 84;
+// normal, three corners and a 2-byte attribute
+const BINARY_TRIANGLE: usize = 50;
+
+/// ASCII or binary STL. Facets sharing a corner position share the vertex, and the normals are left to
+/// be generated, since the stored facet normals are often missing or wrong. `bytes` are the contents of the file at `path`
+pub fn load(path: &Path, bytes: &[u8]) -> Result<MeshData, String> {
+    let triangles = if is_binary(bytes) {
+        read_binary(bytes)
+    } else {
+        let text = std::str::from_utf8(bytes).map_err(|_| format!("{}: not a binary STL and not ASCII text", path.display()))?;
+        read_ascii(text).map_err(|e| format!("{}: {}", path.display(), e))?
+    };
+
+    // STL repeats every corner for each facet; welding them keeps meshes within the vertex limit and lets
+    // normal generation smooth across facets (it splits the vertices again where faces need different normals)
+    let mut mesh = MeshData { has_normals: false, ..MeshData::new() };
+    let mut shared: HashMap<[u32; 3], u32> = HashMap::new();
+    for position in triangles.into_iter().flatten() {
+        // adding 0.0 turns -0.0 into 0.0, so both land on the same vertex
+        let key = position.map(|coordinate| (coordinate + 0.0).to_bits());
+        let index = *shared.entry(key).or_insert_with(|| {
+            mesh.vertices.push(Vertex { position, color: [1.0; 3], normal: [0.0, 1.0, 0.0], uv: [0.0; 2] });
+            mesh.vertices.len() as u32 - 1
+        });
+        mesh.indices.push(index);
+    }
+    Ok(mesh)
+}
+
+// binary files may also start with "solid", so the size decides
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.len() >= BINARY_HEADER && {
+        let count = u32::from_le_bytes([bytes[80], bytes[81], bytes[82], bytes[83]]) as usize;
+        bytes.len() == BINARY_HEADER + count * BINARY_TRIANGLE
+    }
+}
+
+fn read_binary(bytes: &[u8]) -> Vec<[[f32; 3]; 3]> {
+    let float = |at: usize| f32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]]);
+    bytes[BINARY_HEADER..].chunks_exact(BINARY_TRIANGLE)
+        .enumerate()
+        .map(|(triangle, _)| {
+            // skip the stored normal
+            let start = BINARY_HEADER + triangle * BINARY_TRIANGLE + 12;
+            std::array::from_fn(|corner| std::array::from_fn(|axis| float(start + corner * 12 + axis * 4)))
+        })
+        .collect()
+}
+
+// only the `vertex x y z` lines matter; every three make a facet
+fn read_ascii(text: &str) -> Result<Vec<[[f32; 3]; 3]>, String> {
+    if !text.trim_start().starts_with("solid") {
+        return Err("not an STL file (no 'solid' at the start and the size doesn't match a binary STL)".to_string());
+    }
+    let mut corners = Vec::new();
+    let mut words = text.split_whitespace();
+    while let Some(word) = words.next() {
+        if word == "vertex" {
+            let mut corner = [0.0; 3];
+            for value in &mut corner {
+                let word = words.next().ok_or("the file ends inside a vertex")?;
+                *value = word.parse().map_err(|_| format!("invalid number '{}'", word))?;
+            }
+            corners.push(corner);
+        }
+    }
+    if corners.len() % 3 != 0 {
+        return Err(format!("{} vertices don't make whole triangles", corners.len()));
+    }
+    Ok(corners.chunks_exact(3).map(|corners| [corners[0], corners[1], corners[2]]).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::normals::{self, NormalMode};
+
+    // the four faces of a tetrahedron, wound outwards
+    const TETRAHEDRON: [[[f32; 3]; 3]; 4] = [
+        [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+        [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+        [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    ];
+
+    fn binary(facets: &[[[f32; 3]; 3]]) -> Vec<u8> {
+        let mut bytes = vec![b' '; 80];
+        bytes.extend_from_slice(&(facets.len() as u32).to_le_bytes());
+        for facet in facets {
+            // a stored normal that is wrong on purpose; it is ignored
+            bytes.extend(std::iter::repeat_n(0.0f32, 3).chain(facet.iter().flatten().copied()).flat_map(f32::to_le_bytes));
+            bytes.extend_from_slice(&[0, 0]);
+        }
+        bytes
+    }
+
+    fn ascii(facets: &[[[f32; 3]; 3]]) -> String {
+        let mut text = String::from("solid tetrahedron\n");
+        for facet in facets {
+            text.push_str("  facet normal 0 0 0\n    outer loop\n");
+            for [x, y, z] in facet {
+                text.push_str(&format!("      vertex {} {} {}\n", x, y, z));
+            }
+            text.push_str("    endloop\n  endfacet\n");
+        }
+        text + "endsolid tetrahedron\n"
+    }
+
+    #[test]
+    fn binary_and_ascii_files_share_corners() {
+        for (kind, bytes) in [("binary", binary(&TETRAHEDRON)), ("ASCII", ascii(&TETRAHEDRON).into_bytes())] {
+            let mesh = load(Path::new("tetrahedron.stl"), &bytes).unwrap();
+            assert_eq!(mesh.vertices.len(), 4, "{}", kind);
+            assert_eq!(mesh.indices.len(), 12, "{}", kind);
+            assert!(!mesh.has_normals, "{}", kind);
+            let corners: Vec<[f32; 3]> = mesh.indices.iter().map(|&index| mesh.vertices[index as usize].position).collect();
+            assert_eq!(corners, TETRAHEDRON.iter().flatten().copied().collect::<Vec<_>>(), "{}", kind);
+        }
+        assert!(load(Path::new("broken.stl"), b"solid x\nvertex 1 2").is_err());
+    }
+
+    #[test]
+    fn large_flat_files_stay_within_the_vertex_limit() {
+        // a 150 x 100 grid of 30000 facets, more than 65536 / 3
+        let mut facets = Vec::new();
+        for row in 0..100 {
+            for column in 0..150 {
+                let [x, z] = [column as f32, row as f32];
+                facets.push([[x, 0.0, z], [x, 0.0, z + 1.0], [x + 1.0, 0.0, z]]);
+                facets.push([[x + 1.0, 0.0, z], [x, 0.0, z + 1.0], [x + 1.0, 0.0, z + 1.0]]);
+            }
+        }
+        let mesh = load(Path::new("grid.stl"), &binary(&facets)).unwrap();
+        assert_eq!(mesh.vertices.len(), 151 * 101);
+        let (vertices, _) = normals::generate_normals(&mesh.vertices, &mesh.indices, NormalMode::default());
+        assert_eq!(vertices.len(), 151 * 101);
+        assert!(vertices.iter().all(|vertex| vertex.normal == [0.0, 1.0, 0.0]));
+    }
+}
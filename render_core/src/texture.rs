@@ -14,8 +14,19 @@ impl Texture {
         queue: &wgpu::Queue,
         path: &str,
     ) -> Result<Self> {
-        let img = image::open(path)
-            .with_context(|| format!("Failed to load texture: {}", path))?;
+        // `<file>#image<n>` is an image stored inside a glTF/GLB file
+        let img = match path.split_once('#') {
+            Some((file, fragment)) => {
+                let index = fragment.strip_prefix("image").and_then(|index| index.parse().ok())
+                    .with_context(|| format!("Invalid embedded image '{}' in texture: {}", fragment, path))?;
+                let bytes = crate::gltf::Gltf::open(std::path::Path::new(file))
+                    .and_then(|gltf| gltf.image_bytes(index))
+                    .map_err(anyhow::Error::msg)
+                    .with_context(|| format!("Failed to load texture: {}", path))?;
+                image::load_from_memory(&bytes)
+            }
+            None => image::open(path),
+        }.with_context(|| format!("Failed to load texture: {}", path))?;
         
         Self::from_image(device, queue, &img)
     }
@@ -1,10 +1,64 @@
-use cgmath::{Matrix4, Vector3, Deg};
+use cgmath::{Deg, InnerSpace, Matrix3, Matrix4, Quaternion, Rad, Rotation, Rotation3, SquareMatrix, Vector3};
+
+// below this, cos(middle angle) counts as zero and the Euler angles are gimbal locked
+const GIMBAL_LOCK_EPSILON: f32 = 1e-6;
+
+/// Order in which Euler angles are applied; `Xyz` rotates about X first, then Y, then Z
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RotationOrder {
+    #[default]
+    Xyz,
+    Xzy,
+    Yxz,
+    Yzx,
+    Zxy,
+    Zyx,
+}
+
+impl RotationOrder {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "xyz" => Some(RotationOrder::Xyz),
+            "xzy" => Some(RotationOrder::Xzy),
+            "yxz" => Some(RotationOrder::Yxz),
+            "yzx" => Some(RotationOrder::Yzx),
+            "zxy" => Some(RotationOrder::Zxy),
+            "zyx" => Some(RotationOrder::Zyx),
+            _ => None,
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn name(self) -> &'static str {
+        match self {
+            RotationOrder::Xyz => "xyz",
+            RotationOrder::Xzy => "xzy",
+            RotationOrder::Yxz => "yxz",
+            RotationOrder::Yzx => "yzx",
+            RotationOrder::Zxy => "zxy",
+            RotationOrder::Zyx => "zyx",
+        }
+    }
+
+    // axis indices (0 = x) in the order they are applied
+    fn axes(self) -> [usize; 3] {
+        match self {
+            RotationOrder::Xyz => [0, 1, 2],
+            RotationOrder::Xzy => [0, 2, 1],
+            RotationOrder::Yxz => [1, 0, 2],
+            RotationOrder::Yzx => [1, 2, 0],
+            RotationOrder::Zxy => [2, 0, 1],
+            RotationOrder::Zyx => [2, 1, 0],
+        }
+    }
+}
 
 /// Represents a 3D transformation (position, rotation, scale)
 #[derive(Debug, Clone, Copy)]
 pub struct Transform {
     pub position: [f32; 3],
-    pub rotation: [f32; 3],  // Euler angles in degrees (x, y, z)
+    // unit quaternion
+    pub rotation: Quaternion<f32>,
     pub scale: [f32; 3],
 }
 
@@ -12,7 +66,7 @@ impl Transform {
     pub fn new() -> Self {
         Transform {
             position: [0.0, 0.0, 0.0],
-            rotation: [0.0, 0.0, 0.0],
+            rotation: Quaternion::new(1.0, 0.0, 0.0, 0.0),
             scale: [1.0, 1.0, 1.0],
         }
     }
@@ -23,9 +77,10 @@ impl Transform {
         self
     }
 
+    // Euler angles in degrees, applied X -> Y -> Z
     #[allow(dead_code)]
     pub fn with_rotation(mut self, x: f32, y: f32, z: f32) -> Self {
-        self.rotation = [x, y, z];
+        self.rotation = Self::rotation_from_euler([x, y, z], RotationOrder::Xyz);
         self
     }
 
@@ -35,19 +90,81 @@ impl Transform {
         self
     }
 
+    /// Quaternion for Euler angles in degrees, given per axis (x, y, z) and applied in `order`
+    pub fn rotation_from_euler(degrees: [f32; 3], order: RotationOrder) -> Quaternion<f32> {
+        let axis_rotation = |axis: usize| {
+            let angle = Deg(degrees[axis]);
+            match axis {
+                0 => Quaternion::from_angle_x(angle),
+                1 => Quaternion::from_angle_y(angle),
+                _ => Quaternion::from_angle_z(angle),
+            }
+        };
+        let [first, second, third] = order.axes();
+        axis_rotation(third) * axis_rotation(second) * axis_rotation(first)
+    }
+
+    /// Euler angles in degrees (x, y, z) that rebuild this rotation when applied in `order`.
+    /// The middle axis stays within ±90°; at exactly ±90° the last axis is set to 0.
+    #[allow(dead_code)]
+    pub fn to_euler(self, order: RotationOrder) -> [f32; 3] {
+        let [i, j, k] = order.axes();
+        // +1 when the axes are a cyclic permutation of x, y, z
+        let sign = if (j + 3 - i) % 3 == 1 { 1.0 } else { -1.0 };
+        // cgmath matrices are column-major, so m[col][row]
+        let m = Matrix3::from(self.rotation.normalize());
+        let r = |row: usize, col: usize| m[col][row];
+
+        let sin_second = (-sign * r(k, i)).clamp(-1.0, 1.0);
+        let second = sin_second.asin();
+        let (first, third) = if 1.0 - sin_second.abs() > GIMBAL_LOCK_EPSILON {
+            (
+                (sign * r(k, j)).atan2(r(k, k)),
+                (sign * r(j, i)).atan2(r(i, i)),
+            )
+        } else {
+            ((-sign * r(j, k)).atan2(r(j, j)), 0.0)
+        };
+
+        let mut degrees = [0.0; 3];
+        degrees[i] = Deg::from(Rad(first)).0;
+        degrees[j] = Deg::from(Rad(second)).0;
+        degrees[k] = Deg::from(Rad(third)).0;
+        degrees
+    }
+
+    /// Blend towards `other`: position and scale linearly, rotation along the shortest arc
+    #[allow(dead_code)]
+    pub fn interpolate(&self, other: &Transform, t: f32) -> Transform {
+        let lerp = |a: [f32; 3], b: [f32; 3]| std::array::from_fn(|axis| a[axis] + (b[axis] - a[axis]) * t);
+        Transform {
+            position: lerp(self.position, other.position),
+            rotation: self.rotation.slerp(other.rotation, t),
+            scale: lerp(self.scale, other.scale),
+        }
+    }
+
+    /// `child` placed inside this transform, e.g. to move a root object by an offset.
+    /// Exact when this transform's scale is uniform; otherwise the shear that results is dropped
+    pub fn combine(&self, child: &Transform) -> Transform {
+        let scaled: [f32; 3] = std::array::from_fn(|axis| child.position[axis] * self.scale[axis]);
+        let offset = self.rotation.rotate_vector(Vector3::from(scaled));
+        Transform {
+            position: std::array::from_fn(|axis| self.position[axis] + offset[axis]),
+            rotation: self.rotation * child.rotation,
+            scale: std::array::from_fn(|axis| self.scale[axis] * child.scale[axis]),
+        }
+    }
+
     /// Convert transform to a 4x4 model matrix
-    pub fn to_matrix(&self) -> Matrix4<f32> {
+    pub fn to_matrix(self) -> Matrix4<f32> {
         let translation = Matrix4::from_translation(Vector3::new(
             self.position[0],
             self.position[1],
             self.position[2],
         ));
 
-        // Apply rotations in order: X -> Y -> Z (Euler angles)
-        let rotation_x = Matrix4::from_angle_x(Deg(self.rotation[0]));
-        let rotation_y = Matrix4::from_angle_y(Deg(self.rotation[1]));
-        let rotation_z = Matrix4::from_angle_z(Deg(self.rotation[2]));
-        let rotation = rotation_z * rotation_y * rotation_x;
+        let rotation = Matrix4::from(self.rotation);
 
         let scale = Matrix4::from_nonuniform_scale(
             self.scale[0],
@@ -58,6 +175,35 @@ impl Transform {
         // Combine: translate * rotate * scale
         translation * rotation * scale
     }
+
+    /// Split a model matrix back into position, rotation and scale. A mirroring matrix gets a
+    /// negative X scale; shear can't be represented and is dropped
+    pub fn from_matrix(matrix: Matrix4<f32>) -> Transform {
+        let mut axes = [matrix.x.truncate(), matrix.y.truncate(), matrix.z.truncate()];
+        let mut scale = axes.map(|axis| axis.magnitude());
+        if Matrix3::from_cols(axes[0], axes[1], axes[2]).determinant() < 0.0 {
+            scale[0] = -scale[0];
+        }
+        for (axis, scale) in axes.iter_mut().zip(scale) {
+            if scale != 0.0 {
+                *axis /= scale;
+            }
+        }
+        // re-orthogonalize so shear or rounding still leaves a pure rotation
+        let x = axes[0].normalize();
+        let z = x.cross(axes[1]).normalize();
+        let y = z.cross(x);
+        let rotation = if [x, y, z].iter().all(|axis| axis.x.is_finite()) {
+            Quaternion::from(Matrix3::from_cols(x, y, z)).normalize()
+        } else {
+            Quaternion::new(1.0, 0.0, 0.0, 0.0)
+        };
+        Transform {
+            position: matrix.w.truncate().into(),
+            rotation,
+            scale,
+        }
+    }
 }
 
 impl Default for Transform {
@@ -65,3 +211,85 @@ impl Default for Transform {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORDERS: [RotationOrder; 6] = [
+        RotationOrder::Xyz, RotationOrder::Xzy, RotationOrder::Yxz,
+        RotationOrder::Yzx, RotationOrder::Zxy, RotationOrder::Zyx,
+    ];
+
+    // q and -q are the same rotation
+    fn same_rotation(a: Quaternion<f32>, b: Quaternion<f32>) -> bool {
+        a.dot(b).abs() > 1.0 - 1e-5
+    }
+
+    #[test]
+    fn euler_angles_round_trip_in_every_order() {
+        for order in ORDERS {
+            assert_eq!(RotationOrder::from_name(order.name()), Some(order));
+            let [first, second, third] = order.axes();
+            // (first, middle, last) angles; the middle one stays inside ±90° so the angles come back as they are
+            for angles in [[0.0, 0.0, 0.0], [30.0, -45.0, 60.0], [-170.0, 80.0, 10.0], [90.0, 10.0, -135.0]] {
+                let mut degrees = [0.0; 3];
+                degrees[first] = angles[0];
+                degrees[second] = angles[1];
+                degrees[third] = angles[2];
+                let rotation = Transform::rotation_from_euler(degrees, order);
+                let transform = Transform { rotation, ..Transform::new() };
+                let euler = transform.to_euler(order);
+                // away from gimbal lock the angles themselves come back
+                assert!(euler.iter().zip(degrees).all(|(a, b)| (a - b).abs() < 1e-2), "{:?}: {:?} became {:?}", order, degrees, euler);
+                // and as a file would store them, they rebuild the same rotation
+                let saved: Vec<f32> = euler.iter().map(|angle| format!("{}", angle).parse().unwrap()).collect();
+                let loaded = Transform::rotation_from_euler([saved[0], saved[1], saved[2]], order);
+                assert!(same_rotation(rotation, loaded), "{:?}: {:?}", order, degrees);
+            }
+        }
+    }
+
+    #[test]
+    fn gimbal_locked_angles_rebuild_the_same_rotation() {
+        for order in ORDERS {
+            let [first, second, third] = order.axes();
+            for middle in [90.0, -90.0] {
+                let mut degrees = [0.0; 3];
+                degrees[first] = 25.0;
+                degrees[second] = middle;
+                degrees[third] = 40.0;
+                let rotation = Transform::rotation_from_euler(degrees, order);
+                let euler = Transform { rotation, ..Transform::new() }.to_euler(order);
+                assert!(euler.iter().all(|angle| angle.is_finite()));
+                assert!((euler[second] - middle).abs() < 1e-2, "{:?}: middle angle {:?}", order, euler);
+                assert_eq!(euler[third], 0.0, "{:?}: the last angle is zeroed at gimbal lock", order);
+                assert!(same_rotation(rotation, Transform::rotation_from_euler(euler, order)), "{:?}: {:?} became {:?}", order, degrees, euler);
+            }
+        }
+    }
+
+    #[test]
+    fn interpolate_slerps_between_the_endpoints() {
+        let a = Transform::new().with_position(0.0, 2.0, 0.0).with_scale(1.0, 1.0, 1.0);
+        let b = Transform::new().with_position(4.0, 2.0, -2.0).with_rotation(0.0, 90.0, 0.0).with_scale(3.0, 1.0, 2.0);
+
+        let start = a.interpolate(&b, 0.0);
+        let end = a.interpolate(&b, 1.0);
+        assert_eq!(start.position, a.position);
+        assert!(same_rotation(start.rotation, a.rotation));
+        assert_eq!(end.position, b.position);
+        assert_eq!(end.scale, b.scale);
+        assert!(same_rotation(end.rotation, b.rotation));
+
+        let middle = a.interpolate(&b, 0.5);
+        assert_eq!(middle.position, [2.0, 2.0, -1.0]);
+        assert_eq!(middle.scale, [2.0, 1.0, 1.5]);
+        assert!(same_rotation(middle.rotation, Transform::rotation_from_euler([0.0, 45.0, 0.0], RotationOrder::Xyz)));
+        assert!((middle.rotation.magnitude() - 1.0).abs() < 1e-5);
+
+        // the shortest arc, even when the quaternions have opposite signs
+        let flipped = Transform { rotation: -b.rotation, ..b };
+        assert!(same_rotation(a.interpolate(&flipped, 0.5).rotation, middle.rotation));
+    }
+}
@@ -1,6 +1,3 @@
-use wgpu;
-use bytemuck;
-
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
@@ -0,0 +1,64 @@
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+/// Watches a directory tree and reports changed files with the given extensions
+pub struct AssetWatcher {
+    // kept alive so the OS watch isn't dropped
+    _watcher: notify::RecommendedWatcher,
+    receiver: Receiver<notify::Result<Event>>,
+    extensions: Vec<&'static str>,
+}
+
+impl AssetWatcher {
+    pub fn new<P: AsRef<Path>>(root: P, extensions: &[&'static str]) -> notify::Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        watcher.watch(root.as_ref(), RecursiveMode::Recursive)?;
+
+        Ok(AssetWatcher {
+            _watcher: watcher,
+            receiver,
+            extensions: extensions.to_vec(),
+        })
+    }
+
+    // drain pending events without blocking; editors often emit several events per save,
+    // so paths are deduplicated
+    pub fn poll_changes(&self) -> Vec<PathBuf> {
+        let mut seen = HashSet::new();
+        let mut changed = Vec::new();
+
+        for result in self.receiver.try_iter() {
+            let event = match result {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("File watcher error: {}", e);
+                    continue;
+                }
+            };
+            if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+                continue;
+            }
+            for path in event.paths {
+                let watched = path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| self.extensions.contains(&ext));
+                if watched && seen.insert(path.clone()) {
+                    changed.push(path);
+                }
+            }
+        }
+
+        changed
+    }
+}
+
+// true if both paths resolve to the same file on disk
+pub fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}